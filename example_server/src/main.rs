@@ -24,6 +24,7 @@ impl AbstractMCPServer for ExampleServer {
                     "bar": {"type": "string"}
                 }
             }),
+            output_schema: None,
             annotations: None,
         }]
     }
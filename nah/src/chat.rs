@@ -11,15 +11,19 @@ use std::io::Write;
 use std::thread::sleep;
 use std::time::SystemTime;
 
+use crate::config::{ModelApi, ModelConfig};
 use crate::editor::launch_editor;
+use crate::history::Manifest;
+use crate::secret;
 use crate::types::NahError;
 use crate::AppContext;
-use crate::ModelConfig;
 use futures_util::pin_mut;
 use futures_util::stream::StreamExt;
 use nah_chat::{
   ChatClient, ChatCompletionParamsBuilder, ChatCompletionStreamEvent, ChatMessage,
-  ChatMessageContentValue, ToolCallRequest,
+  ChatMessageContentValue, FunctionCallRequest, ResponseFunctionCallItem,
+  ResponseFunctionCallOutputItem, ResponseInputItem, ResponseMessageContent, ResponseMessageItem,
+  ResponseObject, ResponsesInput, ResponsesParamsBuilder, ResponsesStreamEvent, ToolCallRequest,
 };
 use serde_json::{json, Value};
 use std::fs::{File, OpenOptions};
@@ -29,6 +33,7 @@ use tokio::runtime::{Builder, Runtime};
 struct ChatContext {
   tools: Vec<Value>,
   tool_name_to_server_map: HashMap<String, String>,
+  model_name: String,
   model_config: ModelConfig,
   messages: Vec<ChatMessage>,
   tokio_runtime: Runtime,
@@ -37,27 +42,45 @@ struct ChatContext {
 }
 const MESSAGE_FILE_PATH: &'static str = ".nah_user_message";
 
-pub fn process_chat(context: &mut AppContext) {
+/**
+ * Defaults of sampling parameters when a model profile doesn't set them.
+ */
+const DEFAULT_MAX_TOKENS: usize = 4096;
+const DEFAULT_TEMPERATURE: f64 = 0.7;
+const DEFAULT_TOP_P: f64 = 0.9;
+const DEFAULT_FREQUENCY_PENALTY: f64 = 0.5;
+
+/**
+ * Chat with the model profile `model_name`, which must exist in `context.models`.
+ */
+pub fn process_chat(context: &mut AppContext, model_name: &str) {
   let (tools, tool_name_to_server_map) = pull_tools(context).unwrap();
-  let model_config = context.model_config.clone().unwrap();
+  let model_config = context.models[model_name].clone();
   let timestamp = std::time::SystemTime::now()
     .duration_since(SystemTime::UNIX_EPOCH)
     .unwrap()
     .as_secs();
-  let mut history_file_path = context.history_path.clone();
-  history_file_path.push(format!("chat_{}.jsonl", timestamp));
+  let history_file_name = format!("chat_{}.jsonl", timestamp);
+  let history_file_path = context.history_path.join(&history_file_name);
+  let _ = Manifest::update(&context.history_path, |manifest| {
+    manifest.chats.push(history_file_name)
+  });
   let history_file = OpenOptions::new()
     .create(true)
     .append(true)
     .open(history_file_path)
     .unwrap();
-  let chat_client = ChatClient::init(
-    model_config.base_url.to_owned(),
-    model_config.auth_token.to_owned(),
-  );
+  let chat_client = match init_chat_client(&model_config) {
+    Ok(c) => c,
+    Err(e) => {
+      eprintln!("{}", e);
+      return;
+    }
+  };
   let mut chat_context = ChatContext {
     tools,
     tool_name_to_server_map,
+    model_name: model_name.to_string(),
     model_config,
     messages: Vec::new(),
     tokio_runtime: Builder::new_current_thread()
@@ -68,25 +91,17 @@ pub fn process_chat(context: &mut AppContext) {
     chat_client,
     history_file,
   };
-  chat_context
-    .model_config
-    .system_prompt
-    .as_ref()
-    .and_then(|sys| {
-      chat_context.messages.push(ChatMessage {
-        role: "system".to_string(),
-        content: ChatMessageContentValue::Text(sys.to_string()),
-        reasoning_content: None,
-        tool_call_id: None,
-        tool_calls: None,
-      });
-      Some(())
-    });
-  println!("Chat with model: {}", chat_context.model_config.model);
+  chat_context.apply_system_prompt();
+  println!(
+    "Chat with model: {} ({})",
+    chat_context.model_config.model, chat_context.model_name
+  );
 
   let mut rl = rustyline::DefaultEditor::new().unwrap();
   loop {
-    println!("Press [ENTER] to draft user message, `exit` to end this chat.");
+    println!(
+      "Press [ENTER] to draft user message, `/model [name]` to switch models, `exit` to end this chat."
+    );
     let command = rl.readline("[chat]>> ");
     match command {
       Ok(cmd) => {
@@ -142,6 +157,28 @@ pub fn process_chat(context: &mut AppContext) {
           }
         } else if trimed_cmd == "exit" {
           break;
+        } else if let Some(rest) = trimed_cmd.strip_prefix("/model") {
+          match rest.trim() {
+            "" => {
+              let mut names: Vec<&String> = context.models.keys().collect();
+              names.sort();
+              for name in names {
+                let mark = if *name == chat_context.model_name {
+                  "*"
+                } else {
+                  " "
+                };
+                println!("{} {} ({})", mark, name, context.models[name].model);
+              }
+            }
+            name => match context.models.get(name) {
+              Some(config) => match chat_context.switch_model(name, config.clone()) {
+                Ok(()) => println!("Switched to model: {} ({})", config.model, name),
+                Err(e) => eprintln!("{}", e),
+              },
+              None => println!("Model profile {} not found.", name),
+            },
+          }
         }
       }
       Err(rustyline::error::ReadlineError::Interrupted) => break,
@@ -156,6 +193,17 @@ pub fn process_chat(context: &mut AppContext) {
   let _ = fs::remove_file(MESSAGE_FILE_PATH);
 }
 
+/**
+ * Create a client for a model, resolving its auth token.
+ */
+fn init_chat_client(config: &ModelConfig) -> Result<ChatClient, NahError> {
+  let auth_token = match &config.auth_token {
+    Some(token) => Some(token.resolve()?),
+    None => None,
+  };
+  Ok(ChatClient::init(config.base_url.to_owned(), auth_token))
+}
+
 /**
  * Pull all available tools from the app context as the tools parameter accepted by OpenAI API.
  */
@@ -196,16 +244,95 @@ impl ChatContext {
     });
   }
 
+  /**
+   * Switch to another model profile, keeping the conversation.
+   */
+  fn switch_model(&mut self, name: &str, config: ModelConfig) -> Result<(), NahError> {
+    self.chat_client = init_chat_client(&config)?;
+    self.model_name = name.to_string();
+    self.model_config = config;
+    self.apply_system_prompt();
+    Ok(())
+  }
+
+  /**
+   * Replace the leading system message with the system prompt of the model.
+   */
+  fn apply_system_prompt(&mut self) {
+    if self.messages.first().is_some_and(|m| m.role == "system") {
+      self.messages.remove(0);
+    }
+    if let Some(sys) = &self.model_config.system_prompt {
+      self.messages.insert(
+        0,
+        ChatMessage {
+          role: "system".to_string(),
+          content: ChatMessageContentValue::Text(sys.to_string()),
+          reasoning_content: None,
+          tool_call_id: None,
+          tool_calls: None,
+        },
+      );
+    }
+  }
+
+  /**
+   * Messages sent to the model: the system prompt and the latest turns that fit
+   * in the context window.
+   */
+  fn context_messages(&self) -> Vec<&ChatMessage> {
+    let start = match self.model_config.context_window {
+      None => 0,
+      Some(window) => {
+        first_message_in_window(&self.messages, window.saturating_sub(self.max_tokens()))
+      }
+    };
+    self
+      .messages
+      .iter()
+      .enumerate()
+      .filter(|(idx, m)| *idx >= start || (*idx == 0 && m.role == "system"))
+      .map(|(_, m)| m)
+      .collect()
+  }
+
+  fn max_tokens(&self) -> usize {
+    self.model_config.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS)
+  }
+
   /**
    * Generate assistant message.
    */
   pub fn generate(&mut self) -> Result<&ChatMessage, NahError> {
+    let message = match self.model_config.api {
+      ModelApi::ChatCompletions => self.generate_chat_completion(),
+      ModelApi::Responses => self.generate_response(),
+    };
+
+    match message {
+      Ok(msg) => {
+        self.push_message(msg);
+        Ok(&self.messages[self.messages.len() - 1])
+      }
+      Err(e) => Err(NahError::model_invalid_response(
+        &self.model_config.model,
+        Some(Box::new(e)),
+      )),
+    }
+  }
+
+  fn generate_chat_completion(&self) -> Result<ChatMessage, NahError> {
     let mut params_builder = ChatCompletionParamsBuilder::new();
     params_builder
-      .max_tokens(4096)
-      .temperature(0.7)
-      .top_p(0.9)
-      .frequency_penalty(0.5)
+      .max_tokens(self.max_tokens())
+      .temperature(self.model_config.temperature.unwrap_or(DEFAULT_TEMPERATURE))
+      .top_p(self.model_config.top_p.unwrap_or(DEFAULT_TOP_P))
+      .frequency_penalty(
+        self
+          .model_config
+          .frequency_penalty
+          .unwrap_or(DEFAULT_FREQUENCY_PENALTY),
+      )
       .insert("tools", json!(self.tools.clone()));
 
     self
@@ -220,10 +347,11 @@ impl ChatContext {
         Some(())
       });
 
-    let message: Result<ChatMessage, NahError> = self.tokio_runtime.block_on(async {
+    let messages = self.context_messages();
+    self.tokio_runtime.block_on(async {
       let stream = match self
         .chat_client
-        .chat_completion_stream(&self.model_config.model, &self.messages, &params_builder)
+        .chat_completion_stream(&self.model_config.model, messages, &params_builder)
         .await
       {
         Ok(s) => s,
@@ -267,18 +395,107 @@ impl ChatContext {
       }
       println!("\nModel finished generation!");
       Ok(message)
-    });
+    })
+  }
 
-    match message {
-      Ok(msg) => {
-        self.push_message(msg);
-        Ok(&self.messages[self.messages.len() - 1])
+  /**
+   * Generate assistant message with the Responses API. Messages are converted
+   * to input items, and the output items back to a message.
+   */
+  fn generate_response(&self) -> Result<ChatMessage, NahError> {
+    let mut params_builder = ResponsesParamsBuilder::new();
+    params_builder.max_output_tokens(self.max_tokens());
+    if let Some(t) = self.model_config.temperature {
+      params_builder.temperature(t);
+    }
+    if let Some(p) = self.model_config.top_p {
+      params_builder.top_p(p);
+    }
+    // Function tools of the Responses API are flat
+    let tools: Vec<Value> = self
+      .tools
+      .iter()
+      .map(|tool| {
+        let mut function = tool["function"].clone();
+        function["type"] = json!("function");
+        function
+      })
+      .collect();
+    params_builder.tools(json!(tools));
+    if let Some(extra_params) = self
+      .model_config
+      .extra_params
+      .as_ref()
+      .and_then(|v| v.as_object())
+    {
+      for (key, value) in extra_params.iter() {
+        params_builder.insert(key, value.to_owned());
       }
-      Err(e) => Err(NahError::model_invalid_response(
-        &self.model_config.model,
-        Some(Box::new(e)),
-      )),
     }
+
+    let input = responses_input(&self.context_messages());
+    let params = params_builder.build();
+    self.tokio_runtime.block_on(async {
+      let stream = match self
+        .chat_client
+        .responses_stream(&self.model_config.model, &input, &params)
+        .await
+      {
+        Ok(s) => s,
+        Err(e) => {
+          return Err(NahError::model_error(
+            &self.model_config.model,
+            "Error in requesting responses from the model",
+            Some(Box::new(e)),
+          ));
+        }
+      };
+
+      pin_mut!(stream);
+      print!("Model is responding ...");
+      let _ = std::io::stdout().flush();
+      let mut chunk_received = 0;
+      let mut response = None;
+
+      while let Some(event_result) = stream.next().await {
+        match event_result {
+          Ok(ResponsesStreamEvent::Completed(r)) | Ok(ResponsesStreamEvent::Incomplete(r)) => {
+            response = Some(r);
+          }
+          Ok(ResponsesStreamEvent::Failed(r)) => {
+            return Err(NahError::model_error(
+              &self.model_config.model,
+              &format!("Response failed: {}", r.error.unwrap_or(Value::Null)),
+              None,
+            ));
+          }
+          Ok(_) => {
+            chunk_received += 1;
+            print!(
+              "\rModel is responding ... {} chunks received.",
+              chunk_received
+            );
+            let _ = std::io::stdout().flush();
+          }
+          Err(e) => {
+            return Err(NahError::model_error(
+              &self.model_config.model,
+              "Error in receiving responses from the model",
+              Some(Box::new(e)),
+            ));
+          }
+        }
+      }
+      println!("\nModel finished generation!");
+      match response {
+        Some(r) => Ok(response_to_message(&r)),
+        None => Err(NahError::model_error(
+          &self.model_config.model,
+          "The response stream ended without a response",
+          None,
+        )),
+      }
+    })
   }
 
   fn process_tool_calls(&mut self, app: &mut AppContext) -> Result<(), NahError> {
@@ -317,6 +534,21 @@ impl ChatContext {
 
         let server = app.server_processes.get_mut(server_name).unwrap();
         let tool_definition = server.get_tool_definition(tool_name)?;
+        if let Err(e) = crate::json_schema::validate_arguments(tool_definition, &args) {
+          // Return the problems to the model so that it can fix the arguments and retry.
+          println!("[Tool: {}]: {}", server_name, e.message);
+          tool_call_responses.push(ChatMessage {
+            role: "tool".to_owned(),
+            content: ChatMessageContentValue::Text(format!(
+              "Error: {}\nThe tool was not called. Please fix the arguments and try again.",
+              e.message
+            )),
+            reasoning_content: None,
+            tool_call_id: Some(item.id.to_owned()),
+            tool_calls: None,
+          });
+          continue;
+        }
         if tool_definition.is_destructive() {
           if !crate::utils::ask_for_user_confirmation(
               &format!("Model requests to call tool {}, which is annotated as destructive. Do you still want to call? [N/y] > ", tool_definition.name),
@@ -344,15 +576,107 @@ impl ChatContext {
   }
 
   fn push_message(&mut self, msg: ChatMessage) {
-    let _ = self
-      .history_file
-      .write(serde_json::to_string(&msg).unwrap().as_bytes());
+    let mut line = serde_json::to_value(&msg).unwrap();
+    secret::mask(&mut line);
+    let _ = self.history_file.write(line.to_string().as_bytes());
     let _ = self.history_file.write(b"\n");
     let _ = self.history_file.flush();
     self.messages.push(msg);
   }
 }
 
+/**
+ * Index of the first message to send so that the conversation fits in
+ * `budget` tokens. Whole turns, from a user message to the next one, are
+ * dropped from the oldest, and the last turn is always kept. Tokens are
+ * estimated as 4 bytes each.
+ */
+fn first_message_in_window(messages: &[ChatMessage], budget: usize) -> usize {
+  let estimates: Vec<usize> = messages
+    .iter()
+    .map(|m| serde_json::to_string(m).unwrap().len() / 4 + 1)
+    .collect();
+  let mut total: usize = estimates.iter().sum();
+  let mut start = match messages.first() {
+    Some(m) if m.role == "system" => 1,
+    _ => 0,
+  };
+  let turn_starts: Vec<usize> = (start..messages.len())
+    .filter(|idx| messages[*idx].role == "user")
+    .collect();
+  for next_turn in turn_starts.into_iter().skip(1) {
+    if total <= budget {
+      break;
+    }
+    total -= estimates[start..next_turn].iter().sum::<usize>();
+    start = next_turn;
+  }
+  start
+}
+
+/**
+ * Convert chat messages to input items of the Responses API.
+ */
+fn responses_input(messages: &[&ChatMessage]) -> ResponsesInput {
+  let mut items = Vec::new();
+  for message in messages.iter() {
+    match message.role.as_str() {
+      "tool" => items.push(ResponseInputItem::FunctionCallOutput(
+        ResponseFunctionCallOutputItem {
+          item_type: Some("function_call_output".to_string()),
+          call_id: message.tool_call_id.clone().unwrap_or_default(),
+          output: message.content.to_string(),
+        },
+      )),
+      role => {
+        let text = message.content.to_string();
+        if !text.is_empty() || message.tool_calls.is_none() {
+          items.push(ResponseInputItem::Message(ResponseMessageItem {
+            item_type: Some("message".to_string()),
+            role: role.to_string(),
+            content: ResponseMessageContent::Text(text),
+          }));
+        }
+        for call in message.tool_calls.iter().flatten() {
+          items.push(ResponseInputItem::FunctionCall(ResponseFunctionCallItem {
+            item_type: Some("function_call".to_string()),
+            call_id: call.id.clone(),
+            name: call.function.name.clone(),
+            arguments: call.function.arguments.clone(),
+          }));
+        }
+      }
+    }
+  }
+  ResponsesInput::Items(items)
+}
+
+/**
+ * Convert output items of a response to an assistant message.
+ */
+fn response_to_message(response: &ResponseObject) -> ChatMessage {
+  let reasoning = response.reasoning_text();
+  let tool_calls: Vec<ToolCallRequest> = response
+    .function_calls()
+    .into_iter()
+    .map(|item| ToolCallRequest {
+      id: item.call_id.clone().or(item.id.clone()).unwrap_or_default(),
+      _type: "function".to_string(),
+      function: FunctionCallRequest {
+        name: item.name.clone().unwrap_or_default(),
+        arguments: item.arguments.clone().unwrap_or_default(),
+      },
+    })
+    .collect();
+  ChatMessage {
+    role: "assistant".to_string(),
+    content: ChatMessageContentValue::Text(response.output_text()),
+    reasoning_content: (!reasoning.is_empty()).then_some(reasoning),
+    tool_call_id: None,
+    tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+  }
+}
+
 fn unpack_mcp_text_contents(server_name: &str, result: &Value) -> Result<String, NahError> {
   let contents = match result
     .as_object()
@@ -429,3 +753,60 @@ fn open_file_or_throw() -> Result<File, NahError> {
   };
   Ok(file)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn message(role: &str, text: &str) -> ChatMessage {
+    ChatMessage {
+      role: role.to_string(),
+      content: ChatMessageContentValue::Text(text.to_string()),
+      reasoning_content: None,
+      tool_call_id: None,
+      tool_calls: None,
+    }
+  }
+
+  #[test]
+  fn test_first_message_in_window() {
+    let long_text = "x".repeat(400);
+    let messages = vec![
+      message("system", "You are helpful."),
+      message("user", &long_text),
+      message("assistant", &long_text),
+      message("user", &long_text),
+      message("tool", &long_text),
+      message("user", "Hi"),
+    ];
+    assert_eq!(first_message_in_window(&messages, 10000), 1);
+    assert_eq!(first_message_in_window(&messages, 300), 3);
+    // The last turn is kept even if it doesn't fit
+    assert_eq!(first_message_in_window(&messages, 0), 5);
+  }
+
+  #[test]
+  fn test_responses_input() {
+    let mut assistant = message("assistant", "");
+    assistant.tool_calls = Some(vec![ToolCallRequest {
+      id: "call_1".to_string(),
+      _type: "function".to_string(),
+      function: FunctionCallRequest {
+        name: "weather_get_forecast".to_string(),
+        arguments: "{}".to_string(),
+      },
+    }]);
+    let mut tool = message("tool", "Sunny");
+    tool.tool_call_id = Some("call_1".to_string());
+    let user = message("user", "Weather?");
+    let input = serde_json::to_value(responses_input(&[&user, &assistant, &tool])).unwrap();
+    assert_eq!(
+      input,
+      json!([
+        {"type": "message", "role": "user", "content": "Weather?"},
+        {"type": "function_call", "call_id": "call_1", "name": "weather_get_forecast", "arguments": "{}"},
+        {"type": "function_call_output", "call_id": "call_1", "output": "Sunny"}
+      ])
+    );
+  }
+}
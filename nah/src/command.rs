@@ -0,0 +1,432 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * Tokenizer for commands typed in the nah shell, and parsers for arguments
+ * passed inline to `call_tool` and `get_prompt`.
+ */
+use crate::script;
+use crate::types::NahError;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::iter::Peekable;
+
+/**
+ * Split a command line into words with shell-like rules:
+ * - Words are separated by whitespace.
+ * - Everything between single quotes is taken literally.
+ * - Between double quotes, a backslash escapes `"`, `\`, `$` and `` ` ``.
+ * - Outside of quotes, a backslash escapes any character.
+ * - A word starting with `{` or `[` is taken as-is until its brackets are
+ *   balanced, so inline JSON such as `{"city": "Paris"}` keeps its quotes and
+ *   spaces. So is the value of a `key=` word starting with `{` or `[`, as in
+ *   `point={"x": 1, "y": 2}`.
+ * - `$name.path[0]` and `${name.path[0]}` are replaced with the value of a
+ *   variable, except between single quotes or after a backslash. In inline
+ *   JSON, only `${name.path[0]}` is replaced.
+ */
+pub fn split_command_line(
+  command: &str,
+  variables: &HashMap<String, Value>,
+) -> Result<Vec<String>, NahError> {
+  let mut words = Vec::new();
+  let mut chars = command.chars().peekable();
+  loop {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    let Some(&first) = chars.peek() else {
+      break;
+    };
+    let mut word = String::new();
+    if first == '{' || first == '[' {
+      read_json_chunk(&mut chars, &mut word, variables)?;
+    }
+    while let Some(c) = chars.next() {
+      match c {
+        c if c.is_whitespace() => break,
+        '=' if !word.contains('=') && matches!(chars.peek(), Some('{' | '[')) => {
+          word.push(c);
+          read_json_chunk(&mut chars, &mut word, variables)?;
+        }
+        '\'' => loop {
+          match chars.next() {
+            Some('\'') => break,
+            Some(c) => word.push(c),
+            None => return Err(unterminated("'")),
+          }
+        },
+        '"' => loop {
+          match chars.next() {
+            Some('"') => break,
+            Some('\\') => match chars.next() {
+              Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+              Some(c) => {
+                word.push('\\');
+                word.push(c);
+              }
+              None => return Err(unterminated("\"")),
+            },
+            Some('$') => push_variable(&mut chars, &mut word, variables)?,
+            Some(c) => word.push(c),
+            None => return Err(unterminated("\"")),
+          }
+        },
+        '\\' => match chars.next() {
+          Some(c) => word.push(c),
+          None => {
+            return Err(NahError::invalid_argument_error(
+              "Command ends with a dangling `\\`",
+              None,
+            ))
+          }
+        },
+        '$' => push_variable(&mut chars, &mut word, variables)?,
+        c => word.push(c),
+      }
+    }
+    words.push(word);
+  }
+  Ok(words)
+}
+
+/**
+ * Replace a variable reference following a `$`, either `name.path[0]` or
+ * `{name.path[0]}`, with its value. A `$` followed by anything else is kept.
+ */
+fn push_variable<I>(
+  chars: &mut Peekable<I>,
+  word: &mut String,
+  variables: &HashMap<String, Value>,
+) -> Result<(), NahError>
+where
+  I: Iterator<Item = char>,
+{
+  let expr = if chars.next_if_eq(&'{').is_some() {
+    read_braced_variable(chars)?
+  } else if chars
+    .peek()
+    .is_some_and(|c| c.is_ascii_alphabetic() || *c == '_')
+  {
+    let mut expr = String::new();
+    while let Some(c) =
+      chars.next_if(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '[' | ']'))
+    {
+      expr.push(c);
+    }
+    expr
+  } else {
+    word.push('$');
+    return Ok(());
+  };
+  word.push_str(&script::render_value(script::lookup_variable(
+    variables, &expr,
+  )?));
+  Ok(())
+}
+
+/**
+ * Read the expression of `${name.path[0]}` after its `{`.
+ */
+fn read_braced_variable<I>(chars: &mut Peekable<I>) -> Result<String, NahError>
+where
+  I: Iterator<Item = char>,
+{
+  let mut expr = String::new();
+  loop {
+    match chars.next() {
+      Some('}') => return Ok(expr),
+      Some(c) => expr.push(c),
+      None => {
+        return Err(NahError::invalid_argument_error(
+          "Missing closing `}` of a variable",
+          None,
+        ))
+      }
+    }
+  }
+}
+
+/**
+ * Copy a JSON object or array verbatim, stopping after its closing bracket.
+ * Variables like `${name}` are replaced.
+ */
+fn read_json_chunk<I>(
+  chars: &mut Peekable<I>,
+  word: &mut String,
+  variables: &HashMap<String, Value>,
+) -> Result<(), NahError>
+where
+  I: Iterator<Item = char>,
+{
+  let mut depth = 0usize;
+  let mut in_string = false;
+  let mut in_escape = false;
+  while let Some(c) = chars.next() {
+    if c == '$' && chars.next_if_eq(&'{').is_some() {
+      let expr = read_braced_variable(chars)?;
+      word.push_str(&script::render_value(script::lookup_variable(
+        variables, &expr,
+      )?));
+      continue;
+    }
+    word.push(c);
+    if in_string {
+      match c {
+        _ if in_escape => in_escape = false,
+        '\\' => in_escape = true,
+        '"' => in_string = false,
+        _ => {}
+      }
+      continue;
+    }
+    match c {
+      '"' => in_string = true,
+      '{' | '[' => depth += 1,
+      '}' | ']' => {
+        depth -= 1;
+        if depth == 0 {
+          return Ok(());
+        }
+      }
+      _ => {}
+    }
+  }
+  Err(NahError::invalid_argument_error(
+    "Inline JSON is not closed",
+    None,
+  ))
+}
+
+fn unterminated(quote: &str) -> NahError {
+  NahError::invalid_argument_error(&format!("Missing closing quote `{}`", quote), None)
+}
+
+/**
+ * Parse arguments of a tool given on the command line. They can be one of:
+ * - a JSON object, e.g. `{"city": "Paris"}`
+ * - a JSON file, e.g. `@args.json`
+ * - `key=value` pairs, e.g. `city=Paris days=3`
+ *
+ * For `key=value` pairs, the value is kept as a string if the input schema
+ * declares the property as a string, and is parsed as JSON otherwise when
+ * possible.
+ */
+pub fn parse_inline_tool_arguments(
+  parts: &[&str],
+  input_schema: &Value,
+) -> Result<Value, NahError> {
+  if let Some(value) = parse_single_json_argument(parts)? {
+    return match value {
+      Value::Object(_) => Ok(value),
+      _ => Err(NahError::invalid_argument_error(
+        "Arguments should be a JSON Object!",
+        None,
+      )),
+    };
+  }
+  let mut result = Map::new();
+  for (key, raw_value) in split_key_value_pairs(parts)? {
+    let is_string = input_schema
+      .get("properties")
+      .and_then(|p| p.get(key))
+      .and_then(|p| p.get("type"))
+      .and_then(|t| t.as_str())
+      == Some("string");
+    let value = if is_string {
+      Value::String(raw_value.to_owned())
+    } else {
+      serde_json::from_str::<Value>(raw_value)
+        .unwrap_or_else(|_| Value::String(raw_value.to_owned()))
+    };
+    result.insert(key.to_owned(), value);
+  }
+  Ok(Value::Object(result))
+}
+
+/**
+ * Parse arguments of a prompt given on the command line, in the same forms as
+ * `parse_inline_tool_arguments`. Prompt arguments are always strings.
+ */
+pub fn parse_inline_prompt_arguments(parts: &[&str]) -> Result<HashMap<String, String>, NahError> {
+  if let Some(value) = parse_single_json_argument(parts)? {
+    let Value::Object(obj) = value else {
+      return Err(NahError::invalid_argument_error(
+        "Arguments should be a JSON Object!",
+        None,
+      ));
+    };
+    return Ok(
+      obj
+        .into_iter()
+        .map(|(k, v)| match v {
+          Value::String(s) => (k, s),
+          v => (k, v.to_string()),
+        })
+        .collect(),
+    );
+  }
+  Ok(
+    split_key_value_pairs(parts)?
+      .into_iter()
+      .map(|(k, v)| (k.to_owned(), v.to_owned()))
+      .collect(),
+  )
+}
+
+/**
+ * Return the JSON value if arguments are given as a single JSON document or
+ * an `@file`.
+ */
+fn parse_single_json_argument(parts: &[&str]) -> Result<Option<Value>, NahError> {
+  let [part] = parts else {
+    return Ok(None);
+  };
+  if let Some(filename) = part.strip_prefix('@') {
+    return crate::load_json_arguments(filename).map(Some);
+  }
+  if part.starts_with('{') || part.starts_with('[') {
+    return match serde_json::from_str::<Value>(part) {
+      Ok(v) => Ok(Some(v)),
+      Err(e) => Err(NahError::invalid_argument_error(
+        "Provided argument is invalid in JSON Format",
+        Some(Box::new(e)),
+      )),
+    };
+  }
+  Ok(None)
+}
+
+fn split_key_value_pairs<'a>(parts: &[&'a str]) -> Result<Vec<(&'a str, &'a str)>, NahError> {
+  parts
+    .iter()
+    .map(|part| match part.split_once('=') {
+      Some((key, value)) if !key.is_empty() => Ok((key, value)),
+      _ => Err(NahError::invalid_argument_error(
+        &format!(
+          "Invalid argument `{}`. Expect JSON, `@file.json` or `key=value` pairs.",
+          part
+        ),
+        None,
+      )),
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn test_split_command_line() {
+    assert_eq!(
+      split_command_line("  read_resources \"file:///a b\"  ", &HashMap::new()).unwrap(),
+      vec!["read_resources", "file:///a b"]
+    );
+    assert_eq!(
+      split_command_line(r#"get_prompt 'it''s' "say \"hi\"" a\ b"#, &HashMap::new()).unwrap(),
+      vec!["get_prompt", "its", "say \"hi\"", "a b"]
+    );
+    assert_eq!(
+      split_command_line(
+        r#"call_tool get_weather {"city": "Paris }", "days": [1, 2]} --form"#,
+        &HashMap::new()
+      )
+      .unwrap(),
+      vec![
+        "call_tool",
+        "get_weather",
+        r#"{"city": "Paris }", "days": [1, 2]}"#,
+        "--form"
+      ]
+    );
+    assert_eq!(
+      split_command_line(
+        r#"call_tool draw point={"x": 1, "y": 2} tags=[1, 2]"#,
+        &HashMap::new()
+      )
+      .unwrap(),
+      vec![
+        "call_tool",
+        "draw",
+        r#"point={"x": 1, "y": 2}"#,
+        "tags=[1, 2]"
+      ]
+    );
+    assert!(split_command_line("use \"abc", &HashMap::new()).is_err());
+    assert!(split_command_line("call_tool a {\"b\": 1", &HashMap::new()).is_err());
+    assert!(split_command_line("   ", &HashMap::new())
+      .unwrap()
+      .is_empty());
+  }
+
+  #[test]
+  fn test_substitute_variables() {
+    let mut variables = HashMap::new();
+    variables.insert(
+      "last".to_string(),
+      json!({"content": [{"type": "text", "text": "hello world"}], "isError": false}),
+    );
+    let command = r#"say $last.content[0].text msg=${last.content[0].text}! "$last.isError" $5"#;
+    assert_eq!(
+      split_command_line(command, &variables).unwrap(),
+      vec!["say", "hello world", "msg=hello world!", "false", "$5"]
+    );
+    let command = r#"call_tool echo {"text": "${last.content[0].text}"}"#;
+    assert_eq!(
+      split_command_line(command, &variables).unwrap(),
+      vec!["call_tool", "echo", r#"{"text": "hello world"}"#]
+    );
+    // Single quotes and backslashes keep `$` literal.
+    let command = r#"call_tool echo '$last' \$last "\${last}" '{"price": "${x}"}'"#;
+    assert_eq!(
+      split_command_line(command, &variables).unwrap(),
+      vec![
+        "call_tool",
+        "echo",
+        "$last",
+        "$last",
+        "${last}",
+        r#"{"price": "${x}"}"#
+      ]
+    );
+    assert!(split_command_line("echo $missing", &variables).is_err());
+    assert!(split_command_line("echo $last.content[3]", &variables).is_err());
+    assert!(split_command_line("echo ${last", &variables).is_err());
+  }
+
+  #[test]
+  fn test_parse_inline_tool_arguments() {
+    let schema = json!({
+      "type": "object",
+      "properties": {
+        "city": {"type": "string"},
+        "days": {"type": "integer"}
+      }
+    });
+    assert_eq!(
+      parse_inline_tool_arguments(&["city=42", "days=3", "extra=x"], &schema).unwrap(),
+      json!({"city": "42", "days": 3, "extra": "x"})
+    );
+    assert_eq!(
+      parse_inline_tool_arguments(&[r#"{"city": "Paris"}"#], &schema).unwrap(),
+      json!({"city": "Paris"})
+    );
+    assert_eq!(
+      parse_inline_tool_arguments(&[], &schema).unwrap(),
+      json!({})
+    );
+    assert!(parse_inline_tool_arguments(&["[1]"], &schema).is_err());
+    assert!(parse_inline_tool_arguments(&["city"], &schema).is_err());
+  }
+
+  #[test]
+  fn test_parse_inline_prompt_arguments() {
+    let args = parse_inline_prompt_arguments(&[r#"{"a": "x", "b": 1}"#]).unwrap();
+    assert_eq!(args.get("a").unwrap(), "x");
+    assert_eq!(args.get("b").unwrap(), "1");
+    let args = parse_inline_prompt_arguments(&["a=x=y"]).unwrap();
+    assert_eq!(args.get("a").unwrap(), "x=y");
+  }
+}
@@ -5,19 +5,121 @@
  */
 use crate::mcp::MCPLocalServerCommand;
 use crate::mcp::MCPRemoteServerConfig;
+use crate::secret::SecretValue;
 use crate::types::NahError;
+use crate::utils;
 use serde::Deserialize;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::BufReader;
-use std::path::PathBuf;
+use std::io::{BufReader, IsTerminal};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug)]
 pub struct NahConfig {
+  /// Config files that have been loaded, in the order of merging.
+  pub files: Vec<PathBuf>,
   pub mcp_servers: HashMap<String, MCPLocalServerCommand>,
   pub mcp_remote_servers: HashMap<String, MCPRemoteServerConfig>,
-  pub model: Option<ModelConfig>,
+  /// Model profiles by name. The legacy `model` field is the profile named
+  /// `default`.
+  pub models: HashMap<String, ModelConfig>,
+  pub default_model: Option<String>,
+  pub argument_input: Option<ArgumentInputMode>,
+}
+
+impl NahConfig {
+  /**
+   * Name of the model profile to chat with when none is given: `defaultModel`,
+   * the legacy `model`, or the only profile.
+   */
+  pub fn default_model_name(&self) -> Option<String> {
+    if let Some(name) = &self.default_model {
+      return Some(name.clone());
+    }
+    if self.models.contains_key(DEFAULT_MODEL_PROFILE) {
+      return Some(DEFAULT_MODEL_PROFILE.to_string());
+    }
+    match self.models.len() {
+      1 => self.models.keys().next().cloned(),
+      _ => None,
+    }
+  }
+
+  /**
+   * Merge another config into this one. Servers and options of `other` take
+   * precedence.
+   */
+  pub fn merge(&mut self, other: NahConfig) {
+    self.files.extend(other.files);
+    for (name, server) in other.mcp_servers {
+      self.mcp_remote_servers.remove(&name);
+      self.mcp_servers.insert(name, server);
+    }
+    for (name, server) in other.mcp_remote_servers {
+      self.mcp_servers.remove(&name);
+      self.mcp_remote_servers.insert(name, server);
+    }
+    self.models.extend(other.models);
+    if other.default_model.is_some() {
+      self.default_model = other.default_model;
+    }
+    if other.argument_input.is_some() {
+      self.argument_input = other.argument_input;
+    }
+  }
+}
+
+/**
+ * Project-level config files, looked up from the working directory up to the
+ * root. Files in the nearest directory are merged in this order, so the
+ * config of nah takes precedence over configs of other tools.
+ */
+const PROJECT_CONFIG_FILES: &[&str] = &[
+  ".vscode/mcp.json",
+  ".cursor/mcp.json",
+  ".mcp.json",
+  ".nah.json",
+];
+
+/**
+ * An entry of `inputs` in VS Code config files, referenced as `${input:id}`.
+ */
+#[derive(Debug, Deserialize)]
+pub(crate) struct ConfigInput {
+  id: String,
+  description: Option<String>,
+  #[serde(default)]
+  password: bool,
+  default: Option<String>,
+  options: Option<Vec<String>>,
+}
+
+/**
+ * How users provide arguments for tool calls and prompts.
+ */
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ArgumentInputMode {
+  /// Edit a JSON template in `$EDITOR`.
+  #[default]
+  Editor,
+  /// Fill fields one by one in an interactive form.
+  Form,
+}
+
+/**
+ * API of a model provider used for chatting.
+ */
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum ModelApi {
+  /// `/chat/completions`
+  #[default]
+  #[serde(rename = "chatCompletions")]
+  ChatCompletions,
+  /// `/responses`
+  #[serde(rename = "responses")]
+  Responses,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -26,19 +128,105 @@ pub struct ModelConfig {
   pub base_url: String,
   pub model: String,
   #[serde(rename = "authToken")]
-  pub auth_token: Option<String>,
+  pub auth_token: Option<SecretValue>,
   #[serde(rename = "extraParams")]
   pub extra_params: Option<Value>,
   #[serde(rename = "systemPrompt")]
   pub system_prompt: Option<String>,
+  #[serde(default)]
+  pub api: ModelApi,
+  #[serde(rename = "maxTokens")]
+  pub max_tokens: Option<usize>,
+  pub temperature: Option<f64>,
+  #[serde(rename = "topP")]
+  pub top_p: Option<f64>,
+  #[serde(rename = "frequencyPenalty")]
+  pub frequency_penalty: Option<f64>,
+  /// Context window in tokens. Older turns are dropped from the requests
+  /// when the conversation doesn't fit in it.
+  #[serde(rename = "contextWindow")]
+  pub context_window: Option<usize>,
+}
+
+/**
+ * Name of the profile of the legacy `model` field.
+ */
+pub const DEFAULT_MODEL_PROFILE: &str = "default";
+
+/**
+ * Load the config file at `path`. Without a path, the user-level config is
+ * merged with the project-level configs found from the working directory.
+ * `ask_inputs` tells whether `${input:id}` values may be asked on the
+ * terminal, which server modes and one-shot commands use for their own data.
+ */
+pub fn load_configs(path: Option<PathBuf>, ask_inputs: bool) -> Result<NahConfig, NahError> {
+  if let Some(path) = path {
+    return load_config(path, ask_inputs);
+  }
+  load_config_files(default_config_paths(), ask_inputs)
+}
+
+/**
+ * Load config files and merge them, later files taking precedence.
+ */
+pub fn load_config_files(paths: Vec<PathBuf>, ask_inputs: bool) -> Result<NahConfig, NahError> {
+  let mut paths = paths.into_iter();
+  let Some(first) = paths.next() else {
+    return Err(NahError::io_error(
+      "No config file is given, and no user or project config is found",
+      None,
+    ));
+  };
+  let mut config = load_config(first, ask_inputs)?;
+  for path in paths {
+    config.merge(load_config(path, ask_inputs)?);
+  }
+  Ok(config)
+}
+
+/**
+ * Existing config files to merge when no config path is given: the user config
+ * and the project configs.
+ */
+pub fn default_config_paths() -> Vec<PathBuf> {
+  let mut paths: Vec<PathBuf> = user_config_path().into_iter().collect();
+  if let Ok(cwd) = std::env::current_dir() {
+    paths.extend(find_project_configs(&cwd));
+  }
+  paths.into_iter().filter(|p| p.is_file()).collect()
+}
+
+/**
+ * Path of the user-level config, e.g. `~/.config/nah/config.json` on Linux.
+ */
+pub fn user_config_path() -> Option<PathBuf> {
+  dirs::config_dir().map(|dir| dir.join("nah").join("config.json"))
+}
+
+/**
+ * Project-level config files in the nearest directory that has any of them.
+ */
+fn find_project_configs(start: &Path) -> Vec<PathBuf> {
+  for dir in start.ancestors() {
+    let found: Vec<PathBuf> = PROJECT_CONFIG_FILES
+      .iter()
+      .map(|name| dir.join(name))
+      .filter(|p| p.is_file())
+      .collect();
+    if !found.is_empty() {
+      return found;
+    }
+  }
+  Vec::new()
 }
 
 /**
  * Load Nah config file.
  *
- * MCP Server config part follow the format follows Claude desktop app.
+ * MCP Server config part follow the format follows Claude desktop app. The
+ * `servers` and `inputs` keys of VS Code `mcp.json` files are supported too.
  */
-pub fn load_config(path: PathBuf) -> Result<NahConfig, NahError> {
+pub fn load_config(path: PathBuf, ask_inputs: bool) -> Result<NahConfig, NahError> {
   let file = match File::open(&path) {
     Ok(f) => f,
     Err(e) => {
@@ -60,27 +248,68 @@ pub fn load_config(path: PathBuf) -> Result<NahConfig, NahError> {
     }
   };
 
-  let (mcp_servers, mcp_remote_servers) = load_mcp_servers(&data, &path)?;
-  let model =
-    data.as_object().and_then(|obj| obj.get("model")).and_then(
-      |model| match serde_json::from_value::<ModelConfig>(model.clone()) {
-        Ok(v) => Some(v),
-        Err(e) => {
-          println!("{:?}", e);
-          None
-        }
-      },
-    );
+  let (mcp_servers, mcp_remote_servers) = load_mcp_servers(&data, &path, ask_inputs)?;
+  let mut models = HashMap::new();
+  if let Some(model) = data.as_object().and_then(|obj| obj.get("model")) {
+    match serde_json::from_value::<ModelConfig>(model.clone()) {
+      Ok(v) => {
+        models.insert(DEFAULT_MODEL_PROFILE.to_string(), v);
+      }
+      Err(e) => {
+        return Err(NahError::invalid_value(
+          "invalid model config in `model`, run `nah validate` for details",
+          Some(Box::new(e)),
+        ))
+      }
+    }
+  }
+  if let Some(profiles) = data.as_object().and_then(|obj| obj.get("models")) {
+    match serde_json::from_value::<HashMap<String, ModelConfig>>(profiles.clone()) {
+      Ok(v) => models.extend(v),
+      Err(e) => {
+        return Err(NahError::invalid_value(
+          "invalid model profiles in `models`, run `nah validate` for details",
+          Some(Box::new(e)),
+        ))
+      }
+    }
+  }
+  let default_model = match data.as_object().and_then(|obj| obj.get("defaultModel")) {
+    None => None,
+    Some(Value::String(name)) => Some(name.to_string()),
+    Some(_) => {
+      return Err(NahError::invalid_value(
+        "defaultModel should be the name of a model profile",
+        None,
+      ))
+    }
+  };
+  let argument_input = match data.as_object().and_then(|obj| obj.get("argumentInput")) {
+    None => None,
+    Some(v) => match serde_json::from_value::<ArgumentInputMode>(v.clone()) {
+      Ok(mode) => Some(mode),
+      Err(e) => {
+        return Err(NahError::invalid_value(
+          "argumentInput should be either \"editor\" or \"form\"",
+          Some(Box::new(e)),
+        ))
+      }
+    },
+  };
   Ok(NahConfig {
+    files: vec![path],
     mcp_servers,
     mcp_remote_servers,
-    model,
+    models,
+    default_model,
+    argument_input,
   })
 }
 
 fn load_mcp_servers(
   data: &Value,
   path: &PathBuf,
+  ask_inputs: bool,
 ) -> Result<
   (
     HashMap<String, MCPLocalServerCommand>,
@@ -90,30 +319,56 @@ fn load_mcp_servers(
 > {
   let mut mcp_servers = HashMap::new();
   let mut mcp_remote_servers = HashMap::new();
-  match &data["mcpServers"] {
-    Value::Object(servers) => {
+  let mut variables = ConfigVariables::new(data, path, ask_inputs)?;
+  // VS Code names the field `servers`
+  match data.get("mcpServers").or_else(|| data.get("servers")) {
+    Some(Value::Object(servers)) => {
       for (key, value) in servers.iter() {
         if value.as_object().is_some_and(|v| v.contains_key("command")) {
-          let server_command = match serde_json::from_value(value.clone()) {
+          let mut server_command = match serde_json::from_value(value.clone()) {
             Ok(v) => v,
             Err(e) => {
               return Err(NahError::invalid_value(
-                &format!("invalid server command for tool {}", key),
+                &format!(
+                  "invalid server command for tool {}, run `nah validate` for details",
+                  key
+                ),
                 Some(Box::new(e)),
               ))
             }
           };
+          if let Err(e) = variables.interpolate_local_server(&mut server_command) {
+            return Err(NahError::invalid_value(
+              &format!("invalid server command for tool {}", key),
+              Some(Box::new(e)),
+            ));
+          }
           mcp_servers.insert(key.to_string(), server_command);
         } else if value.as_object().is_some_and(|v| v.contains_key("url")) {
-          let remote_server_config = match serde_json::from_value(value.clone()) {
-            Ok(v) => v,
-            Err(e) => {
-              return Err(NahError::invalid_value(
-                &format!("invalid server command for tool {}", key),
-                Some(Box::new(e)),
-              ))
+          let mut remote_server_config: MCPRemoteServerConfig =
+            match serde_json::from_value(value.clone()) {
+              Ok(v) => v,
+              Err(e) => {
+                return Err(NahError::invalid_value(
+                  &format!("invalid server command for tool {}", key),
+                  Some(Box::new(e)),
+                ))
+              }
+            };
+          for header in remote_server_config.headers.values_mut() {
+            let SecretValue::Plain(value) = header else {
+              continue;
+            };
+            match variables.interpolate(value) {
+              Ok(v) => *value = v,
+              Err(e) => {
+                return Err(NahError::invalid_value(
+                  &format!("invalid server command for tool {}", key),
+                  Some(Box::new(e)),
+                ))
+              }
             }
-          };
+          }
           mcp_remote_servers.insert(key.to_string(), remote_server_config);
         } else {
           return Err(NahError::invalid_value(
@@ -131,10 +386,180 @@ fn load_mcp_servers(
   }
 }
 
+/**
+ * Folder that relative paths of a config file are resolved against: the
+ * directory of the file, or its parent for configs in `.vscode` and `.cursor`.
+ */
+pub fn workspace_folder(config_path: &Path) -> PathBuf {
+  let path = std::fs::canonicalize(config_path).unwrap_or(config_path.to_path_buf());
+  let mut folder = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+  if folder
+    .file_name()
+    .is_some_and(|name| name == ".vscode" || name == ".cursor")
+  {
+    folder.pop();
+  }
+  folder
+}
+
+/**
+ * Resolves `${...}` references in a config file: `${VAR}` and `${env:VAR}`
+ * for environment variables, `${input:id}` for inputs asked from the user,
+ * `${workspaceFolder}` and `${userHome}`.
+ */
+struct ConfigVariables {
+  workspace_folder: PathBuf,
+  inputs: Vec<ConfigInput>,
+  /// Whether inputs can be asked on the terminal.
+  ask_inputs: bool,
+  input_values: HashMap<String, String>,
+}
+
+impl ConfigVariables {
+  fn new(data: &Value, path: &Path, ask_inputs: bool) -> Result<Self, NahError> {
+    let inputs = match data.get("inputs") {
+      None => Vec::new(),
+      Some(v) => match serde_json::from_value(v.clone()) {
+        Ok(inputs) => inputs,
+        Err(e) => {
+          return Err(NahError::invalid_value(
+            "invalid inputs in config file",
+            Some(Box::new(e)),
+          ))
+        }
+      },
+    };
+    Ok(ConfigVariables {
+      workspace_folder: workspace_folder(path),
+      inputs,
+      ask_inputs: ask_inputs && std::io::stdin().is_terminal(),
+      input_values: HashMap::new(),
+    })
+  }
+
+  /**
+   * Interpolate args, env values and cwd of a local server. A relative cwd is
+   * resolved against the workspace folder.
+   */
+  fn interpolate_local_server(
+    &mut self,
+    command: &mut MCPLocalServerCommand,
+  ) -> Result<(), NahError> {
+    for arg in command.args.iter_mut() {
+      *arg = self.interpolate(arg)?;
+    }
+    for value in command.env.values_mut() {
+      *value = self.interpolate(value)?;
+    }
+    if let Some(cwd) = &command.cwd {
+      let cwd = PathBuf::from(self.interpolate(&cwd.to_string_lossy())?);
+      command.cwd = Some(self.workspace_folder.join(cwd));
+    }
+    Ok(())
+  }
+
+  fn interpolate(&mut self, value: &str) -> Result<String, NahError> {
+    interpolate_with(value, |reference| self.resolve(reference))
+  }
+
+  fn resolve(&mut self, reference: &str) -> Result<String, NahError> {
+    match reference.split_once(':') {
+      Some(("env", name)) => env_var(name),
+      Some(("input", id)) => self.ask_input(id),
+      _ => match reference {
+        "workspaceFolder" => Ok(self.workspace_folder.display().to_string()),
+        "userHome" => match dirs::home_dir() {
+          Some(home) => Ok(home.display().to_string()),
+          None => Err(NahError::invalid_value("home directory is not found", None)),
+        },
+        name => env_var(name),
+      },
+    }
+  }
+
+  /**
+   * Ask the user for the value of an input. Each input is asked once. When
+   * inputs can't be asked, the default value is used if there is one.
+   */
+  fn ask_input(&mut self, id: &str) -> Result<String, NahError> {
+    if let Some(v) = self.input_values.get(id) {
+      return Ok(v.clone());
+    }
+    let Some(input) = self.inputs.iter().find(|input| input.id == id) else {
+      return Err(NahError::invalid_value(
+        &format!("input {} is not defined in `inputs`", id),
+        None,
+      ));
+    };
+    if !self.ask_inputs {
+      return match &input.default {
+        Some(default) => Ok(default.clone()),
+        None => Err(NahError::invalid_value(
+          &format!(
+            "input {} has no default and can't be asked without an interactive shell",
+            id
+          ),
+          None,
+        )),
+      };
+    }
+    let mut prompt = input.description.clone().unwrap_or(input.id.clone());
+    if let Some(options) = &input.options {
+      prompt.push_str(&format!(" ({})", options.join(" | ")));
+    }
+    if let Some(default) = &input.default {
+      prompt.push_str(&format!(" [{}]", default));
+    }
+    prompt.push_str(": ");
+    let mut value = utils::ask_for_input(&prompt, input.password)?;
+    if value.is_empty() {
+      value = input.default.clone().unwrap_or_default();
+    }
+    self.input_values.insert(id.to_string(), value.clone());
+    Ok(value)
+  }
+}
+
+fn env_var(name: &str) -> Result<String, NahError> {
+  match std::env::var(name) {
+    Ok(v) => Ok(v),
+    Err(e) => Err(NahError::invalid_value(
+      &format!("environment variable {} is not set", name),
+      Some(Box::new(e)),
+    )),
+  }
+}
+
+/**
+ * Replace each `${reference}` in a value with the result of `resolve`.
+ */
+fn interpolate_with<F>(value: &str, mut resolve: F) -> Result<String, NahError>
+where
+  F: FnMut(&str) -> Result<String, NahError>,
+{
+  let mut result = String::new();
+  let mut rest = value;
+  while let Some(start) = rest.find("${") {
+    result.push_str(&rest[..start]);
+    let Some(len) = rest[start..].find('}') else {
+      return Err(NahError::invalid_value(
+        &format!("unclosed `${{` in {}", value),
+        None,
+      ));
+    };
+    result.push_str(&resolve(&rest[start + 2..start + len])?);
+    rest = &rest[start + len + 1..];
+  }
+  result.push_str(rest);
+  Ok(result)
+}
+
 #[cfg(test)]
 mod tests {
-  use super::load_mcp_servers;
+  use super::{find_project_configs, interpolate_with, load_mcp_servers, NahConfig};
+  use crate::types::NahError;
   use serde_json::Value;
+  use std::collections::HashMap;
   use std::path::PathBuf;
 
   #[test]
@@ -156,7 +581,8 @@ mod tests {
       }"#;
     let test_value: Value = serde_json::from_str(test_data).unwrap();
 
-    let (mcp_servers, mcp_remote_servers) = load_mcp_servers(&test_value, &PathBuf::new()).unwrap();
+    let (mcp_servers, mcp_remote_servers) =
+      load_mcp_servers(&test_value, &PathBuf::new(), false).unwrap();
     assert_eq!(mcp_servers.len(), 1);
     assert!(mcp_servers.contains_key("weather"));
     assert!(mcp_remote_servers.contains_key("huggingface"));
@@ -164,4 +590,104 @@ mod tests {
       .headers
       .contains_key("Authorization"));
   }
+
+  #[test]
+  fn test_interpolate_with() {
+    let lookup = |reference: &str| match reference {
+      "TOKEN" => Ok("abc".to_string()),
+      "env:HOME" => Ok("/home/nah".to_string()),
+      _ => Err(NahError::invalid_value(reference, None)),
+    };
+    assert_eq!(
+      interpolate_with("Bearer ${TOKEN}", lookup).unwrap(),
+      "Bearer abc"
+    );
+    assert_eq!(
+      interpolate_with("${env:HOME}/data:${TOKEN}", lookup).unwrap(),
+      "/home/nah/data:abc"
+    );
+    assert_eq!(interpolate_with("$HOME", lookup).unwrap(), "$HOME");
+    assert!(interpolate_with("${MISSING}", lookup).is_err());
+    assert!(interpolate_with("${TOKEN", lookup).is_err());
+  }
+
+  #[test]
+  fn test_load_vscode_servers_and_merge() {
+    let test_data = r#"
+      {
+        "inputs": [],
+        "servers": {
+          "weather": {"type": "stdio", "command": "uv"},
+          "docs": {"type": "http", "url": "https://example.com/mcp"}
+        }
+      }"#;
+    let test_value: Value = serde_json::from_str(test_data).unwrap();
+    let (mcp_servers, mcp_remote_servers) =
+      load_mcp_servers(&test_value, &PathBuf::new(), false).unwrap();
+    assert!(mcp_servers["weather"].args.is_empty());
+    assert!(mcp_remote_servers.contains_key("docs"));
+
+    let mut user = NahConfig {
+      files: vec![PathBuf::from("user.json")],
+      mcp_servers,
+      mcp_remote_servers,
+      models: HashMap::new(),
+      default_model: None,
+      argument_input: None,
+    };
+    let (mcp_servers, mcp_remote_servers) = load_mcp_servers(
+      &serde_json::json!({"mcpServers": {"docs": {"command": "docs-server", "args": []}}}),
+      &PathBuf::new(),
+      false,
+    )
+    .unwrap();
+    user.merge(NahConfig {
+      files: vec![PathBuf::from(".mcp.json")],
+      mcp_servers,
+      mcp_remote_servers,
+      models: HashMap::new(),
+      default_model: None,
+      argument_input: None,
+    });
+    assert_eq!(user.files.len(), 2);
+    assert_eq!(user.mcp_servers["docs"].command, "docs-server");
+    assert!(user.mcp_remote_servers.is_empty());
+  }
+
+  #[test]
+  fn test_inputs_without_terminal() {
+    let data = serde_json::json!({
+      "inputs": [
+        {"id": "region", "default": "eu"},
+        {"id": "token", "password": true}
+      ],
+      "servers": {
+        "weather": {"command": "weather", "args": ["--region", "${input:region}"]}
+      }
+    });
+    let (mcp_servers, _) = load_mcp_servers(&data, &PathBuf::new(), false).unwrap();
+    assert_eq!(mcp_servers["weather"].args, vec!["--region", "eu"]);
+    let mut data = data;
+    data["servers"]["weather"]["args"][1] = Value::from("${input:token}");
+    let error = load_mcp_servers(&data, &PathBuf::new(), true).unwrap_err();
+    assert!(error.to_string().contains("input token has no default"));
+  }
+
+  #[test]
+  fn test_find_project_configs() {
+    let root = std::env::temp_dir().join(format!("nah_config_{}", std::process::id()));
+    let nested = root.join("src").join("module");
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::create_dir_all(root.join(".vscode")).unwrap();
+    std::fs::write(root.join(".vscode").join("mcp.json"), "{}").unwrap();
+    std::fs::write(root.join(".nah.json"), "{}").unwrap();
+    assert_eq!(
+      find_project_configs(&nested),
+      vec![
+        root.join(".vscode").join("mcp.json"),
+        root.join(".nah.json")
+      ]
+    );
+    std::fs::remove_dir_all(&root).unwrap();
+  }
 }
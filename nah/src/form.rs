@@ -0,0 +1,412 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * Interactive forms to fill arguments field by field, as an alternative to
+ * editing a JSON template in `$EDITOR`.
+ */
+use crate::json_schema::{self, JSONSchemaTypeName};
+use crate::types::NahError;
+use nah_mcp_types::MCPPromptArgument;
+use rustyline::DefaultEditor;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/**
+ * Fill the arguments of a tool through a form that walks its input schema.
+ */
+pub fn fill_tool_arguments(input_schema: &Value) -> Result<Value, NahError> {
+  let mut form = Form::new(input_schema)?;
+  println!("Fill arguments field by field. Press [Ctrl-C] to cancel.");
+  form.ask_object_fields(input_schema, "", 0)
+}
+
+/**
+ * Fill the arguments of a prompt through a form.
+ */
+pub fn fill_prompt_arguments(
+  arguments: &[MCPPromptArgument],
+) -> Result<HashMap<String, String>, NahError> {
+  let mut form = Form::new(&Value::Null)?;
+  println!("Fill arguments field by field. Press [Ctrl-C] to cancel.");
+  let mut result = HashMap::new();
+  for arg in arguments.iter() {
+    let required = arg.required.unwrap_or(false);
+    print_field_header(&arg.name, "string", required, arg.description.as_deref());
+    loop {
+      let input = form.read_line(&arg.name)?;
+      if !input.is_empty() {
+        result.insert(arg.name.to_owned(), input);
+        break;
+      } else if !required {
+        break;
+      }
+      println!("`{}` is required.", arg.name);
+    }
+  }
+  Ok(result)
+}
+
+/**
+ * Source of the lines typed in a form.
+ */
+trait LineReader {
+  fn read_line(&mut self, prompt: &str) -> rustyline::Result<String>;
+}
+
+impl LineReader for DefaultEditor {
+  fn read_line(&mut self, prompt: &str) -> rustyline::Result<String> {
+    self.readline(prompt)
+  }
+}
+
+struct Form<'a> {
+  root: &'a Value,
+  rl: Box<dyn LineReader>,
+}
+
+impl<'a> Form<'a> {
+  fn new(root: &'a Value) -> Result<Self, NahError> {
+    match DefaultEditor::new() {
+      Ok(rl) => Ok(Form {
+        root,
+        rl: Box::new(rl),
+      }),
+      Err(e) => Err(NahError::io_error(
+        "Failed to initialize the form",
+        Some(Box::new(e)),
+      )),
+    }
+  }
+
+  /**
+   * Ask for a value of the schema. Return `None` if an optional field is
+   * skipped.
+   */
+  fn ask_value(
+    &mut self,
+    schema: &'a Value,
+    path: &str,
+    required: bool,
+    depth: usize,
+  ) -> Result<Option<Value>, NahError> {
+    let Some(schema) = json_schema::resolve_ref(self.root, schema, depth)? else {
+      return self.ask_json(path, required, None);
+    };
+    let type_summary = json_schema::get_schema_summary(schema);
+    print_field_header(
+      path,
+      &type_summary,
+      required,
+      schema
+        .get("description")
+        .or_else(|| schema.get("title"))
+        .and_then(|v| v.as_str()),
+    );
+    let default = schema.get("default");
+    if let Some(default) = default {
+      println!("  Default: {}", default);
+    }
+
+    if let Some(value) = schema.get("const") {
+      println!("  Fixed value: {}", value);
+      return Ok(Some(value.clone()));
+    }
+    if let Some(options) = schema.get("enum").and_then(|v| v.as_array()) {
+      return match self.ask_choice(path, options, required, default)? {
+        Some(idx) => Ok(Some(options[idx].clone())),
+        None => Ok(default.cloned()),
+      };
+    }
+    let alternatives = schema
+      .get("anyOf")
+      .or_else(|| schema.get("oneOf"))
+      .and_then(|v| v.as_array());
+    if let Some(alternatives) = alternatives {
+      let non_null: Vec<&'a Value> = alternatives
+        .iter()
+        .filter(|s| s.get("type").and_then(|t| t.as_str()) != Some("null"))
+        .collect();
+      if non_null.len() == 1 {
+        // A nullable value such as `anyOf: [{"type": "string"}, {"type": "null"}]`
+        return self.ask_value(non_null[0], path, required, depth + 1);
+      }
+      let summaries: Vec<Value> = alternatives
+        .iter()
+        .map(|s| Value::String(json_schema::get_schema_summary(s)))
+        .collect();
+      println!("  Choose a variant:");
+      let Some(idx) = self.ask_choice(path, &summaries, required, None)? else {
+        return Ok(None);
+      };
+      return self.ask_value(&alternatives[idx], path, true, depth + 1);
+    }
+
+    match json_schema::get_type_name(schema)? {
+      JSONSchemaTypeName::Object => {
+        if !required && !self.confirm(&format!("Fill `{}`? [y/N] > ", path))? {
+          return Ok(default.cloned());
+        }
+        self.ask_object_fields(schema, path, depth).map(Some)
+      }
+      JSONSchemaTypeName::Array => {
+        let item_schema = match schema.get("prefixItems").or_else(|| schema.get("items")) {
+          Some(Value::Array(item_schemas)) => item_schemas.first(),
+          item_schema => item_schema,
+        };
+        if !required && !self.confirm(&format!("Fill `{}`? [y/N] > ", path))? {
+          return Ok(default.cloned());
+        }
+        let mut items = Vec::new();
+        while self.confirm(&format!(
+          "Add item #{} to `{}`? [y/N] > ",
+          items.len(),
+          path
+        ))? {
+          let item_path = format!("{}[{}]", path, items.len());
+          let item = match item_schema {
+            Some(item_schema) => self.ask_value(item_schema, &item_path, true, depth)?,
+            None => self.ask_json(&item_path, true, None)?,
+          };
+          items.extend(item);
+        }
+        Ok(Some(Value::Array(items)))
+      }
+      JSONSchemaTypeName::String => self.ask_parsed(path, required, default, |s| {
+        Some(Value::String(s.to_owned()))
+      }),
+      JSONSchemaTypeName::Integer => self.ask_parsed(path, required, default, |s| {
+        s.parse::<i64>().ok().map(Value::from)
+      }),
+      JSONSchemaTypeName::Number => self.ask_parsed(path, required, default, |s| {
+        s.parse::<f64>()
+          .ok()
+          .and_then(serde_json::Number::from_f64)
+          .map(Value::Number)
+      }),
+      JSONSchemaTypeName::Boolean => self.ask_parsed(path, required, default, |s| {
+        match s.to_lowercase().as_str() {
+          "y" | "yes" | "true" => Some(Value::Bool(true)),
+          "n" | "no" | "false" => Some(Value::Bool(false)),
+          _ => None,
+        }
+      }),
+      JSONSchemaTypeName::Null => Ok(Some(Value::Null)),
+      JSONSchemaTypeName::Unknown => self.ask_json(path, required, default),
+    }
+  }
+
+  /**
+   * Ask for all properties of an object schema.
+   */
+  fn ask_object_fields(
+    &mut self,
+    schema: &'a Value,
+    path: &str,
+    depth: usize,
+  ) -> Result<Value, NahError> {
+    let required_fields: Vec<&str> = schema
+      .get("required")
+      .and_then(|v| v.as_array())
+      .map(|arr| arr.iter().filter_map(|v| v.as_str()).collect())
+      .unwrap_or_default();
+    let mut result = Map::new();
+    for (field_name, field_schema) in json_schema::get_properties(schema) {
+      let field_path = if path.is_empty() {
+        field_name.to_owned()
+      } else {
+        format!("{}.{}", path, field_name)
+      };
+      let required = required_fields.contains(&field_name.as_str());
+      if let Some(value) = self.ask_value(field_schema, &field_path, required, depth)? {
+        result.insert(field_name.to_owned(), value);
+      }
+    }
+    Ok(Value::Object(result))
+  }
+
+  /**
+   * Ask to pick one of the options by its number or its value, and return the
+   * index of the chosen option. Options may have the same value, so the index
+   * is the only way to tell them apart.
+   */
+  fn ask_choice(
+    &mut self,
+    path: &str,
+    options: &[Value],
+    required: bool,
+    default: Option<&Value>,
+  ) -> Result<Option<usize>, NahError> {
+    for (idx, option) in options.iter().enumerate() {
+      println!("  [{}] {}", idx + 1, option);
+    }
+    let default_idx = default
+      .and_then(|d| options.iter().position(|o| o == d))
+      .map(Value::from);
+    let choice = self.ask_parsed(path, required, default_idx.as_ref(), |s| {
+      if let Some(idx) = s
+        .parse::<usize>()
+        .ok()
+        .and_then(|idx| idx.checked_sub(1))
+        .filter(|idx| *idx < options.len())
+      {
+        return Some(Value::from(idx));
+      }
+      let json_input = serde_json::from_str::<Value>(s).ok();
+      options
+        .iter()
+        .position(|o| o.as_str() == Some(s) || json_input.as_ref() == Some(o))
+        .map(Value::from)
+    })?;
+    Ok(choice.and_then(|v| v.as_u64()).map(|idx| idx as usize))
+  }
+
+  /**
+   * Ask for a raw JSON value.
+   */
+  fn ask_json(
+    &mut self,
+    path: &str,
+    required: bool,
+    default: Option<&Value>,
+  ) -> Result<Option<Value>, NahError> {
+    println!("  Enter a value in JSON format.");
+    self.ask_parsed(path, required, default, |s| {
+      serde_json::from_str::<Value>(s).ok()
+    })
+  }
+
+  /**
+   * Keep asking until the input can be parsed. An empty input takes the default
+   * value, or skips an optional field.
+   */
+  fn ask_parsed<F>(
+    &mut self,
+    path: &str,
+    required: bool,
+    default: Option<&Value>,
+    parse: F,
+  ) -> Result<Option<Value>, NahError>
+  where
+    F: Fn(&str) -> Option<Value>,
+  {
+    loop {
+      let input = self.read_line(path)?;
+      if input.is_empty() {
+        if default.is_some() || !required {
+          return Ok(default.cloned());
+        }
+        println!("`{}` is required.", path);
+        continue;
+      }
+      match parse(&input) {
+        Some(v) => return Ok(Some(v)),
+        None => println!("Invalid value for `{}`, please try again.", path),
+      }
+    }
+  }
+
+  fn confirm(&mut self, prompt: &str) -> Result<bool, NahError> {
+    let input = self.read_line_with_prompt(prompt)?;
+    Ok(input == "y" || input == "Y")
+  }
+
+  fn read_line(&mut self, path: &str) -> Result<String, NahError> {
+    self.read_line_with_prompt(&format!("{} > ", path))
+  }
+
+  fn read_line_with_prompt(&mut self, prompt: &str) -> Result<String, NahError> {
+    match self.rl.read_line(prompt) {
+      Ok(line) => Ok(line.trim().to_owned()),
+      Err(rustyline::error::ReadlineError::Interrupted)
+      | Err(rustyline::error::ReadlineError::Eof) => Err(NahError::user_cancel_request()),
+      Err(e) => Err(NahError::io_error(
+        "Failed to read the input",
+        Some(Box::new(e)),
+      )),
+    }
+  }
+}
+
+fn print_field_header(path: &str, type_summary: &str, required: bool, description: Option<&str>) {
+  let required_marker = if required { " [REQUIRED]" } else { "" };
+  match description {
+    Some(desc) => println!("* {} ({}){}: {}", path, type_summary, required_marker, desc),
+    None => println!("* {} ({}){}", path, type_summary, required_marker),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+  use std::collections::VecDeque;
+
+  impl LineReader for VecDeque<&'static str> {
+    fn read_line(&mut self, _prompt: &str) -> rustyline::Result<String> {
+      self
+        .pop_front()
+        .map(str::to_owned)
+        .ok_or(rustyline::error::ReadlineError::Eof)
+    }
+  }
+
+  fn fill(schema: &Value, lines: &[&'static str]) -> Result<Value, NahError> {
+    let mut form = Form {
+      root: schema,
+      rl: Box::new(lines.iter().copied().collect::<VecDeque<_>>()),
+    };
+    form.ask_object_fields(schema, "", 0)
+  }
+
+  #[test]
+  fn test_fill_scalars() {
+    let schema = json!({
+      "type": "object",
+      "properties": {
+        "city": {"type": "string"},
+        "days": {"type": "integer", "default": 3},
+        "unit": {"enum": ["C", "F"], "default": "F"},
+        "metric": {"type": "boolean"},
+        "note": {"anyOf": [{"type": "string"}, {"type": "null"}]}
+      },
+      "required": ["city", "unit"]
+    });
+    // Fields are asked in the order of their names; `unit` first gets an out
+    // of range choice, then the second option.
+    let lines = ["Paris", "", "yes", "windy", "3", "2"];
+    assert_eq!(
+      fill(&schema, &lines).unwrap(),
+      json!({"city": "Paris", "days": 3, "unit": "F", "metric": true, "note": "windy"})
+    );
+    let lines = ["Paris", "", "", "", ""];
+    assert_eq!(
+      fill(&schema, &lines).unwrap(),
+      json!({"city": "Paris", "days": 3, "unit": "F"})
+    );
+    assert!(fill(&schema, &[]).is_err());
+  }
+
+  #[test]
+  fn test_fill_nested() {
+    let schema = json!({
+      "type": "object",
+      "properties": {
+        "shape": {"oneOf": [
+          {"type": "object", "properties": {"radius": {"type": "number"}}},
+          {"type": "object", "properties": {"side": {"type": "number"}}}
+        ]},
+        "tags": {"type": "array", "items": {"type": "string"}}
+      },
+      "required": ["shape", "tags"]
+    });
+    // Both variants are summarized as `object`: the second one is chosen by its number
+    let lines = ["2", "1.5", "y", "a", "y", "b", "n"];
+    assert_eq!(
+      fill(&schema, &lines).unwrap(),
+      json!({"shape": {"side": 1.5}, "tags": ["a", "b"]})
+    );
+  }
+}
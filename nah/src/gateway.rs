@@ -0,0 +1,636 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * A MCP server aggregating all configured servers. Tools and prompts are
+ * renamed to `server_name` like the tools of chats, resources keep their URIs,
+ * and requests are routed back to the server owning the item.
+ */
+use crate::config::NahConfig;
+use crate::history::Manifest;
+use crate::mcp::{MCPRequest, MCPServer};
+use crate::oneshot;
+use crate::startup::ServerConfigs;
+use crate::types::NahError;
+use nah_mcp_types::{
+  MCPResourceContent, MCPResourceDefinition, MCPResponse, MCPToolDefinition, MCP_PROTOCOL_VERSION,
+};
+use nah_server::{
+  run_mcp_server_with_http, run_mcp_server_with_stdio, AbstractMCPServer, ServerInfo, MCP_ENDPOINT,
+};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+/**
+ * Tools exposed by the gateway, matched by their namespaced names. A pattern
+ * may contain `*` to match any characters. Tools are exposed when they match
+ * an allow pattern, or when there is none, and match no deny pattern.
+ */
+#[derive(Debug, Default)]
+pub struct ToolFilter {
+  pub allow: Vec<String>,
+  pub deny: Vec<String>,
+}
+
+impl ToolFilter {
+  pub fn allows(&self, name: &str) -> bool {
+    (self.allow.is_empty() || self.allow.iter().any(|p| glob_match(p, name)))
+      && !self.deny.iter().any(|p| glob_match(p, name))
+  }
+}
+
+fn glob_match(pattern: &str, name: &str) -> bool {
+  let mut parts = pattern.split('*');
+  let first = parts.next().unwrap_or_default();
+  let Some(mut rest) = name.strip_prefix(first) else {
+    return false;
+  };
+  let parts: Vec<&str> = parts.collect();
+  let Some((last, middle)) = parts.split_last() else {
+    // No `*` in the pattern
+    return rest.is_empty();
+  };
+  for part in middle {
+    match rest.find(part) {
+      Some(idx) => rest = &rest[idx + part.len()..],
+      None => return false,
+    }
+  }
+  rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/**
+ * Items listed by name: tools and prompts.
+ */
+#[derive(Debug, Clone, Copy)]
+enum Catalog {
+  Tools,
+  Prompts,
+}
+
+impl Catalog {
+  fn list_method(self) -> &'static str {
+    match self {
+      Catalog::Tools => "tools/list",
+      Catalog::Prompts => "prompts/list",
+    }
+  }
+
+  fn key(self) -> &'static str {
+    match self {
+      Catalog::Tools => "tools",
+      Catalog::Prompts => "prompts",
+    }
+  }
+}
+
+pub struct GatewayServer {
+  servers: BTreeMap<String, Box<dyn MCPServer>>,
+  filter: ToolFilter,
+  /// Server and original name of each exposed tool.
+  tool_routes: HashMap<String, (String, String)>,
+  /// Server and original name of each exposed prompt.
+  prompt_routes: HashMap<String, (String, String)>,
+  /// Server of each resource URI.
+  resource_routes: HashMap<String, String>,
+  /// Server of each resource template, by the URI prefix before the first
+  /// variable.
+  template_routes: Vec<(String, String)>,
+}
+
+impl GatewayServer {
+  pub fn new(servers: BTreeMap<String, Box<dyn MCPServer>>, filter: ToolFilter) -> GatewayServer {
+    GatewayServer {
+      servers,
+      filter,
+      tool_routes: HashMap::new(),
+      prompt_routes: HashMap::new(),
+      resource_routes: HashMap::new(),
+      template_routes: Vec::new(),
+    }
+  }
+
+  /**
+   * Send a request to a server with a new id.
+   */
+  fn forward(
+    &mut self,
+    server_name: &str,
+    method: &str,
+    params: Option<Value>,
+  ) -> Result<MCPResponse, NahError> {
+    let Some(server) = self.servers.get_mut(server_name) else {
+      return Err(NahError::invalid_value(
+        &format!("Server {} is not started", server_name),
+        None,
+      ));
+    };
+    let mut request = json!({
+      "jsonrpc": "2.0",
+      "id": uuid::Uuid::new_v4().to_string(),
+      "method": method,
+    });
+    if let Some(params) = params {
+      request["params"] = params;
+    }
+    let request: MCPRequest = serde_json::from_value(request).unwrap();
+    server.send_and_wait_for_response(request)
+  }
+
+  /**
+   * List all pages of a list method on a server.
+   */
+  fn list_all(
+    &mut self,
+    server_name: &str,
+    method: &str,
+    key: &str,
+  ) -> Result<Vec<Value>, NahError> {
+    let mut items = Vec::new();
+    let mut cursor: Option<Value> = None;
+    loop {
+      let params = cursor.take().map(|c| json!({ "cursor": c }));
+      let response = self.forward(server_name, method, params)?;
+      let Some(result) = response.result else {
+        let server = self.servers.get(server_name).unwrap();
+        return Err(server.parse_response_error(&response));
+      };
+      if let Some(page) = result.get(key).and_then(|v| v.as_array()) {
+        items.extend(page.iter().cloned());
+      }
+      match result.get("nextCursor") {
+        Some(c) if !c.is_null() => cursor = Some(c.clone()),
+        _ => return Ok(items),
+      }
+    }
+  }
+
+  /**
+   * List tools or prompts of all servers with namespaced names, and refresh
+   * their routes. Servers failing to list are skipped.
+   */
+  fn list_catalog(&mut self, catalog: Catalog) -> Vec<Value> {
+    let mut routes = HashMap::new();
+    let mut result = Vec::new();
+    let server_names: Vec<String> = self.servers.keys().cloned().collect();
+    for server_name in server_names {
+      let items = match self.list_all(&server_name, catalog.list_method(), catalog.key()) {
+        Ok(items) => items,
+        Err(e) => {
+          eprintln!("Failed to list {} of {}: {}", catalog.key(), server_name, e);
+          continue;
+        }
+      };
+      for mut item in items {
+        let Some(name) = item.get("name").and_then(|v| v.as_str()).map(String::from) else {
+          continue;
+        };
+        let new_name = format!("{}_{}", server_name, name);
+        if matches!(catalog, Catalog::Tools) && !self.filter.allows(&new_name) {
+          continue;
+        }
+        item["name"] = Value::String(new_name.clone());
+        routes.insert(new_name, (server_name.clone(), name));
+        result.push(item);
+      }
+    }
+    match catalog {
+      Catalog::Tools => self.tool_routes = routes,
+      Catalog::Prompts => self.prompt_routes = routes,
+    }
+    result
+  }
+
+  /**
+   * List resources or resource templates of all servers with namespaced
+   * names, and refresh their routes.
+   */
+  fn list_resources(&mut self, templates: bool) -> Vec<Value> {
+    let (method, key, uri_key) = if templates {
+      (
+        "resources/templates/list",
+        "resourceTemplates",
+        "uriTemplate",
+      )
+    } else {
+      ("resources/list", "resources", "uri")
+    };
+    let mut result = Vec::new();
+    if templates {
+      self.template_routes.clear();
+    } else {
+      self.resource_routes.clear();
+    }
+    let server_names: Vec<String> = self.servers.keys().cloned().collect();
+    for server_name in server_names {
+      let items = match self.list_all(&server_name, method, key) {
+        Ok(items) => items,
+        Err(e) => {
+          eprintln!("Failed to list {} of {}: {}", key, server_name, e);
+          continue;
+        }
+      };
+      for mut item in items {
+        let Some(uri) = item.get(uri_key).and_then(|v| v.as_str()).map(String::from) else {
+          continue;
+        };
+        if let Some(name) = item.get("name").and_then(|v| v.as_str()) {
+          item["name"] = Value::String(format!("{}_{}", server_name, name));
+        }
+        if templates {
+          let prefix = uri.split('{').next().unwrap_or_default().to_string();
+          self.template_routes.push((prefix, server_name.clone()));
+        } else {
+          self
+            .resource_routes
+            .entry(uri)
+            .or_insert_with(|| server_name.clone());
+        }
+        result.push(item);
+      }
+    }
+    result
+  }
+
+  /**
+   * Find the server of a resource URI: listed resources first, then the
+   * template with the longest matching prefix.
+   */
+  fn resource_server(&self, uri: &str) -> Option<String> {
+    if let Some(server) = self.resource_routes.get(uri) {
+      return Some(server.clone());
+    }
+    self
+      .template_routes
+      .iter()
+      .filter(|(prefix, _)| uri.starts_with(prefix.as_str()))
+      .max_by_key(|(prefix, _)| prefix.len())
+      .map(|(_, server)| server.clone())
+  }
+
+  /**
+   * Route a named call, `tools/call` or `prompts/get`, to its server.
+   */
+  fn call_named(&mut self, catalog: Catalog, method: &str, request: &MCPRequest) -> MCPResponse {
+    let mut params = request.params.clone().unwrap_or_else(|| json!({}));
+    let Some(name) = params
+      .get("name")
+      .and_then(|v| v.as_str())
+      .map(String::from)
+    else {
+      return error_response(&request.id, -32602, format!("Missing name in {}", method));
+    };
+    let routes = |gateway: &GatewayServer| match catalog {
+      Catalog::Tools => gateway.tool_routes.get(&name).cloned(),
+      Catalog::Prompts => gateway.prompt_routes.get(&name).cloned(),
+    };
+    let route = match routes(self) {
+      Some(route) => Some(route),
+      None => {
+        self.list_catalog(catalog);
+        routes(self)
+      }
+    };
+    let Some((server_name, original_name)) = route else {
+      return error_response(&request.id, -32602, format!("Unknown name: {}", name));
+    };
+    params["name"] = Value::String(original_name);
+    self.respond(&request.id, &server_name, method, Some(params))
+  }
+
+  fn read_resource(&mut self, request: &MCPRequest) -> MCPResponse {
+    let Some(uri) = request
+      .params
+      .as_ref()
+      .and_then(|p| p.get("uri"))
+      .and_then(|v| v.as_str())
+    else {
+      return error_response(
+        &request.id,
+        -32602,
+        "Missing uri in resources/read".to_string(),
+      );
+    };
+    let server_name = match self.resource_server(uri) {
+      Some(s) => Some(s),
+      None => {
+        self.list_resources(false);
+        self.list_resources(true);
+        self.resource_server(uri)
+      }
+    };
+    let Some(server_name) = server_name else {
+      return MCPResponse::new(
+        request.id.clone(),
+        None,
+        Some(json!({"code": -32002, "message": "Resource not found", "data": {"uri": uri}})),
+      );
+    };
+    self.respond(
+      &request.id,
+      &server_name,
+      "resources/read",
+      request.params.clone(),
+    )
+  }
+
+  /**
+   * Forward a request and answer with the response of the server.
+   */
+  fn respond(
+    &mut self,
+    id: &Value,
+    server_name: &str,
+    method: &str,
+    params: Option<Value>,
+  ) -> MCPResponse {
+    match self.forward(server_name, method, params) {
+      Ok(mut response) => {
+        response.id = id.clone();
+        response
+      }
+      Err(e) => error_response(id, -32603, e.to_string()),
+    }
+  }
+
+  /**
+   * Stop all servers.
+   */
+  pub fn kill(&mut self) {
+    for server in self.servers.values_mut() {
+      let _ = server.kill();
+    }
+  }
+}
+
+fn error_response(id: &Value, code: i64, message: String) -> MCPResponse {
+  MCPResponse::new(
+    id.clone(),
+    None,
+    Some(json!({"code": code, "message": message})),
+  )
+}
+
+impl AbstractMCPServer for GatewayServer {
+  fn get_server_info(&self) -> ServerInfo {
+    ServerInfo {
+      name: "nah-gateway".to_string(),
+      version: env!("CARGO_PKG_VERSION").to_string(),
+    }
+  }
+
+  // All requests are routed by `on_request`.
+
+  fn get_tools_list(&self) -> Vec<MCPToolDefinition> {
+    Vec::new()
+  }
+
+  fn on_tool_call(
+    &mut self,
+    _name: &str,
+    _args: Option<&serde_json::Map<String, Value>>,
+  ) -> String {
+    String::new()
+  }
+
+  fn get_resources_list(&self) -> Vec<MCPResourceDefinition> {
+    Vec::new()
+  }
+
+  fn on_resources_read(&self, _uri: &str) -> Vec<MCPResourceContent> {
+    Vec::new()
+  }
+
+  fn on_request(&mut self, request: &MCPRequest) -> Option<MCPResponse> {
+    let result = match request.method.as_str() {
+      "initialize" => json!({
+        "protocolVersion": MCP_PROTOCOL_VERSION,
+        "capabilities": {
+          "tools": {"listChanged": false},
+          "resources": {"listChanged": false},
+          "prompts": {"listChanged": false}
+        },
+        "serverInfo": self.get_server_info(),
+      }),
+      "ping" => json!({}),
+      "tools/list" => json!({ "tools": self.list_catalog(Catalog::Tools) }),
+      "prompts/list" => json!({ "prompts": self.list_catalog(Catalog::Prompts) }),
+      "resources/list" => json!({ "resources": self.list_resources(false) }),
+      "resources/templates/list" => json!({ "resourceTemplates": self.list_resources(true) }),
+      "tools/call" => return Some(self.call_named(Catalog::Tools, "tools/call", request)),
+      "prompts/get" => return Some(self.call_named(Catalog::Prompts, "prompts/get", request)),
+      "resources/read" => return Some(self.read_resource(request)),
+      _ => return None,
+    };
+    Some(MCPResponse::new(request.id.clone(), Some(result), None))
+  }
+}
+
+/**
+ * Start all configured servers and serve them as one MCP server over stdio,
+ * or over HTTP when an address is given. Returns the exit code.
+ */
+pub fn run_gateway(
+  config: &NahConfig,
+  history_path: Option<PathBuf>,
+  http_address: Option<&str>,
+  filter: ToolFilter,
+) -> i32 {
+  match serve(config, history_path, http_address, filter) {
+    Ok(code) => code,
+    Err(e) => {
+      eprintln!("{}", e);
+      e.code
+    }
+  }
+}
+
+fn serve(
+  config: &NahConfig,
+  history_path: Option<PathBuf>,
+  http_address: Option<&str>,
+  filter: ToolFilter,
+) -> Result<i32, NahError> {
+  let history_path = history_path.unwrap_or_else(|| {
+    let timestamp = std::time::SystemTime::now()
+      .duration_since(std::time::SystemTime::UNIX_EPOCH)
+      .unwrap()
+      .as_secs();
+    PathBuf::from(format!("nah_serve_{}", timestamp))
+  });
+  if let Err(e) = std::fs::create_dir_all(&history_path) {
+    return Err(NahError::io_error(
+      &format!(
+        "Failed to create history folder: {}",
+        history_path.display()
+      ),
+      Some(Box::new(e)),
+    ));
+  }
+  Manifest::create(&history_path, &config.files)?;
+  let configs = ServerConfigs {
+    local: &config.mcp_servers,
+    remote: &config.mcp_remote_servers,
+  };
+  let mut servers = BTreeMap::new();
+  for server_name in configs.names() {
+    if configs.is_disabled(&server_name) {
+      continue;
+    }
+    match oneshot::start_server(config, &server_name, &history_path) {
+      Ok(server) => {
+        servers.insert(server_name, server);
+      }
+      Err(e) => eprintln!("Failed to start {}, it is skipped: {}", server_name, e),
+    }
+  }
+  eprintln!(
+    "Serving {} server(s), history folder: {}",
+    servers.len(),
+    history_path.display()
+  );
+  let mut gateway = GatewayServer::new(servers, filter);
+  let result = match http_address {
+    Some(address) => {
+      eprintln!("Listening on http://{}{}", address, MCP_ENDPOINT);
+      run_mcp_server_with_http(&mut gateway, address)
+    }
+    None => run_mcp_server_with_stdio(&mut gateway),
+  };
+  gateway.kill();
+  match result {
+    Ok(()) => Ok(0),
+    Err(e) => Err(NahError::io_error(
+      "Failed to serve the gateway",
+      Some(Box::new(e)),
+    )),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mcp::FakeServer;
+  use std::sync::{Arc, Mutex};
+
+  fn request(method: &str, params: Value) -> MCPRequest {
+    serde_json::from_value(json!({"jsonrpc": "2.0", "id": 7, "method": method, "params": params}))
+      .unwrap()
+  }
+
+  /**
+   * A server listing `items` for each list method, and echoing the params of
+   * other requests.
+   */
+  fn fake_server(name: &str, items: Value) -> (Box<dyn MCPServer>, Arc<Mutex<Vec<Value>>>) {
+    let server = FakeServer::new(name, move |request| {
+      Ok(match items.get(&request.method) {
+        Some(list) => list.clone(),
+        None => request.params.clone().unwrap_or_default(),
+      })
+    });
+    let requests = server.requests.clone();
+    (Box::new(server), requests)
+  }
+
+  #[test]
+  fn test_routing() {
+    let (weather, weather_requests) = fake_server(
+      "weather",
+      json!({
+        "tools/list": {"tools": [{"name": "forecast", "inputSchema": {}}]},
+        "resources/list": {"resources": []},
+        "resources/templates/list": {"resourceTemplates": [{"uriTemplate": "weather://{city}", "name": "city"}]},
+      }),
+    );
+    let (files, files_requests) = fake_server(
+      "files",
+      json!({
+        "tools/list": {"tools": [{"name": "read", "inputSchema": {}}]},
+        "resources/list": {"resources": [{"uri": "file:///a", "name": "a"}]},
+        "resources/templates/list": {"resourceTemplates": [{"uriTemplate": "file:///{path}"}]},
+      }),
+    );
+    let servers = BTreeMap::from([
+      ("weather".to_string(), weather),
+      ("files".to_string(), files),
+    ]);
+    let filter = ToolFilter {
+      allow: Vec::new(),
+      deny: vec!["files_*".to_string()],
+    };
+    let mut gateway = GatewayServer::new(servers, filter);
+
+    let response = gateway
+      .on_request(&request("initialize", json!({})))
+      .unwrap();
+    assert_eq!(
+      response.result.unwrap()["protocolVersion"],
+      MCP_PROTOCOL_VERSION
+    );
+
+    // Routes are listed on the first call, and the call gets the original name.
+    let params = json!({"name": "weather_forecast", "arguments": {"city": "Paris"}});
+    let response = gateway.call_named(Catalog::Tools, "tools/call", &request("tools/call", params));
+    assert_eq!(response.id, json!(7));
+    assert_eq!(
+      response.result.unwrap(),
+      json!({"name": "forecast", "arguments": {"city": "Paris"}})
+    );
+    let forwarded = weather_requests.lock().unwrap().last().unwrap().clone();
+    assert_eq!(forwarded["method"], "tools/call");
+    assert_ne!(forwarded["id"], json!(7));
+
+    // Denied tools are not routed.
+    let params = json!({"name": "files_read"});
+    let response = gateway.call_named(Catalog::Tools, "tools/call", &request("tools/call", params));
+    assert_eq!(response.error.unwrap()["code"], -32602);
+    let response = gateway.call_named(
+      Catalog::Tools,
+      "tools/call",
+      &request("tools/call", json!({})),
+    );
+    assert_eq!(response.error.unwrap()["code"], -32602);
+    assert!(files_requests
+      .lock()
+      .unwrap()
+      .iter()
+      .all(|r| r["method"] != "tools/call"));
+
+    assert_eq!(gateway.resource_server("file:///a"), None);
+    assert_eq!(gateway.list_resources(false).len(), 1);
+    assert_eq!(gateway.list_resources(true)[1]["name"], "weather_city");
+    assert_eq!(
+      gateway.resource_server("file:///a").as_deref(),
+      Some("files")
+    );
+    assert_eq!(
+      gateway.resource_server("file:///b").as_deref(),
+      Some("files")
+    );
+    assert_eq!(
+      gateway.resource_server("weather://paris").as_deref(),
+      Some("weather")
+    );
+    assert_eq!(gateway.resource_server("other://x"), None);
+  }
+
+  #[test]
+  fn test_tool_filter() {
+    let filter = ToolFilter {
+      allow: vec!["weather_*".to_string(), "fs_read_*".to_string()],
+      deny: vec!["*_delete*".to_string()],
+    };
+    assert!(filter.allows("weather_forecast"));
+    assert!(filter.allows("fs_read_file"));
+    assert!(!filter.allows("fs_write_file"));
+    assert!(!filter.allows("weather_delete_alert"));
+    assert!(ToolFilter::default().allows("anything"));
+    assert!(glob_match("a*b*c", "aXbYc"));
+    assert!(!glob_match("ab*ba", "aba"));
+    assert!(!glob_match("exact", "exactly"));
+  }
+}
@@ -0,0 +1,488 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * Communication history of a session. Every message exchanged with a MCP
+ * server is written to `<server>.jsonl` as an envelope, and `manifest.json`
+ * links all files of the session.
+ */
+use crate::secret;
+use crate::types::NahError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+const MANIFEST_VERSION: u32 = 1;
+
+/**
+ * Serializes changes of manifests.
+ */
+static MANIFEST_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+  #[serde(rename = "clientToServer")]
+  ClientToServer,
+  #[serde(rename = "serverToClient")]
+  ServerToClient,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Transport {
+  #[serde(rename = "stdio")]
+  Stdio,
+  #[serde(rename = "http")]
+  Http,
+}
+
+/**
+ * A message in the history file, with where and when it was sent.
+ */
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Envelope {
+  /// RFC 3339 time in UTC.
+  pub timestamp: String,
+  pub direction: Direction,
+  pub transport: Transport,
+  pub server: String,
+  /// For a response, the time since its request was sent.
+  #[serde(rename = "latencyMs", skip_serializing_if = "Option::is_none")]
+  pub latency_ms: Option<u64>,
+  /// The JSON-RPC message, or the raw line when it is not valid JSON.
+  pub message: Value,
+}
+
+/**
+ * Writes messages of a server to its history file and pairs responses with
+ * their requests.
+ */
+pub struct TrafficRecorder {
+  server_name: String,
+  transport: Transport,
+  file: File,
+  /// Requests waiting for responses, by direction and id.
+  pending: HashMap<(bool, String), Instant>,
+}
+
+impl TrafficRecorder {
+  /**
+   * Open the history file of a server and register it in the manifest.
+   */
+  pub fn open(
+    history_path: &Path,
+    server_name: &str,
+    transport: Transport,
+    stderr_file: Option<&str>,
+  ) -> Result<Self, NahError> {
+    let file_name = format!("{}.jsonl", server_name);
+    let file_path = history_path.join(&file_name);
+    let file = match OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&file_path)
+    {
+      Ok(f) => f,
+      Err(e) => {
+        return Err(NahError::io_error(
+          &format!("Failed to create history file: {}", file_path.display()),
+          Some(Box::new(e)),
+        ));
+      }
+    };
+    Manifest::update(history_path, |manifest| {
+      manifest.servers.insert(
+        server_name.to_string(),
+        ManifestServer {
+          transport,
+          log: file_name,
+          stderr: stderr_file.map(|s| s.to_string()),
+        },
+      );
+    })?;
+    Ok(TrafficRecorder {
+      server_name: server_name.to_string(),
+      transport,
+      file,
+      pending: HashMap::new(),
+    })
+  }
+
+  /**
+   * Record a message sent to the server.
+   */
+  pub fn record_sent(&mut self, message: Value) {
+    self.record(Direction::ClientToServer, message);
+  }
+
+  /**
+   * Record a line received from the server.
+   */
+  pub fn record_received(&mut self, line: &str) {
+    self.record_line(Direction::ServerToClient, line);
+  }
+
+  /**
+   * Record a line of a message, which is kept as a string when it is not
+   * valid JSON.
+   */
+  pub fn record_line(&mut self, direction: Direction, line: &str) {
+    let line = line.trim_end();
+    let message = serde_json::from_str(line).unwrap_or_else(|_| Value::String(line.to_string()));
+    self.record(direction, message);
+  }
+
+  fn record(&mut self, direction: Direction, mut message: Value) {
+    let outgoing = direction == Direction::ClientToServer;
+    let mut latency_ms = None;
+    if let Some(id) = message.get("id") {
+      let id = id.to_string();
+      if message.get("method").is_some() {
+        self.pending.insert((outgoing, id), Instant::now());
+      } else if let Some(sent_at) = self.pending.remove(&(!outgoing, id)) {
+        latency_ms = Some(sent_at.elapsed().as_millis() as u64);
+      }
+    }
+    secret::mask(&mut message);
+    let envelope = Envelope {
+      timestamp: format_timestamp(SystemTime::now()),
+      direction,
+      transport: self.transport,
+      server: self.server_name.clone(),
+      latency_ms,
+      message,
+    };
+    let mut line = serde_json::to_string(&envelope).unwrap();
+    line.push('\n');
+    let _ = self.file.write_all(line.as_bytes());
+  }
+
+  pub fn flush(&mut self) {
+    let _ = self.file.flush();
+  }
+}
+
+/**
+ * Index of the files in a history directory.
+ */
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Manifest {
+  pub version: u32,
+  #[serde(rename = "startedAt")]
+  pub started_at: String,
+  #[serde(rename = "configFiles", default)]
+  pub config_files: Vec<PathBuf>,
+  #[serde(default)]
+  pub servers: BTreeMap<String, ManifestServer>,
+  /// Chat transcripts.
+  #[serde(default)]
+  pub chats: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestServer {
+  pub transport: Transport,
+  /// History file of the server.
+  pub log: String,
+  /// File of the standard error output of a local server.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub stderr: Option<String>,
+}
+
+impl Manifest {
+  /**
+   * Start the manifest of a new session.
+   */
+  pub fn create(history_path: &Path, config_files: &[PathBuf]) -> Result<(), NahError> {
+    Manifest::update(history_path, |manifest| {
+      manifest.config_files = config_files
+        .iter()
+        .map(|p| p.canonicalize().unwrap_or_else(|_| p.clone()))
+        .collect();
+    })
+  }
+
+  pub fn load(history_path: &Path) -> Result<Manifest, NahError> {
+    let path = history_path.join(MANIFEST_FILE);
+    let file = match File::open(&path) {
+      Ok(f) => f,
+      Err(e) => {
+        return Err(NahError::io_error(
+          &format!("Failed to open {}", path.display()),
+          Some(Box::new(e)),
+        ));
+      }
+    };
+    match serde_json::from_reader(file) {
+      Ok(m) => Ok(m),
+      Err(e) => Err(NahError::invalid_value(
+        &format!("invalid manifest {}", path.display()),
+        Some(Box::new(e)),
+      )),
+    }
+  }
+
+  /**
+   * Change the manifest in a history directory, creating it when missing.
+   */
+  pub fn update<F: FnOnce(&mut Manifest)>(history_path: &Path, f: F) -> Result<(), NahError> {
+    // Servers starting in parallel register themselves at the same time.
+    let _lock = MANIFEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut manifest = match Manifest::load(history_path) {
+      Ok(m) => m,
+      Err(_) => Manifest {
+        version: MANIFEST_VERSION,
+        started_at: format_timestamp(SystemTime::now()),
+        ..Default::default()
+      },
+    };
+    f(&mut manifest);
+    let path = history_path.join(MANIFEST_FILE);
+    let data = serde_json::to_string_pretty(&manifest).unwrap();
+    match std::fs::write(&path, data) {
+      Ok(()) => Ok(()),
+      Err(e) => Err(NahError::io_error(
+        &format!("Failed to write {}", path.display()),
+        Some(Box::new(e)),
+      )),
+    }
+  }
+}
+
+/**
+ * A message in the history of a session.
+ */
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+  pub envelope: Envelope,
+  /// Method of the message, or of its request for a response.
+  pub method: Option<String>,
+}
+
+impl HistoryEntry {
+  pub fn id(&self) -> Option<&Value> {
+    self.envelope.message.get("id")
+  }
+
+  pub fn is_request(&self) -> bool {
+    self.id().is_some() && self.envelope.message.get("method").is_some()
+  }
+
+  /**
+   * Whether the message is a JSON-RPC error or a tool result with `isError`.
+   */
+  pub fn is_error(&self) -> bool {
+    is_error_message(&self.envelope.message)
+  }
+
+  /**
+   * Status of a response, empty for other messages.
+   */
+  pub fn status(&self) -> &'static str {
+    if self.is_error() {
+      "error"
+    } else if self.envelope.message.get("result").is_some() {
+      "ok"
+    } else {
+      ""
+    }
+  }
+}
+
+/**
+ * Whether a message is a JSON-RPC error or a tool result with `isError`.
+ */
+pub fn is_error_message(message: &Value) -> bool {
+  message.get("error").is_some()
+    || message
+      .pointer("/result/isError")
+      .and_then(|v| v.as_bool())
+      .unwrap_or(false)
+}
+
+/**
+ * Methods of pending requests by direction and id, to name their responses.
+ */
+#[derive(Debug, Default)]
+pub struct MethodTracker {
+  methods: HashMap<(Direction, String), String>,
+}
+
+impl MethodTracker {
+  /**
+   * Method of a request or notification, or of the request a response
+   * answers.
+   */
+  pub fn method(&mut self, direction: Direction, message: &Value) -> Option<String> {
+    let id = message.get("id").map(|id| id.to_string());
+    match (message.get("method").and_then(|m| m.as_str()), id) {
+      (Some(method), Some(id)) => {
+        self.methods.insert((direction, id), method.to_string());
+        Some(method.to_string())
+      }
+      (Some(method), None) => Some(method.to_string()),
+      (None, Some(id)) => {
+        let request_direction = match direction {
+          Direction::ClientToServer => Direction::ServerToClient,
+          Direction::ServerToClient => Direction::ClientToServer,
+        };
+        self.methods.remove(&(request_direction, id))
+      }
+      (None, None) => None,
+    }
+  }
+}
+
+/**
+ * Load all server messages of a session in the order they were recorded.
+ */
+pub fn load_session(history_path: &Path) -> Result<Vec<HistoryEntry>, NahError> {
+  let manifest = Manifest::load(history_path)?;
+  let mut entries = Vec::new();
+  for server in manifest.servers.values() {
+    let path = history_path.join(&server.log);
+    let data = match std::fs::read_to_string(&path) {
+      Ok(d) => d,
+      Err(e) => {
+        return Err(NahError::io_error(
+          &format!("Failed to read history file: {}", path.display()),
+          Some(Box::new(e)),
+        ));
+      }
+    };
+    let mut methods = MethodTracker::default();
+    for line in data.lines() {
+      let Ok(envelope) = serde_json::from_str::<Envelope>(line) else {
+        continue;
+      };
+      let method = methods.method(envelope.direction, &envelope.message);
+      entries.push(HistoryEntry { envelope, method });
+    }
+  }
+  // Timestamps in RFC 3339 with a fixed width are ordered as strings.
+  entries.sort_by(|a, b| a.envelope.timestamp.cmp(&b.envelope.timestamp));
+  Ok(entries)
+}
+
+/**
+ * Filter of history entries. Unset fields match all entries.
+ */
+#[derive(Debug, Default)]
+pub struct HistoryFilter {
+  pub server: Option<String>,
+  pub method: Option<String>,
+  pub errors_only: bool,
+}
+
+impl HistoryFilter {
+  pub fn matches(&self, entry: &HistoryEntry) -> bool {
+    if let Some(server) = &self.server {
+      if entry.envelope.server != *server {
+        return false;
+      }
+    }
+    if let Some(method) = &self.method {
+      if entry.method.as_deref() != Some(method.as_str()) {
+        return false;
+      }
+    }
+    !self.errors_only || entry.is_error()
+  }
+}
+
+/**
+ * Format a time as RFC 3339 in UTC with milliseconds.
+ */
+pub fn format_timestamp(time: SystemTime) -> String {
+  let since_epoch = time
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .unwrap_or_default();
+  let secs = since_epoch.as_secs();
+  let (days, secs_of_day) = ((secs / 86400) as i64, secs % 86400);
+  // Civil date from days since 1970-01-01, see
+  // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z - era * 146097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+  format!(
+    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+    year,
+    month,
+    day,
+    secs_of_day / 3600,
+    secs_of_day % 3600 / 60,
+    secs_of_day % 60,
+    since_epoch.subsec_millis()
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Duration;
+
+  #[test]
+  fn test_format_timestamp() {
+    let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+    assert_eq!(format_timestamp(time), "2024-02-29T12:34:56.789Z");
+    assert_eq!(
+      format_timestamp(SystemTime::UNIX_EPOCH),
+      "1970-01-01T00:00:00.000Z"
+    );
+  }
+
+  #[test]
+  fn test_recorder() {
+    let dir = std::env::temp_dir().join(format!("nah_test_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut recorder =
+      TrafficRecorder::open(&dir, "weather", Transport::Stdio, Some("weather.stderr")).unwrap();
+    recorder.record_sent(serde_json::json!({"jsonrpc": "2.0", "id": "1", "method": "tools/list"}));
+    recorder.record_received("{\"jsonrpc\": \"2.0\", \"id\": \"1\", \"result\": {}}\n");
+    recorder.record_received("not json\n");
+    recorder.flush();
+
+    let data = std::fs::read_to_string(dir.join("weather.jsonl")).unwrap();
+    let envelopes: Vec<Envelope> = data
+      .lines()
+      .map(|l| serde_json::from_str(l).unwrap())
+      .collect();
+    assert_eq!(envelopes.len(), 3);
+    assert_eq!(envelopes[0].direction, Direction::ClientToServer);
+    assert!(envelopes[0].latency_ms.is_none());
+    assert_eq!(envelopes[1].direction, Direction::ServerToClient);
+    assert!(envelopes[1].latency_ms.is_some());
+    assert_eq!(envelopes[2].message, Value::String("not json".to_string()));
+
+    let manifest = Manifest::load(&dir).unwrap();
+    assert_eq!(manifest.servers["weather"].log, "weather.jsonl");
+    assert_eq!(
+      manifest.servers["weather"].stderr.as_deref(),
+      Some("weather.stderr")
+    );
+
+    let entries = load_session(&dir).unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[1].method.as_deref(), Some("tools/list"));
+    assert_eq!(entries[1].status(), "ok");
+    let filter = HistoryFilter {
+      method: Some("tools/list".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(entries.iter().filter(|e| filter.matches(e)).count(), 2);
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+}
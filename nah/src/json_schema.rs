@@ -6,67 +6,290 @@
 /**
  * Utilities to process JSON Schema.
  */
+use nah_mcp_types::MCPToolDefinition;
+use serde::Serialize;
 use serde_json::Value;
 
 use crate::types::NahError;
 
-enum JSONSchemaTypeName {
+/**
+ * Max depth of nested `$ref` to follow when creating a template.
+ */
+const MAX_REF_DEPTH: usize = 16;
+
+pub enum JSONSchemaTypeName {
   String,
   Object,
+  Array,
   Number,
+  Integer,
   Boolean,
   Null,
+  Unknown,
 }
 
 /**
  * Create a JSON instance template from a JSON Schema.
+ *
+ * Descriptions, required markers, options and defaults of object fields are
+ * written as `//` comment lines, which are removed before the arguments are
+ * parsed.
  */
 pub fn create_instance_template(schema: &Value) -> Result<String, NahError> {
-  let mut lines = create_instance_template_impl(schema, 0)?;
-  lines.last_mut().unwrap().pop();
+  let lines = create_instance_template_impl(schema, schema, 0)?;
   Ok(lines.join("\n"))
 }
 
-fn create_instance_template_impl(schema: &Value, indent: usize) -> Result<Vec<String>, NahError> {
-  let type_name = get_type_name(schema)?;
-  let mut indent_string = String::with_capacity(indent);
-  for _i in 0..indent {
-    indent_string.push(' ');
+/**
+ * Create the template lines of a value. The first line has no indent so that
+ * it can be connected to a field name, and the following lines are indented
+ * relatively to it.
+ */
+fn create_instance_template_impl(
+  root: &Value,
+  schema: &Value,
+  depth: usize,
+) -> Result<Vec<String>, NahError> {
+  let Some(schema) = resolve_ref(root, schema, depth)? else {
+    return Ok(vec!["<FILL A VALUE>".to_owned()]);
+  };
+  if let Value::Bool(_) = schema {
+    return Ok(vec!["<FILL A VALUE>".to_owned()]);
+  }
+  if let Some(value) = get_suggested_value(schema) {
+    let mut buf = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buf, formatter);
+    value.serialize(&mut serializer).unwrap();
+    return Ok(
+      String::from_utf8(buf)
+        .unwrap()
+        .split('\n')
+        .map(|l| l.to_owned())
+        .collect(),
+    );
+  }
+  if let Some(alternative) = get_first_alternative(schema) {
+    return create_instance_template_impl(root, alternative, depth);
   }
 
-  match type_name {
-    JSONSchemaTypeName::String => Ok(vec!["\"<FILL A STRING>\",".to_owned()]),
-    JSONSchemaTypeName::Null => Ok(vec!["null,".to_owned()]),
-    JSONSchemaTypeName::Number => Ok(vec!["<FILL A NUMBER>,".to_owned()]),
-    JSONSchemaTypeName::Boolean => Ok(vec!["<FILL A BOOLEAN VALUE>,".to_owned()]),
+  match get_type_name(schema)? {
+    JSONSchemaTypeName::String => Ok(vec!["\"<FILL A STRING>\"".to_owned()]),
+    JSONSchemaTypeName::Null => Ok(vec!["null".to_owned()]),
+    JSONSchemaTypeName::Number => Ok(vec!["<FILL A NUMBER>".to_owned()]),
+    JSONSchemaTypeName::Integer => Ok(vec!["<FILL AN INTEGER>".to_owned()]),
+    JSONSchemaTypeName::Boolean => Ok(vec!["<FILL A BOOLEAN VALUE>".to_owned()]),
+    JSONSchemaTypeName::Unknown => Ok(vec!["<FILL A VALUE>".to_owned()]),
+    JSONSchemaTypeName::Array => {
+      let item_schema = match schema.get("prefixItems").or_else(|| schema.get("items")) {
+        Some(Value::Array(item_schemas)) => item_schemas.first(),
+        item_schema => item_schema,
+      };
+      let Some(item_schema) = item_schema else {
+        return Ok(vec!["[]".to_owned()]);
+      };
+      let mut result = vec!["[".to_owned()];
+      for line in create_instance_template_impl(root, item_schema, depth)? {
+        result.push(format!("    {}", line));
+      }
+      result.push("]".to_owned());
+      Ok(result)
+    }
     JSONSchemaTypeName::Object => {
-      let mut result = Vec::new();
-      let properties = get_properties(schema)?;
-      result.push("{".to_owned());
-      for (field_name, field_schema) in properties.iter() {
-        let field_template = create_instance_template_impl(field_schema, indent + 4)?;
-        // grab the first line to connect field_name;
+      let properties = get_properties(schema);
+      if properties.is_empty() {
+        return Ok(vec!["{}".to_owned()]);
+      }
+      let required: Vec<&str> = schema
+        .get("required")
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default();
+      let mut result = vec!["{".to_owned()];
+      for (idx, (field_name, field_schema)) in properties.iter().enumerate() {
+        let resolved_field_schema = resolve_ref(root, field_schema, depth)?.unwrap_or(field_schema);
+        for line in get_comment_lines(
+          resolved_field_schema,
+          required.contains(&field_name.as_str()),
+        ) {
+          result.push(format!("    // {}", line));
+        }
+        let mut field_template = create_instance_template_impl(root, field_schema, depth)?;
+        if idx + 1 != properties.len() {
+          field_template.last_mut().unwrap().push(',');
+        }
+        // connect the field name with the first line
         result.push(format!(
-          "{}    \"{}\": {}",
-          indent_string, field_name, field_template[0]
+          "    {}: {}",
+          serde_json::to_string(field_name).unwrap(),
+          field_template[0]
         ));
-        for i in 1..field_template.len() {
-          result.push(field_template[i].clone());
+        for line in field_template.iter().skip(1) {
+          result.push(format!("    {}", line));
         }
       }
-      // Remove the extra comma in the end of last line to follow JSON format
-      result.last_mut().and_then(|line| {
-        if line.ends_with(",") {
-          line.pop();
-        };
-        Some(line)
-      });
-      result.push(format!("{}}},", indent_string));
+      result.push("}".to_owned());
       Ok(result)
     }
   }
 }
 
+/**
+ * Follow `$ref` of a schema until reaching a schema without it. Return `None`
+ * if the references are nested too deeply, which happens on recursive schemas.
+ */
+pub fn resolve_ref<'a>(
+  root: &'a Value,
+  schema: &'a Value,
+  depth: usize,
+) -> Result<Option<&'a Value>, NahError> {
+  let Some(reference) = schema.get("$ref").and_then(|v| v.as_str()) else {
+    return Ok(Some(schema));
+  };
+  if depth >= MAX_REF_DEPTH {
+    return Ok(None);
+  }
+  let target = match reference.strip_prefix('#') {
+    Some("") => Some(root),
+    Some(pointer) => root.pointer(pointer),
+    None => None,
+  };
+  match target {
+    Some(target) => resolve_ref(root, target, depth + 1),
+    None => Err(NahError::received_invalid_json_schema(
+      &format!("cannot resolve $ref {}", reference),
+      None,
+    )),
+  }
+}
+
+/**
+ * Get the value to pre-fill for a schema: `const`, `default` or the first
+ * option of `enum`.
+ */
+fn get_suggested_value(schema: &Value) -> Option<&Value> {
+  schema
+    .get("const")
+    .or_else(|| schema.get("default"))
+    .or_else(|| {
+      schema
+        .get("enum")
+        .and_then(|v| v.as_array())
+        .and_then(|v| v.first())
+    })
+}
+
+/**
+ * Get the first non-null alternative of `anyOf` or `oneOf`.
+ */
+fn get_first_alternative(schema: &Value) -> Option<&Value> {
+  let alternatives = schema
+    .get("anyOf")
+    .or_else(|| schema.get("oneOf"))
+    .and_then(|v| v.as_array())?;
+  alternatives
+    .iter()
+    .find(|s| s.get("type").and_then(|t| t.as_str()) != Some("null"))
+    .or(alternatives.first())
+}
+
+/**
+ * Create comment lines that describe an object field.
+ */
+fn get_comment_lines(schema: &Value, required: bool) -> Vec<String> {
+  let mut result = Vec::new();
+  let description = schema
+    .get("description")
+    .or_else(|| schema.get("title"))
+    .and_then(|v| v.as_str());
+  let mut first_line = String::new();
+  if required {
+    first_line.push_str("[REQUIRED]");
+  }
+  let mut description_lines = description.into_iter().flat_map(|d| d.lines());
+  if let Some(line) = description_lines.next() {
+    if !first_line.is_empty() {
+      first_line.push(' ');
+    }
+    first_line.push_str(line.trim());
+  }
+  if !first_line.is_empty() {
+    result.push(first_line);
+  }
+  result.extend(description_lines.map(|l| l.trim().to_owned()));
+
+  if let Some(options) = schema.get("enum").and_then(|v| v.as_array()) {
+    let options: Vec<String> = options.iter().map(|v| v.to_string()).collect();
+    result.push(format!("Options: {}", options.join(", ")));
+  }
+  let alternatives = schema
+    .get("anyOf")
+    .or_else(|| schema.get("oneOf"))
+    .and_then(|v| v.as_array());
+  if let Some(alternatives) = alternatives {
+    let names: Vec<String> = alternatives.iter().map(get_schema_summary).collect();
+    result.push(format!("One of: {}", names.join(" | ")));
+  }
+  if let Some(default) = schema.get("default") {
+    result.push(format!("Default: {}", default));
+  }
+  result
+}
+
+/**
+ * Get a short summary of a schema, e.g. `string` or `#/$defs/point`.
+ */
+pub fn get_schema_summary(schema: &Value) -> String {
+  if let Some(reference) = schema.get("$ref").and_then(|v| v.as_str()) {
+    return reference.to_owned();
+  }
+  if let Some(title) = schema.get("title").and_then(|v| v.as_str()) {
+    return title.to_owned();
+  }
+  match schema.get("type") {
+    Some(Value::String(s)) => s.to_owned(),
+    Some(Value::Array(arr)) => {
+      let names: Vec<&str> = arr.iter().filter_map(|v| v.as_str()).collect();
+      names.join(" | ")
+    }
+    _ => "any".to_owned(),
+  }
+}
+
+/**
+ * Validate the arguments of a tool call against the input schema of the tool.
+ */
+pub fn validate_arguments(tool: &MCPToolDefinition, args: &Value) -> Result<(), NahError> {
+  let errors = nah_mcp_types::json_schema::validate(&tool.input_schema, args);
+  if errors.is_empty() {
+    Ok(())
+  } else {
+    let details: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    Err(NahError::arguments_not_match_schema(&tool.name, &details))
+  }
+}
+
+/**
+ * Check the `structuredContent` of a tool call result against the output schema
+ * of the tool. Return a list of problems, which is empty if the result conforms.
+ * Error results are not checked, as they don't carry structured content.
+ */
+pub fn check_structured_content(output_schema: &Value, result: &Value) -> Vec<String> {
+  if result.get("isError").and_then(|v| v.as_bool()) == Some(true) {
+    return Vec::new();
+  }
+  match result
+    .as_object()
+    .and_then(|obj| obj.get("structuredContent"))
+  {
+    None => vec!["structuredContent is missing from the result".to_owned()],
+    Some(content) => nah_mcp_types::json_schema::validate(output_schema, content)
+      .iter()
+      .map(|e| e.to_string())
+      .collect(),
+  }
+}
+
 /// Return true if the argument is an empty JSON object.
 pub fn is_empty_object(value: &Value) -> bool {
   value
@@ -76,51 +299,67 @@ pub fn is_empty_object(value: &Value) -> bool {
 }
 
 /**
- * Get type of a JSON Schema
+ * Return true if the schema of tool arguments declares no property.
  */
-fn get_type_name(schema: &Value) -> Result<JSONSchemaTypeName, NahError> {
-  match schema
-    .as_object()
-    .and_then(|v| v.get("type"))
-    .and_then(|v| v.as_str())
-  {
-    Some(type_name) => match type_name {
-      "string" => Ok(JSONSchemaTypeName::String),
-      "object" => Ok(JSONSchemaTypeName::Object),
-      "number" => Ok(JSONSchemaTypeName::Number),
-      "integer" => Ok(JSONSchemaTypeName::Number),
-      "boolean" => Ok(JSONSchemaTypeName::Boolean),
-      "null" => Ok(JSONSchemaTypeName::Null),
-      _ => Err(NahError::received_invalid_json_schema(
-        &format!("unknown type name : {}", type_name),
-        None,
-      )),
-    },
-    None => Err(NahError::received_invalid_json_schema(
-      "type name not found",
-      None,
-    )),
-  }
+pub fn has_no_properties(schema: &Value) -> bool {
+  schema.get("properties").is_none_or(is_empty_object)
 }
 
 /**
- * Get properties as a list of field name and the type schema
+ * Get type of a JSON Schema. For a list of types such as `["string", "null"]`,
+ * the first non-null type is returned.
  */
-fn get_properties(schema: &Value) -> Result<Vec<(&String, &Value)>, NahError> {
-  let properties = match schema
-    .as_object()
-    .and_then(|v| v.get("properties"))
-    .and_then(|v| v.as_object())
-  {
-    None => {
+pub fn get_type_name(schema: &Value) -> Result<JSONSchemaTypeName, NahError> {
+  let type_name = match schema.get("type") {
+    Some(Value::String(s)) => s.as_str(),
+    Some(Value::Array(arr)) => {
+      let type_names: Vec<&str> = arr.iter().filter_map(|v| v.as_str()).collect();
+      match type_names.iter().find(|t| **t != "null") {
+        Some(t) => t,
+        None => type_names.first().copied().unwrap_or("null"),
+      }
+    }
+    Some(_) => {
       return Err(NahError::received_invalid_json_schema(
-        "properties not found on an object",
+        "type should be a string or an array",
         None,
-      ));
+      ))
+    }
+    None => {
+      // Guess the type from other keywords
+      return Ok(if schema.get("properties").is_some() {
+        JSONSchemaTypeName::Object
+      } else if schema.get("items").is_some() || schema.get("prefixItems").is_some() {
+        JSONSchemaTypeName::Array
+      } else {
+        JSONSchemaTypeName::Unknown
+      });
     }
-    Some(p) => p,
   };
-  Ok(properties.iter().collect())
+  match type_name {
+    "string" => Ok(JSONSchemaTypeName::String),
+    "object" => Ok(JSONSchemaTypeName::Object),
+    "array" => Ok(JSONSchemaTypeName::Array),
+    "number" => Ok(JSONSchemaTypeName::Number),
+    "integer" => Ok(JSONSchemaTypeName::Integer),
+    "boolean" => Ok(JSONSchemaTypeName::Boolean),
+    "null" => Ok(JSONSchemaTypeName::Null),
+    _ => Err(NahError::received_invalid_json_schema(
+      &format!("unknown type name : {}", type_name),
+      None,
+    )),
+  }
+}
+
+/**
+ * Get properties as a list of field name and the type schema. An object
+ * without `properties` has no field.
+ */
+pub fn get_properties(schema: &Value) -> Vec<(&String, &Value)> {
+  match schema.get("properties").and_then(|v| v.as_object()) {
+    None => Vec::new(),
+    Some(p) => p.iter().collect(),
+  }
 }
 
 #[cfg(test)]
@@ -134,4 +373,104 @@ mod tests {
     assert_eq!(is_empty_object(&json!({"key": 1})), false);
     assert_eq!(is_empty_object(&json!(0)), false);
   }
+
+  #[test]
+  fn test_create_instance_template() {
+    let schema = json!({
+      "type": "object",
+      "$defs": {
+        "point": {
+          "type": "object",
+          "description": "A point",
+          "properties": {"x": {"type": "number"}, "y": {"type": "number"}}
+        }
+      },
+      "properties": {
+        "city": {"type": "string", "description": "City name"},
+        "days": {"type": ["integer", "null"]},
+        "unit": {"enum": ["C", "F"], "default": "F"},
+        "tags": {"type": "array", "items": {"type": "string"}},
+        "origin": {"$ref": "#/$defs/point"},
+        "target": {"anyOf": [{"type": "null"}, {"$ref": "#/$defs/point"}]},
+        "extra": {"type": "object"}
+      },
+      "required": ["city"]
+    });
+    let template = create_instance_template(&schema).unwrap();
+    assert_eq!(
+      template,
+      r#"{
+    // [REQUIRED] City name
+    "city": "<FILL A STRING>",
+    "days": <FILL AN INTEGER>,
+    "extra": {},
+    // A point
+    "origin": {
+        "x": <FILL A NUMBER>,
+        "y": <FILL A NUMBER>
+    },
+    "tags": [
+        "<FILL A STRING>"
+    ],
+    // One of: null | #/$defs/point
+    "target": {
+        "x": <FILL A NUMBER>,
+        "y": <FILL A NUMBER>
+    },
+    // Options: "C", "F"
+    // Default: "F"
+    "unit": "F"
+}"#
+    );
+    assert!(create_instance_template(&json!({"type": "object"})).unwrap() == "{}");
+    assert!(create_instance_template(&json!({"type": "foo"})).is_err());
+    assert!(create_instance_template(&json!({"$ref": "#/missing"})).is_err());
+  }
+
+  #[test]
+  fn test_validate_arguments() {
+    let tool: nah_mcp_types::MCPToolDefinition = serde_json::from_value(json!({
+      "name": "get_weather",
+      "inputSchema": {
+        "type": "object",
+        "properties": {
+          "city": {"type": "string"},
+          "days": {"type": "integer", "minimum": 1}
+        },
+        "required": ["city"]
+      }
+    }))
+    .unwrap();
+    assert!(validate_arguments(&tool, &json!({"city": "Paris", "days": 2})).is_ok());
+    let err = validate_arguments(&tool, &json!({"days": 0})).unwrap_err();
+    assert_eq!(err.code, 11);
+    assert_eq!(
+      err.message,
+      "Arguments of tool get_weather do not match its inputSchema:\n  \
+       * /city: required property is missing\n  \
+       * /days: expected a value >= 1, found 0"
+    );
+  }
+
+  #[test]
+  fn test_check_structured_content() {
+    let schema = json!({
+      "type": "object",
+      "properties": {"temperature": {"type": "number"}},
+      "required": ["temperature"]
+    });
+    let valid = json!({"content": [], "structuredContent": {"temperature": 21.5}});
+    assert!(check_structured_content(&schema, &valid).is_empty());
+    let invalid = json!({"content": [], "structuredContent": {"temperature": "hot"}});
+    assert_eq!(
+      check_structured_content(&schema, &invalid),
+      vec!["/temperature: expected number, found string".to_owned()]
+    );
+    assert_eq!(
+      check_structured_content(&schema, &json!({"content": []})).len(),
+      1
+    );
+    let error = json!({"content": [{"type": "text", "text": "boom"}], "isError": true});
+    assert!(check_structured_content(&schema, &error).is_empty());
+  }
 }
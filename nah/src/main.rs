@@ -4,35 +4,138 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 mod chat;
+mod command;
 mod config;
 mod editor;
+mod form;
+mod gateway;
+mod history;
 mod json_schema;
 mod mcp;
+mod mock;
+mod oneshot;
+#[macro_use]
+mod output;
+mod proxy;
+mod replay;
+mod script;
+mod secret;
+mod shell_helper;
+mod startup;
 mod types;
 mod utils;
+mod validate;
 
-use clap::Parser;
-use config::{load_config, ModelConfig};
+use clap::{Parser, Subcommand};
+use config::{load_configs, ArgumentInputMode, ModelConfig};
 use editor::launch_editor;
-use mcp::{MCPLocalServerCommand, MCPLocalServerProcess, MCPServer};
+use history::{Direction, HistoryEntry, HistoryFilter, Manifest};
+use mcp::{MCPLocalServerCommand, MCPLocalServerProcess, MCPRequest, MCPServer};
+use nah_mcp_types::MCPPromptArgument;
+use output::OutputMode;
+use script::OnError;
 use serde_json::Value;
+use shell_helper::{CompletionCandidates, NahHelper};
+use startup::ServerConfigs;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 use types::NahError;
 
-use crate::mcp::{MCPHTTPServerConnection, MCPRemoteServerConfig};
+use crate::mcp::MCPRemoteServerConfig;
 
 /// Read some lines of a file
 #[derive(Debug, Parser)]
 struct Cli {
-  /// JSON config file that declares the `mcpServers` field.
-  mcp_config_file: PathBuf,
+  /// JSON config file that declares the `mcpServers` field. Without it, the
+  /// user config is merged with project configs found from the working
+  /// directory (`.vscode/mcp.json`, `.cursor/mcp.json`, `.mcp.json`, `.nah.json`).
+  mcp_config_file: Option<PathBuf>,
   /// Path to store history records.
   #[arg(long, value_name = "PATH")]
   history_path: Option<PathBuf>,
+  /// Run commands in a script file and exit.
+  #[arg(long, value_name = "PATH")]
+  script: Option<PathBuf>,
+  /// Keep running the script when a command fails.
+  #[arg(long, requires = "script")]
+  continue_on_error: bool,
+  /// Output format of shell commands. In `json` mode, each command prints a
+  /// JSON document on a single line.
+  #[arg(long, value_enum, default_value_t)]
+  output: OutputMode,
+  /// Start servers when they are first used instead of when nah starts.
+  #[arg(long)]
+  lazy: bool,
+  /// Run a single request and exit instead of starting the interactive shell.
+  #[command(subcommand)]
+  command: Option<CliCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+enum CliCommand {
+  /// Start all servers and chat with a model right away.
+  Chat {
+    /// Name of the model profile in `models`.
+    #[arg(long)]
+    model: Option<String>,
+  },
+  /// Check config files and report every problem found.
+  Validate {
+    /// Config file to check. Defaults to the config file given before the
+    /// command, or the user and project configs.
+    config: Option<PathBuf>,
+  },
+  /// Send requests recorded in a history directory to restarted servers and
+  /// compare the responses with the recorded ones.
+  Replay {
+    /// History directory of the session to replay.
+    history_dir: PathBuf,
+    /// JSON pointer of a field to ignore, like `/result/content/*/timestamp`.
+    /// `*` matches all keys or items. Ids are always ignored, and `timestamps`
+    /// ignores all date-time strings.
+    #[arg(long = "mask", value_name = "POINTER")]
+    masks: Vec<String>,
+  },
+  /// Act as a stdio MCP server answering requests with the responses
+  /// recorded in a server history file.
+  Mock {
+    /// History file of a server, like `history/weather.jsonl`.
+    history_file: PathBuf,
+  },
+  /// Act as a stdio MCP server forwarding all messages to a configured local
+  /// server, and record them in the history folder.
+  Proxy {
+    /// Name of the server in `mcpServers`.
+    server: String,
+    /// Print messages matching a filter to stderr: a method like `tools/call`,
+    /// a prefix like `tools/*`, or `errors`.
+    #[arg(long, value_name = "FILTER")]
+    show: Vec<String>,
+    /// Print messages matching a filter and wait for [ENTER] on the terminal
+    /// before forwarding them.
+    #[arg(long, value_name = "FILTER")]
+    pause: Vec<String>,
+  },
+  /// Act as a single MCP server exposing the tools, resources and prompts of
+  /// all configured servers, with names prefixed by their server name.
+  Serve {
+    /// Serve with the Streamable HTTP transport at this address, like
+    /// `127.0.0.1:8080`, instead of stdio.
+    #[arg(long, value_name = "ADDRESS")]
+    http: Option<String>,
+    /// Only expose tools matching a pattern like `weather_*`, where `*`
+    /// matches any characters.
+    #[arg(long = "allow-tool", value_name = "PATTERN")]
+    allow_tools: Vec<String>,
+    /// Hide tools matching a pattern.
+    #[arg(long = "deny-tool", value_name = "PATTERN")]
+    deny_tools: Vec<String>,
+  },
+  #[command(flatten)]
+  OneShot(oneshot::OneShotCommand),
 }
 
 /**
@@ -44,22 +147,121 @@ struct AppContext {
   pub history_path: PathBuf,
   pub server_commands: HashMap<String, MCPLocalServerCommand>,
   pub remote_server_configs: HashMap<String, MCPRemoteServerConfig>,
-  pub model_config: Option<ModelConfig>,
+  pub models: HashMap<String, ModelConfig>,
+  pub default_model: Option<String>,
+  pub argument_input: ArgumentInputMode,
+  /// Variables for scripts. `last` holds the result of the last command.
+  pub variables: HashMap<String, Value>,
+  /// Whether the last processed command failed.
+  pub command_failed: bool,
+  pub on_error: OnError,
+  pub script_depth: usize,
+  pub output: OutputMode,
 }
 
+/**
+ * Maximum nesting of `source` commands.
+ */
+const MAX_SCRIPT_DEPTH: usize = 16;
+
 fn main() {
   let args = Cli::parse();
-  println!("Config file: {:?}", args.mcp_config_file);
-  let data = match load_config(args.mcp_config_file) {
+  // Servers run in their own process groups and don't receive signals from
+  // the terminal, so they are stopped here when nah is interrupted.
+  if let Err(e) = ctrlc::set_handler(|| {
+    mcp::terminate_all_servers();
+    std::process::exit(130);
+  }) {
+    eprintln!("Failed to set the signal handler: {}", e);
+  }
+  let default_panic_hook = std::panic::take_hook();
+  std::panic::set_hook(Box::new(move |info| {
+    // A panic in another thread doesn't end the process, whose servers are
+    // still in use.
+    if std::thread::current().name() == Some("main") {
+      mcp::terminate_all_servers();
+    }
+    default_panic_hook(info);
+  }));
+  let chat_model = match args.command {
+    Some(CliCommand::OneShot(command)) => {
+      let code = match load_configs(args.mcp_config_file, false) {
+        Ok(data) => oneshot::run_one_shot(&data, args.history_path, command),
+        Err(e) => {
+          eprintln!("{}", e);
+          e.code
+        }
+      };
+      std::process::exit(code);
+    }
+    Some(CliCommand::Validate { config }) => {
+      let paths = match config.or(args.mcp_config_file) {
+        Some(path) => vec![path],
+        None => config::default_config_paths(),
+      };
+      if paths.is_empty() {
+        eprintln!("No config file is given, and no user or project config is found");
+        std::process::exit(1);
+      }
+      std::process::exit(validate::run_validate(&paths, args.output));
+    }
+    Some(CliCommand::Replay { history_dir, masks }) => {
+      std::process::exit(replay::run_replay(
+        args.mcp_config_file,
+        &history_dir,
+        args.history_path,
+        &masks,
+        args.output,
+      ));
+    }
+    Some(CliCommand::Mock { history_file }) => {
+      std::process::exit(mock::run_mock(&history_file));
+    }
+    Some(CliCommand::Proxy {
+      server,
+      show,
+      pause,
+    }) => {
+      let code = match load_configs(args.mcp_config_file, false) {
+        Ok(data) => proxy::run_proxy(&data, &server, args.history_path, &show, &pause),
+        Err(e) => {
+          eprintln!("{}", e);
+          e.code
+        }
+      };
+      std::process::exit(code);
+    }
+    Some(CliCommand::Serve {
+      http,
+      allow_tools,
+      deny_tools,
+    }) => {
+      let filter = gateway::ToolFilter {
+        allow: allow_tools,
+        deny: deny_tools,
+      };
+      let code = match load_configs(args.mcp_config_file, false) {
+        Ok(data) => gateway::run_gateway(&data, args.history_path, http.as_deref(), filter),
+        Err(e) => {
+          eprintln!("{}", e);
+          e.code
+        }
+      };
+      std::process::exit(code);
+    }
+    Some(CliCommand::Chat { model }) => Some(model),
+    None => None,
+  };
+  let output = args.output;
+  let data = match load_configs(args.mcp_config_file, true) {
     Ok(d) => d,
     Err(e) => {
-      println!("{}", e);
+      logln!(output, "{}", e);
       return;
     }
   };
-  println!("Found servers:");
-  for server in data.mcp_servers.keys() {
-    println!(" - {}", server);
+  for file in data.files.iter() {
+    logln!(output, "Config file: {:?}", file);
   }
   let timestamp = std::time::SystemTime::now()
     .duration_since(SystemTime::UNIX_EPOCH)
@@ -70,68 +272,101 @@ fn main() {
     Some(p) => p.clone(),
   };
   if std::fs::create_dir(history_path.clone()).is_err() {
-    println!(
+    logln!(
+      output,
       "Failed to create history folder: {}",
       history_path.display()
     );
     return;
   } else {
-    println!(
+    logln!(
+      output,
       "Nah communication history folder: {}",
       history_path.display()
     );
   }
+  if let Err(e) = Manifest::create(&history_path, &data.files) {
+    logln!(output, "{}", e);
+  }
 
+  let default_model = data.default_model_name();
   let mut context = AppContext {
     server_processes: HashMap::new(),
     current_server: None,
     history_path,
     server_commands: data.mcp_servers,
     remote_server_configs: data.mcp_remote_servers,
-    model_config: data.model,
+    default_model,
+    models: data.models,
+    argument_input: data.argument_input.unwrap_or_default(),
+    variables: HashMap::new(),
+    command_failed: false,
+    on_error: if args.continue_on_error {
+      OnError::Continue
+    } else {
+      OnError::Stop
+    },
+    script_depth: 0,
+    output: args.output,
   };
 
-  for (server_name, command) in context.server_commands.iter() {
-    println!("Launching server: {}", server_name);
-
-    let process =
-      match MCPLocalServerProcess::start_and_init(server_name, command, &context.history_path) {
-        Err(e) => {
-          println!(
-            "Fatal error while launching {}, give up this server.",
-            server_name
-          );
-          println!("Error: {}", e);
-          continue;
-        }
-        Ok(p) => p,
-      };
+  let configs = context.server_configs();
+  let mut startup_names = Vec::new();
+  for server_name in configs.names() {
+    if configs.is_disabled(&server_name) {
+      logln!(output, "Server {} is disabled", server_name);
+    } else if args.lazy || configs.is_lazy(&server_name) {
+      logln!(
+        output,
+        "Server {} will be started when it is first used",
+        server_name
+      );
+    } else {
+      startup_names.push(server_name);
+    }
+  }
+  if !startup_names.is_empty() {
+    logln!(output, "Starting servers:");
+  }
+  context.server_processes =
+    startup::start_servers(configs, &startup_names, &context.history_path, output);
 
-    context
-      .server_processes
-      .insert(server_name.to_owned(), Box::new(process));
+  if let Some(model) = chat_model {
+    let mut command_parts = vec!["chat"];
+    command_parts.extend(model.as_deref());
+    let result = context.process_chat(&command_parts);
+    let code = result.map_or_else(|e| e.code, |_| 0);
+    context.stop_servers();
+    std::process::exit(code);
   }
 
-  for (server_name, config) in context.remote_server_configs.iter() {
-    println!("Initializing remote server: {}", server_name);
-    let conn = match MCPHTTPServerConnection::init(server_name, config) {
+  if let Some(script_path) = args.script {
+    let code = match context.run_script(&script_path) {
+      Ok(()) => 0,
       Err(e) => {
-        println!(
-          "Fatal error while initializing {}, give up this server.",
-          server_name
-        );
-        println!("Error: {}", e);
-        continue;
+        let code = e.code;
+        match output {
+          OutputMode::Text => println!("{}", e),
+          OutputMode::Json => println!("{}", output::json_document("script", &Err(e))),
+        }
+        code
       }
-      Ok(p) => p,
     };
-    context
-      .server_processes
-      .insert(server_name.to_owned(), Box::new(conn));
+    context.stop_servers();
+    std::process::exit(code);
   }
 
-  let mut rl = rustyline::DefaultEditor::new().unwrap();
+  let mut rl = rustyline::Editor::new().unwrap();
+  rl.set_helper(Some(NahHelper::new()));
+  let history_file = shell_helper::history_file_path();
+  if let Some(path) = &history_file {
+    // The file doesn't exist in the first session
+    let _ = rl.load_history(path);
+  }
   loop {
+    if let Some(helper) = rl.helper_mut() {
+      helper.candidates = context.completion_candidates();
+    }
     let prompt = match &context.current_server {
       Some(n) => format!("[{}] >> ", n),
       None => ">> ".to_owned(),
@@ -139,9 +374,15 @@ fn main() {
     let inst = rl.readline(&prompt);
     match inst {
       Ok(command) => {
-        if context.process_command(&command) {
-          rl.add_history_entry(command).unwrap();
+        // Empty input is not appended to history. Save the history before
+        // processing the command, as `exit` terminates the process directly.
+        if !command.trim().is_empty() {
+          rl.add_history_entry(command.as_str()).unwrap();
+          if let Some(path) = &history_file {
+            let _ = rl.save_history(path);
+          }
         }
+        context.process_command(&command);
       }
       Err(rustyline::error::ReadlineError::Interrupted) => {
         println!("Interrupted! To exit nah, type `exit`.");
@@ -160,20 +401,29 @@ impl AppContext {
   /**
    * Process a command.
    */
-  fn process_command(&mut self, command: &str) -> bool {
-    let command_parts: Vec<&str> = split_command_into_parts(command);
-    match command_parts.first() {
-      None => {
-        // Empty input, not to append history
-        false
+  fn process_command(&mut self, command: &str) {
+    self.command_failed = false;
+    let output = self.output;
+    let words = match command::split_command_line(command, &self.variables) {
+      Ok(words) => words,
+      Err(e) => {
+        textln!(output, "{}", e);
+        self.finish_command("", Err(e));
+        return;
       }
+    };
+    let command_parts: Vec<&str> = words.iter().map(|w| w.as_str()).collect();
+    match command_parts.first() {
+      // Empty input
+      None => {}
       Some(key) => {
-        match *key {
-          "help" => print_help(),
+        let result = match *key {
+          "help" => self.process_help(),
           "use" => self.process_use(&command_parts),
           "exit" => self.process_exit(),
           "list_servers" => self.process_list_servers(),
           "restart_server" => self.process_restart_server(&command_parts),
+          "server_info" => self.process_server_info(&command_parts),
           "list_tools" => self.process_list_tools(),
           "inspect_tool" => self.process_inspect_tool(&command_parts),
           "call_tool" => self.process_call_tool(&command_parts),
@@ -184,89 +434,300 @@ impl AppContext {
           "inspect_prompt" => self.process_inspect_prompt(&command_parts),
           "get_prompt" => self.process_get_prompt(&command_parts),
           "set_timeout" => self.process_set_timeout(&command_parts),
-          "chat" => self.process_chat(),
+          "chat" => self.process_chat(&command_parts),
+          "source" => self.process_source(&command_parts),
+          "let" => self.process_let(&command_parts),
+          "assert" => self.process_assert(&command_parts),
+          "set" => self.process_set(&command_parts),
+          "history" => self.process_history(&command_parts),
           _ => {
-            println!("Invalid command: {}.", key);
+            textln!(output, "Invalid command: {}.", key);
+            Err(NahError::invalid_command(key))
           }
         };
-        true
+        self.finish_command(key, result);
       }
     }
   }
 
-  fn process_use(&mut self, command_parts: &Vec<&str>) {
+  /**
+   * Record the result of a command: a result is kept as `$last`, and a JSON
+   * document is printed in JSON output mode.
+   */
+  fn finish_command(&mut self, command: &str, result: Result<Value, NahError>) {
+    if self.output == OutputMode::Json {
+      println!("{}", output::json_document(command, &result));
+    }
+    match result {
+      Ok(Value::Null) => {}
+      Ok(v) => {
+        self.variables.insert("last".to_string(), v);
+      }
+      Err(_) => self.command_failed = true,
+    }
+  }
+
+  fn process_help(&mut self) -> Result<Value, NahError> {
+    if self.output == OutputMode::Text {
+      print_help();
+    }
+    Ok(Value::Array(
+      shell_helper::COMMANDS
+        .iter()
+        .map(|(name, usage)| serde_json::json!({"name": name, "usage": usage}))
+        .collect(),
+    ))
+  }
+
+  fn process_use(&mut self, command_parts: &Vec<&str>) -> Result<Value, NahError> {
+    let output = self.output;
     if command_parts.len() != 2 {
-      println!(
+      return Err(output::usage_error(
+        output,
         "Usage of use:\n\
     >> use [server_name] \n\
-MCP server of `server_name` will be used as the current server."
-      );
-      return;
+MCP server of `server_name` will be used as the current server.",
+      ));
     }
     let server_name = command_parts[1];
+    let configs = self.server_configs();
+    let (configured, disabled) = (
+      configs.contains(server_name),
+      configs.is_disabled(server_name),
+    );
+    if configured && !self.server_processes.contains_key(server_name) {
+      if disabled {
+        textln!(
+          output,
+          "Server {} is disabled in the config file.",
+          server_name
+        );
+        return Err(NahError::invalid_value(
+          &format!("Server {} is disabled", server_name),
+          None,
+        ));
+      }
+      self.start_lazy_servers(&[server_name.to_string()]);
+      if !self.server_processes.contains_key(server_name) {
+        return Err(NahError::invalid_value(
+          &format!("Failed to start server {}", server_name),
+          None,
+        ));
+      }
+    }
     if self.server_processes.contains_key(server_name) {
       self.current_server = Some(server_name.to_owned());
+      Ok(Value::Null)
     } else {
-      println!("Server {} not found. Available servers are:", server_name);
-      for item in self.server_processes.keys() {
-        println!("* {}", item);
+      textln!(
+        output,
+        "Server {} not found. Available servers are:",
+        server_name
+      );
+      for item in self.available_servers() {
+        textln!(output, "* {}", item);
       }
+      Err(NahError::invalid_value(
+        &format!("Server {} not found", server_name),
+        None,
+      ))
     }
   }
 
-  fn process_exit(&mut self) {
-    println!("Terminate MCP servers..");
+  fn server_configs(&self) -> ServerConfigs<'_> {
+    ServerConfigs {
+      local: &self.server_commands,
+      remote: &self.remote_server_configs,
+    }
+  }
+
+  /**
+   * Names of started servers and servers that can be started, sorted.
+   */
+  fn available_servers(&self) -> Vec<String> {
+    let configs = self.server_configs();
+    configs
+      .names()
+      .into_iter()
+      .filter(|name| !configs.is_disabled(name))
+      .collect()
+  }
+
+  /**
+   * Start servers which were not started with nah.
+   */
+  fn start_lazy_servers(&mut self, server_names: &[String]) {
+    let servers = startup::start_servers(
+      self.server_configs(),
+      server_names,
+      &self.history_path,
+      self.output,
+    );
+    self.server_processes.extend(servers);
+  }
+
+  fn process_exit(&mut self) -> ! {
+    self.stop_servers();
+    std::process::exit(0);
+  }
+
+  fn stop_servers(&mut self) {
+    logln!(self.output, "Terminate MCP servers..");
+    let output = self.output;
     self.server_processes.iter_mut().for_each(|(name, server)| {
       if server.kill().is_err() {
-        println!("Failed to terminate server: {}", name);
+        logln!(output, "Failed to terminate server: {}", name);
       }
     });
-    std::process::exit(0);
   }
 
-  fn process_list_servers(&mut self) {
-    self.server_processes.iter().for_each(|(name, _)| {
-      println!("* {}", name);
-    });
+  fn process_list_servers(&mut self) -> Result<Value, NahError> {
+    let configs = self.server_configs();
+    let mut result = Vec::new();
+    for name in configs.names() {
+      let mut item = match self.server_processes.get(&name) {
+        Some(server) => {
+          let info = server.connection_info();
+          textln!(
+            self.output,
+            "* {:<20} {:<11} {} ({}), up {}, {} request(s), {} failed",
+            name,
+            "running",
+            info.server_description(),
+            info.endpoint(),
+            mcp::format_uptime(info.uptime_secs),
+            info.requests,
+            info.errors
+          );
+          let mut item = serde_json::to_value(info).unwrap();
+          item["status"] = Value::from("running");
+          item
+        }
+        None => {
+          let status = if configs.is_disabled(&name) {
+            "disabled"
+          } else if configs.is_lazy(&name) {
+            "lazy"
+          } else {
+            "not started"
+          };
+          textln!(self.output, "* {:<20} {}", name, status);
+          serde_json::json!({"status": status})
+        }
+      };
+      item["name"] = Value::from(name);
+      result.push(item);
+    }
+    Ok(Value::Array(result))
   }
 
-  fn process_restart_server(&mut self, command_parts: &Vec<&str>) {
+  fn process_server_info(&mut self, command_parts: &[&str]) -> Result<Value, NahError> {
+    let output = self.output;
+    let help_message = "Usage of server_info:\n\
+    >> server_info [server_name] \n\
+Show the connection to MCP server of `server_name`. If no `server_name` is provided, current server will be shown.";
+    if command_parts.len() > 2 {
+      return Err(output::usage_error(output, help_message));
+    }
+    let server_name = match command_parts.get(1) {
+      Some(s) => s.to_string(),
+      None => match &self.current_server {
+        Some(s) => s.clone(),
+        None => {
+          textln!(output, "No current server is selected!");
+          return Err(output::usage_error(output, help_message));
+        }
+      },
+    };
+    let Some(server) = self.server_processes.get(&server_name) else {
+      textln!(output, "Server {} is not running.", server_name);
+      return Err(NahError::invalid_value(
+        &format!("Server {} is not running", server_name),
+        None,
+      ));
+    };
+    let info = server.connection_info();
+    textln!(output, "Server:           {}", server_name);
+    textln!(output, "Server info:      {}", info.server_description());
+    textln!(
+      output,
+      "Protocol version: {}",
+      info.protocol_version().unwrap_or("unknown")
+    );
+    textln!(output, "Transport:        {}", info.endpoint());
+    if let Some(session_id) = &info.session_id {
+      textln!(output, "Session id:       {}", session_id);
+    }
+    textln!(
+      output,
+      "Uptime:           {}",
+      mcp::format_uptime(info.uptime_secs)
+    );
+    textln!(
+      output,
+      "Requests:         {} ({} failed)",
+      info.requests,
+      info.errors
+    );
+    textln!(output, "Capabilities:     {}", info.capabilities_summary());
+    if let Some(instructions) = info.instructions() {
+      textln!(output, "Instructions:");
+      for line in instructions.lines() {
+        textln!(output, "  {}", line);
+      }
+    }
+    let mut result = serde_json::to_value(info).unwrap();
+    result["name"] = Value::from(server_name);
+    Ok(result)
+  }
+
+  fn process_restart_server(&mut self, command_parts: &Vec<&str>) -> Result<Value, NahError> {
+    let output = self.output;
     let help_message = "Usage of use:\n\
     >> restart_server [server_name] \n\
 MCP server of `server_name` will be restarted. If no `server_name` is provided, current server will be restarted.";
     if command_parts.len() > 2 {
-      println!("{}", help_message);
-      return;
+      return Err(output::usage_error(output, help_message));
     }
     let server_name = match command_parts.get(1) {
       Some(s) => *s,
       None => match &self.current_server {
         Some(s) => s,
         None => {
-          println!("No current server is selected!");
-          println!("{}", help_message);
-          return;
+          textln!(output, "No current server is selected!");
+          return Err(output::usage_error(output, help_message));
         }
       },
     };
 
     let command = match self.server_commands.get(server_name) {
+      Some(p) if p.disabled => {
+        textln!(output, "MCP Server {} is disabled!", server_name);
+        return Err(NahError::invalid_value(
+          &format!("MCP Server {} is disabled", server_name),
+          None,
+        ));
+      }
       Some(p) => p,
       None => {
-        println!("MCP Server {} not found!", server_name);
-        return;
+        textln!(output, "MCP Server {} not found!", server_name);
+        return Err(NahError::invalid_value(
+          &format!("MCP Server {} not found", server_name),
+          None,
+        ));
       }
     };
 
     let process =
       match MCPLocalServerProcess::start_and_init(server_name, command, &self.history_path) {
         Err(e) => {
-          println!(
+          textln!(
+            output,
             "Fatal error while launching {}, give up this server.",
             server_name
           );
-          println!("Error: {}", e);
-          return;
+          textln!(output, "Error: {}", e);
+          return Err(e);
         }
         Ok(p) => p,
       };
@@ -276,272 +737,345 @@ MCP server of `server_name` will be restarted. If no `server_name` is provided,
       .insert(server_name.to_owned(), Box::new(process));
 
     let _ = old_process.is_some_and(|mut p| p.kill().is_ok());
+    Ok(Value::Null)
   }
 
-  fn process_list_tools(&mut self) {
+  fn process_list_tools(&mut self) -> Result<Value, NahError> {
+    let output = self.output;
     self.process_with_current_server(|_server_name, server_process| {
       let tools = match server_process.fetch_tools() {
         Ok(t) => t,
         Err(e) => {
-          println!("Failed to fetch tool list: {}", e);
-          return;
+          textln!(output, "Failed to fetch tool list: {}", e);
+          return Err(e);
         }
       };
 
       for item in tools.iter() {
-        println!(" * {}", item.name);
+        textln!(output, " * {}", item.name);
       }
-    });
+      Ok(serde_json::to_value(&tools).unwrap())
+    })
   }
 
-  fn process_inspect_tool(&mut self, command_parts: &Vec<&str>) {
+  fn process_inspect_tool(&mut self, command_parts: &Vec<&str>) -> Result<Value, NahError> {
+    let output = self.output;
     if command_parts.len() != 2 {
-      println!("Usage: inspect_tool [tool name]");
-      return;
+      return Err(output::usage_error(
+        output,
+        "Usage: inspect_tool [tool name]",
+      ));
     }
     let tool_name = command_parts[1];
     self.process_with_current_server(|server_name, server_process| {
       let tool_def = server_process.get_tool_definition(tool_name);
       match tool_def {
         Ok(def) => {
-          println!("{}", def.name);
+          textln!(output, "{}", def.name);
           def.description.as_ref().and_then(|desc| {
-            println!("= Description =");
-            println!("{desc}");
-            println!("======");
+            textln!(output, "= Description =");
+            textln!(output, "{desc}");
+            textln!(output, "======");
             Some(())
           });
           def.annotations.as_ref().and_then(|annotations| {
-            println!("= Annotations =");
+            textln!(output, "= Annotations =");
             annotations.title.as_ref().and_then(|title| {
-              println!("Title: {}", title);
+              textln!(output, "Title: {}", title);
               Some(())
             });
             annotations.read_only_hint.as_ref().and_then(|h| {
-              println!("Read only hint: {}", h);
+              textln!(output, "Read only hint: {}", h);
               Some(())
             });
             annotations.destructive_hint.as_ref().and_then(|h| {
-              println!("Destructive hint: {}", h);
+              textln!(output, "Destructive hint: {}", h);
               Some(())
             });
             annotations.idempotent_hint.as_ref().and_then(|h| {
-              println!("Idempotent hint: {}", h);
+              textln!(output, "Idempotent hint: {}", h);
               Some(())
             });
             annotations.open_world_hint.as_ref().and_then(|h| {
-              println!("Open world hint: {}", h);
+              textln!(output, "Open world hint: {}", h);
               Some(())
             });
-            println!("=====");
+            textln!(output, "=====");
             Some(())
           });
+          if let Some(schema) = &def.output_schema {
+            textln!(output, "= Output Schema =");
+            textln!(output, "{}", serde_json::to_string_pretty(schema).unwrap());
+            textln!(output, "=====");
+          }
+          Ok(serde_json::to_value(def).unwrap())
         }
         Err(e) => {
-          println!(
+          textln!(
+            output,
             "Failed to load tool {} from server {} due to error: {}",
-            tool_name, server_name, e
+            tool_name,
+            server_name,
+            e
           );
+          Err(e)
         }
       }
-    });
+    })
   }
 
-  fn process_call_tool(&mut self, command_parts: &Vec<&str>) {
-    if command_parts.len() != 2 {
-      println!("Usage: call_tool [tool name]");
-      return;
+  fn process_call_tool(&mut self, command_parts: &Vec<&str>) -> Result<Value, NahError> {
+    let output = self.output;
+    let (command_parts, input_mode) = self.parse_argument_input_mode(command_parts);
+    if command_parts.len() < 2 {
+      return Err(output::usage_error(
+        output,
+        "Usage: call_tool [tool name] [JSON | @file.json | key=value ...] [--form | --editor]",
+      ));
     }
     let tool_name = command_parts[1];
+    let inline_arguments = &command_parts[2..];
     self.process_with_current_server(|server_name, server_process| {
-      let tool_def = server_process.get_tool_definition(tool_name);
-      match tool_def {
-        Ok(def) => {
-          if def.is_destructive() {
-            if ! utils::ask_for_user_confirmation(
-            &format!("Tool {} is annotated as destructive. Do you still want to call? [N/y] > ", def.name),
-              &format!("Tool {} has not been called.", def.name),
-            ) {
-              return
-            }
+      let def = match server_process.get_tool_definition(tool_name) {
+        Ok(def) => def,
+        Err(e) => {
+          textln!(
+            output,
+            "Failed to load tool {} from server {} due to error: {}",
+            tool_name,
+            server_name,
+            e
+          );
+          return Err(e);
+        }
+      };
+      if def.is_destructive()
+        && !utils::ask_for_user_confirmation(
+          &format!(
+            "Tool {} is annotated as destructive. Do you still want to call? [N/y] > ",
+            def.name
+          ),
+          &format!("Tool {} has not been called.", def.name),
+        )
+      {
+        return Err(NahError::user_cancel_request());
+      }
+      let output_schema = def.output_schema.clone();
+      let arguments = if !inline_arguments.is_empty() {
+        match command::parse_inline_tool_arguments(inline_arguments, &def.input_schema) {
+          Ok(v) => v,
+          Err(e) => {
+            textln!(output, "{}", e);
+            return Err(e);
           }
-          // create a temporary file for the request
-          let param_template = json_schema::create_instance_template(&def.input_schema);
-          match param_template {
-            Ok(template) => {
-              let temp_filename = format!(".nah_req.{}.args.js", tool_name);
-              match File::create(&temp_filename).and_then(|file: File| {
-                          let mut file = file;
-                          file.write_all("// Please fill arguments for tool call here in JSON format. \n// Lines starts with '//' will be removed\n".as_bytes())?;
-                          file.write_all(template.as_bytes())?;
-                          file.flush()?;
-                          Ok(())
-                      }) {
-                          Err(e) => println!("Failed to prepare file for argument template for tool calling {} > {} due to error: {}", server_name, tool_name, e),
-                          Ok(()) => {
-                            if !json_schema::is_empty_object(&def.input_schema.as_object().and_then(|obj| obj.get("properties")).unwrap()) {
-                              // load parameter and call tool
-                              let launch_editor_outcome = launch_editor(&temp_filename);
-                              if launch_editor_outcome.is_err() {
-                                  println!("{}", launch_editor_outcome.unwrap_err());
-                                  return;
-                              }
-                            } else {
-                              println!("No argument is requested. Directly call the function...");
-                            }
-                            let arguments= match load_json_arguments(&temp_filename) {
-                              Err(e) => {
-                                println!("{}", e);
-                                return;
-                              },
-                              Ok(v) => v
-                            };
-                            let result = server_process.call_tool(tool_name, &arguments);
-                            match result {
-                              Err(e) => {
-                                println!("Received error: {}", e);
-                              }
-                              Ok(result) => {
-                                println!("Result: \n{}\n", serde_json::to_string_pretty(&result).unwrap());
-                              }
-                            }
-                            if std::fs::remove_file(&temp_filename).is_err() {
-                                println!("Failed to clean up the temporary argument file.")
-                            }
-                          }
-                      }
-            }
-            Err(e) => {
-              println!(
-                "Failed to prepare argment template for tool calling: {} > {} due to error: {}",
-                server_name, tool_name, e
-              );
-            }
+        }
+      } else if json_schema::has_no_properties(&def.input_schema) {
+        textln!(
+          output,
+          "No argument is requested. Directly call the function..."
+        );
+        Value::Object(serde_json::Map::new())
+      } else {
+        let arguments = match input_mode {
+          ArgumentInputMode::Form => form::fill_tool_arguments(&def.input_schema),
+          ArgumentInputMode::Editor => edit_tool_arguments(tool_name, &def.input_schema),
+        };
+        match arguments {
+          Ok(v) => v,
+          Err(e) => {
+            textln!(
+              output,
+              "Failed to prepare arguments for tool calling: {} > {} due to error: {}",
+              server_name,
+              tool_name,
+              e
+            );
+            return Err(e);
           }
         }
+      };
+      if let Err(e) = json_schema::validate_arguments(def, &arguments) {
+        logln!(output, "{}", e);
+        if !utils::ask_for_user_confirmation(
+          "Do you still want to call the tool with these arguments? [N/y] > ",
+          &format!("Tool {} has not been called.", tool_name),
+        ) {
+          return Err(e);
+        }
+      }
+      let result = server_process.call_tool(tool_name, &arguments);
+      match result {
         Err(e) => {
-          println!(
-            "Failed to load tool {} from server {} due to error: {}",
-            tool_name, server_name, e
+          textln!(output, "Received error: {}", e);
+          Err(e)
+        }
+        Ok(result) => {
+          textln!(
+            output,
+            "Result: \n{}\n",
+            serde_json::to_string_pretty(&result).unwrap()
           );
+          if let Some(schema) = &output_schema {
+            let problems = json_schema::check_structured_content(schema, &result);
+            if !problems.is_empty() {
+              logln!(
+                output,
+                "Result of tool {} does not match its outputSchema:",
+                tool_name
+              );
+              for problem in problems.iter() {
+                logln!(output, "  * {}", problem);
+              }
+            }
+          }
+          Ok(result)
         }
       }
-    });
+    })
   }
 
-  fn process_list_resources(&mut self) {
+  fn process_list_resources(&mut self) -> Result<Value, NahError> {
+    let output = self.output;
     self.process_with_current_server(|_, server_process| {
-      println!("Direct resources");
-      match server_process.fetch_resources_list() {
+      textln!(output, "Direct resources");
+      let resources = match server_process.fetch_resources_list() {
         Ok(r) => {
           for item in r.iter() {
-            println!(" * {}", item.uri.as_ref().unwrap());
+            textln!(output, " * {}", item.uri.as_ref().unwrap());
           }
+          Ok(serde_json::to_value(&r).unwrap())
         }
         Err(e) => {
-          println!("Failed to load resource list: {}", e);
+          textln!(output, "Failed to load resource list: {}", e);
+          Err(e)
         }
       };
 
-      println!("Resource templates");
-      match server_process.fetch_resource_templates_list() {
+      textln!(output, "Resource templates");
+      let templates = match server_process.fetch_resource_templates_list() {
         Ok(r) => {
           for item in r.iter() {
-            println!(" * {}", item.uri_template.as_ref().unwrap());
+            textln!(output, " * {}", item.uri_template.as_ref().unwrap());
           }
+          Ok(serde_json::to_value(&r).unwrap())
         }
         Err(e) => {
-          println!("Failed to load resource templates: {}", e);
+          textln!(output, "Failed to load resource templates: {}", e);
+          Err(e)
         }
       };
-    });
+      Ok(serde_json::json!({
+        "resources": resources?,
+        "resourceTemplates": templates?,
+      }))
+    })
   }
 
-  fn process_inspect_resources(&mut self, command_parts: &Vec<&str>) {
+  fn process_inspect_resources(&mut self, command_parts: &Vec<&str>) -> Result<Value, NahError> {
+    let output = self.output;
     if command_parts.len() != 2 {
-      println!("Usage: read_resource [Resource URI]");
-      return;
+      return Err(output::usage_error(
+        output,
+        "Usage: read_resource [Resource URI]",
+      ));
     }
     let uri = command_parts[1];
     self.process_with_current_server(|_, server_process| {
       match server_process.get_resources_definition(uri) {
         Ok(r) => {
-          println!("Name: {}", r.name);
-          println!("URI: {}", uri);
+          textln!(output, "Name: {}", r.name);
+          textln!(output, "URI: {}", uri);
           let _ = r.size.as_ref().is_some_and(|v| {
-            println!("Size: {}", v);
+            textln!(output, "Size: {}", v);
             true
           });
           let _ = r.mime_type.as_ref().is_some_and(|v| {
-            println!("MIME Type: {}", v);
+            textln!(output, "MIME Type: {}", v);
             true
           });
           let _ = r.description.as_ref().is_some_and(|v| {
-            println!("======");
-            println!("{}", v);
-            println!("======");
+            textln!(output, "======");
+            textln!(output, "{}", v);
+            textln!(output, "======");
             true
           });
+          Ok(serde_json::to_value(r).unwrap())
         }
         Err(e) => {
-          println!("Error: {}", e)
+          textln!(output, "Error: {}", e);
+          Err(e)
         }
       }
-    });
+    })
   }
 
-  fn process_read_resources(&mut self, command_parts: &Vec<&str>) {
+  fn process_read_resources(&mut self, command_parts: &Vec<&str>) -> Result<Value, NahError> {
+    let output = self.output;
     if command_parts.len() != 2 {
-      println!("Usage: read_resource [Resource URI]");
-      return;
+      return Err(output::usage_error(
+        output,
+        "Usage: read_resource [Resource URI]",
+      ));
     }
     let uri = command_parts[1];
-    self.process_with_current_server(|_, server_process| {
-      match server_process.read_resources(uri) {
+    self.process_with_current_server(
+      |_, server_process| match server_process.read_resources(uri) {
         Ok(r) => {
-          println!("Result: \n{}\n", serde_json::to_string_pretty(&r).unwrap());
+          textln!(
+            output,
+            "Result: \n{}\n",
+            serde_json::to_string_pretty(&r).unwrap()
+          );
+          Ok(serde_json::to_value(&r).unwrap())
         }
         Err(e) => {
-          println!("Received error: {}", e);
+          textln!(output, "Received error: {}", e);
+          Err(e)
         }
-      }
-    });
+      },
+    )
   }
 
-  fn process_list_prompts(&mut self) {
+  fn process_list_prompts(&mut self) -> Result<Value, NahError> {
+    let output = self.output;
     self.process_with_current_server(|_, server_process| {
       match server_process.fetch_prompts_list() {
         Ok(r) => {
           for item in r.iter() {
-            println!("* {}", item.name);
+            textln!(output, "* {}", item.name);
           }
+          Ok(serde_json::to_value(&r).unwrap())
         }
         Err(e) => {
-          println!("Failed to load prompt list: {}", e);
+          textln!(output, "Failed to load prompt list: {}", e);
+          Err(e)
         }
       }
-    });
+    })
   }
 
-  fn process_inspect_prompt(&mut self, command_parts: &Vec<&str>) {
+  fn process_inspect_prompt(&mut self, command_parts: &Vec<&str>) -> Result<Value, NahError> {
+    let output = self.output;
     if command_parts.len() != 2 {
-      println!("Usage: inspect_prompt [Prompt name]");
-      return;
+      return Err(output::usage_error(
+        output,
+        "Usage: inspect_prompt [Prompt name]",
+      ));
     }
     let prompt_name = command_parts[1];
     self.process_with_current_server(|_, server_process| {
       match server_process.get_prompt_definition(prompt_name) {
         Ok(p) => {
-          println!("Name: {}", p.name);
+          textln!(output, "Name: {}", p.name);
           let _ = p.description.as_ref().is_some_and(|desc| {
-            println!("Description:\n  {}", desc);
+            textln!(output, "Description:\n  {}", desc);
             true
           });
           let _ = p.arguments.as_ref().is_some_and(|args| {
             if args.len() == 0 {
               false
             } else {
-              println!("Args:");
+              textln!(output, "Args:");
               for arg in args.iter() {
                 let mut desc = String::new();
                 if arg.required.is_some_and(|v| v) {
@@ -551,149 +1085,488 @@ MCP server of `server_name` will be restarted. If no `server_name` is provided,
                   desc.push_str(v);
                   true
                 });
-                println!("  {}: {}", arg.name, desc);
+                textln!(output, "  {}: {}", arg.name, desc);
               }
               true
             }
           });
+          Ok(serde_json::to_value(p).unwrap())
         }
         Err(e) => {
-          println!("Failed to load prompt {}: {}", prompt_name, e);
+          textln!(output, "Failed to load prompt {}: {}", prompt_name, e);
+          Err(e)
         }
       }
-    });
+    })
   }
 
-  fn process_get_prompt(&mut self, command_parts: &Vec<&str>) {
-    if command_parts.len() != 2 {
-      println!("Usage: get_prompt [Prompt name]");
-      return;
+  fn process_get_prompt(&mut self, command_parts: &Vec<&str>) -> Result<Value, NahError> {
+    let output = self.output;
+    let (command_parts, input_mode) = self.parse_argument_input_mode(command_parts);
+    if command_parts.len() < 2 {
+      return Err(output::usage_error(
+        output,
+        "Usage: get_prompt [Prompt name] [JSON | @file.json | key=value ...] [--form | --editor]",
+      ));
     }
-    let prompt_name = drop_quotes(command_parts[1]);
+    let prompt_name = command_parts[1];
+    let inline_arguments = &command_parts[2..];
     self.process_with_current_server(|_, server_process| {
-      let prompt_def = server_process.get_prompt_definition(&prompt_name);
-      match prompt_def {
-        Ok(def) => {
-          match &def.arguments {
-            Some(args) => {
-              if args.len() > 0 {
-                let temp_filename = format!(".nah_req.{}.args.js", prompt_name);
-                let template_lines: Vec<String> = args.iter().map(|arg| format!("    \"{}\": \"<FILL ARGUMENT HERE>\"", arg.name)).collect();
-                let write_template_result = File::create(&temp_filename).and_then(|file| {
-                  let mut file = file;
-                  file.write_all(b"// Please fill arguments for prompt call here in JSON format \n// Lines starts with '//' will be removed\n")?;
-                  file.write_all(b"{\n")?;
-                  for (idx, line) in template_lines.iter().enumerate() {
-                    file.write_all(line.as_bytes())?;
-                    if idx + 1 != template_lines.len() {
-                      file.write_all(b",\n")?;
-                    } else {
-                      file.write_all(b"\n")?;
-                    }
-                  }
-                  file.write_all(b"}\n")?;
-                  Ok(())
-                });
-                if write_template_result.is_err() {
-                  println!("Failed to prepare the argument template file for getting prompt {} due to error {}", prompt_name, write_template_result.err().unwrap());
-                  return;
-                }
-                // load parameter and call tool
-                let launch_editor_outcome = launch_editor(&temp_filename);
-                if launch_editor_outcome.is_err() {
-                  println!("{}", launch_editor_outcome.unwrap_err());
-                  return;
-                }
-                let argument_value = load_json_arguments(&temp_filename);
-                let arguments= match argument_value.and_then(|v| match v.as_object() {
-                  Some(v) => Ok(v.clone()),
-                  None => Err(NahError::invalid_argument_error("Arguments should be a JSON Object!", None))
-                }) {
-                  Err(e) => {
-                    println!("{}", e);
-                    return;
-                  },
-                  Ok(v) => v
-                };
-                let args_map = arguments.iter().map(|(k, v)| {
-                  (k.to_owned(), v.as_str().unwrap_or("").to_owned())
-                }).collect();
-                let result = server_process.get_prompt_content(&prompt_name, &args_map);
-                match result {
-                  Err(e) => {
-                    println!("Received error: {}", e);
-                  }
-                  Ok(result) => {
-                    println!("Result: \n{}\n", serde_json::to_string_pretty(&result).unwrap());
-                  }
-                };
-                if std::fs::remove_file(&temp_filename).is_err() {
-                  println!("Failed to clean up the temporary argument file.")
-                }
-              } else {
-                println!("Prompt {} doesn't need arguments.", prompt_name);
-              }
-            },
-            None => {
-              println!("Prompt {} doesn't need arguments.", prompt_name);
+      let def = match server_process.get_prompt_definition(prompt_name) {
+        Ok(def) => def,
+        Err(e) => {
+          textln!(
+            output,
+            "Failed to prepare argument template for getting prompt {} due to error: {}",
+            prompt_name,
+            e
+          );
+          return Err(e);
+        }
+      };
+      let args_map = match &def.arguments {
+        _ if !inline_arguments.is_empty() => {
+          match command::parse_inline_prompt_arguments(inline_arguments) {
+            Ok(v) => v,
+            Err(e) => {
+              textln!(output, "{}", e);
+              return Err(e);
             }
+          }
+        }
+        Some(args) if !args.is_empty() => {
+          let args_map = match input_mode {
+            ArgumentInputMode::Form => form::fill_prompt_arguments(args),
+            ArgumentInputMode::Editor => edit_prompt_arguments(prompt_name, args),
           };
-
+          match args_map {
+            Ok(v) => v,
+            Err(e) => {
+              textln!(output, "{}", e);
+              return Err(e);
+            }
+          }
+        }
+        _ => {
+          textln!(output, "Prompt {} doesn't need arguments.", prompt_name);
+          HashMap::new()
         }
+      };
+      let result = server_process.get_prompt_content(prompt_name, &args_map);
+      match result {
         Err(e) => {
-          println!("Failed to prepare argument template for getting prompt {} due to error: {}", prompt_name, e);
+          textln!(output, "Received error: {}", e);
+          Err(e)
+        }
+        Ok(result) => {
+          textln!(
+            output,
+            "Result: \n{}\n",
+            serde_json::to_string_pretty(&result).unwrap()
+          );
+          Ok(serde_json::to_value(&result).unwrap())
         }
       }
-    });
+    })
   }
 
-  fn process_set_timeout(&mut self, command_parts: &Vec<&str>) {
+  fn process_set_timeout(&mut self, command_parts: &Vec<&str>) -> Result<Value, NahError> {
+    let output = self.output;
     if command_parts.len() != 2 {
-      println!("Usage: set_timeout [timeout in milliseconds]");
-      return;
+      return Err(output::usage_error(
+        output,
+        "Usage: set_timeout [timeout in milliseconds]",
+      ));
     }
     let timeout_ms: u64 = match command_parts[1].parse() {
       Ok(t) => t,
-      Err(_) => {
-        println!("Timeout value must be an non-negative integer!");
-        return;
+      Err(e) => {
+        textln!(output, "Timeout value must be an non-negative integer!");
+        return Err(NahError::invalid_value(
+          "Timeout value must be an non-negative integer",
+          Some(Box::new(e)),
+        ));
       }
     };
 
     self.process_with_current_server(|server_name, server_process| {
       server_process.set_timeout(timeout_ms);
-      println!(
+      textln!(
+        output,
         "Timeout for MCP server {} has been set to {}ms",
-        server_name, timeout_ms
+        server_name,
+        timeout_ms
       );
-    });
+      Ok(Value::Null)
+    })
+  }
+
+  fn process_source(&mut self, command_parts: &[&str]) -> Result<Value, NahError> {
+    if command_parts.len() != 2 {
+      return Err(output::usage_error(
+        self.output,
+        "Usage: source [script file]",
+      ));
+    }
+    let result = self.run_script(Path::new(command_parts[1]));
+    if let Err(e) = &result {
+      textln!(self.output, "{}", e);
+    }
+    result.map(|_| Value::Null)
+  }
+
+  fn process_let(&mut self, command_parts: &[&str]) -> Result<Value, NahError> {
+    match command_parts {
+      [_, name, "=", value] if !name.starts_with('$') => {
+        self
+          .variables
+          .insert(name.to_string(), script::parse_value(value));
+        Ok(Value::Null)
+      }
+      _ => Err(output::usage_error(
+        self.output,
+        "Usage: let [name] = [value], e.g. `let text = $last.content[0].text`",
+      )),
+    }
+  }
+
+  fn process_assert(&mut self, command_parts: &[&str]) -> Result<Value, NahError> {
+    match script::check_assertion(&command_parts[1..]) {
+      Ok(()) => Ok(Value::Null),
+      Err(message) => {
+        textln!(self.output, "{}", message);
+        Err(NahError::assertion_failed(&message))
+      }
+    }
+  }
+
+  fn process_set(&mut self, command_parts: &[&str]) -> Result<Value, NahError> {
+    match command_parts {
+      [_, "on_error", "stop"] => self.on_error = OnError::Stop,
+      [_, "on_error", "continue"] => self.on_error = OnError::Continue,
+      [_, "output", "text"] => self.output = OutputMode::Text,
+      [_, "output", "json"] => self.output = OutputMode::Json,
+      _ => {
+        return Err(output::usage_error(
+          self.output,
+          "Usage: set on_error [stop | continue]\n       set output [text | json]",
+        ))
+      }
+    }
+    Ok(Value::Null)
+  }
+
+  /**
+   * Run all commands in a script file. Depending on `on_error`, it either
+   * stops at the first failed command or runs all commands, and fails if any
+   * command failed.
+   */
+  fn run_script(&mut self, path: &Path) -> Result<(), NahError> {
+    if self.script_depth >= MAX_SCRIPT_DEPTH {
+      return Err(NahError::invalid_value(
+        &format!("Scripts are nested too deeply at {}", path.display()),
+        None,
+      ));
+    }
+    let commands = script::read_script(path)?;
+    self.script_depth += 1;
+    let mut failed_lines = Vec::new();
+    for (line_no, command) in commands.iter() {
+      textln!(self.output, ">> {}", command);
+      self.process_command(command);
+      if self.command_failed {
+        logln!(
+          self.output,
+          "{}:{}: command failed",
+          path.display(),
+          line_no
+        );
+        failed_lines.push(*line_no);
+        if self.on_error == OnError::Stop {
+          break;
+        }
+      }
+    }
+    self.script_depth -= 1;
+    if failed_lines.is_empty() {
+      Ok(())
+    } else {
+      Err(NahError::script_failed(
+        &path.display().to_string(),
+        &failed_lines,
+      ))
+    }
+  }
+
+  /**
+   * Collect names for completion. Tools, prompts and resources come from the
+   * caches of the current server, so they are available once listed.
+   */
+  fn completion_candidates(&self) -> CompletionCandidates {
+    let mut candidates = CompletionCandidates {
+      servers: self.available_servers(),
+      models: self.models.keys().cloned().collect(),
+      ..Default::default()
+    };
+    if let Some(server) = self
+      .current_server
+      .as_ref()
+      .and_then(|name| self.server_processes.get(name))
+    {
+      candidates.tools = server._get_tool_map().keys().cloned().collect();
+      candidates.prompts = server._get_prompt_map().keys().cloned().collect();
+      candidates.resource_uris = server
+        ._get_resource_map()
+        .values()
+        .filter_map(|r| r.uri.clone())
+        .collect();
+    }
+    candidates.servers.sort();
+    candidates.models.sort();
+    candidates.tools.sort();
+    candidates.prompts.sort();
+    candidates.resource_uris.sort();
+    candidates
+  }
+
+  /**
+   * Remove `--form` or `--editor` from the command parts and return the
+   * argument input mode it selects, falling back to the configured mode.
+   */
+  fn parse_argument_input_mode<'a>(
+    &self,
+    command_parts: &[&'a str],
+  ) -> (Vec<&'a str>, ArgumentInputMode) {
+    let mut mode = self.argument_input;
+    let mut rest = Vec::new();
+    for part in command_parts.iter() {
+      match *part {
+        "--form" => mode = ArgumentInputMode::Form,
+        "--editor" => mode = ArgumentInputMode::Editor,
+        _ => rest.push(*part),
+      }
+    }
+    (rest, mode)
+  }
+
+  fn process_history(&mut self, command_parts: &[&str]) -> Result<Value, NahError> {
+    let usage = "Usage: history list [count] [--server name] [--method name] [--errors]\n       \
+history show [n]\n       history resend [n]";
+    let subcommand = match command_parts.get(1) {
+      Some(&s @ ("list" | "show" | "resend")) => s,
+      _ => return Err(output::usage_error(self.output, usage)),
+    };
+    let entries = match history::load_session(&self.history_path) {
+      Ok(e) => e,
+      Err(e) => {
+        textln!(self.output, "Failed to load the history: {}", e);
+        return Err(e);
+      }
+    };
+    if subcommand == "list" {
+      return self.process_history_list(&entries, &command_parts[2..], usage);
+    }
+    let entry = match command_parts {
+      [_, _, n] => match n.parse::<usize>() {
+        Ok(idx) if idx >= 1 && idx <= entries.len() => &entries[idx - 1],
+        _ => {
+          textln!(self.output, "Message {} not found.", n);
+          return Err(NahError::invalid_value(
+            &format!("message {} not found in the history", n),
+            None,
+          ));
+        }
+      },
+      _ => return Err(output::usage_error(self.output, usage)),
+    };
+    if subcommand == "show" {
+      textln!(
+        self.output,
+        "{}",
+        serde_json::to_string_pretty(&entry.envelope).unwrap()
+      );
+      return Ok(serde_json::to_value(&entry.envelope).unwrap());
+    }
+    if !entry.is_request() || entry.envelope.direction != Direction::ClientToServer {
+      textln!(self.output, "Only requests sent to servers can be resent.");
+      return Err(NahError::invalid_value(
+        "only requests sent to servers can be resent",
+        None,
+      ));
+    }
+    let mut message = entry.envelope.message.clone();
+    message["id"] = Value::String(uuid::Uuid::new_v4().to_string());
+    let request: MCPRequest = match serde_json::from_value(message) {
+      Ok(r) => r,
+      Err(e) => {
+        return Err(NahError::invalid_value(
+          "invalid request in the history",
+          Some(Box::new(e)),
+        ))
+      }
+    };
+    let output = self.output;
+    self.process_with_current_server(|_server_name, server_process| {
+      let response = server_process.send_and_wait_for_response(request)?;
+      match response.result {
+        Some(result) => {
+          textln!(output, "{}", serde_json::to_string_pretty(&result).unwrap());
+          Ok(result)
+        }
+        None => {
+          let e = server_process.parse_response_error(&response);
+          textln!(output, "{}", e);
+          Err(e)
+        }
+      }
+    })
+  }
+
+  fn process_history_list(
+    &mut self,
+    entries: &[HistoryEntry],
+    args: &[&str],
+    usage: &str,
+  ) -> Result<Value, NahError> {
+    let mut count = 20;
+    let mut filter = HistoryFilter::default();
+    let mut idx = 0;
+    while idx < args.len() {
+      match (args[idx], args.get(idx + 1)) {
+        ("--server", Some(server)) => {
+          filter.server = Some(server.to_string());
+          idx += 1;
+        }
+        ("--method", Some(method)) => {
+          filter.method = Some(method.to_string());
+          idx += 1;
+        }
+        ("--errors", _) => filter.errors_only = true,
+        (n, _) => match n.parse::<usize>() {
+          Ok(n) => count = n,
+          Err(_) => return Err(output::usage_error(self.output, usage)),
+        },
+      }
+      idx += 1;
+    }
+    let matched: Vec<(usize, &HistoryEntry)> = entries
+      .iter()
+      .enumerate()
+      .filter(|(_, entry)| filter.matches(entry))
+      .collect();
+    let recent = &matched[matched.len().saturating_sub(count)..];
+    let mut result = Vec::new();
+    for (idx, entry) in recent {
+      let envelope = &entry.envelope;
+      let id = entry.id().map(|id| match id {
+        Value::String(s) => s.clone(),
+        _ => id.to_string(),
+      });
+      textln!(
+        self.output,
+        "{:>4}  {}  {:<12} {} {:<28} {:<10} {:<6} {}",
+        idx + 1,
+        // Time of the day in `2025-01-01T12:34:56.789Z`
+        envelope
+          .timestamp
+          .get(11..23)
+          .unwrap_or(&envelope.timestamp),
+        envelope.server,
+        match envelope.direction {
+          Direction::ClientToServer => "->",
+          Direction::ServerToClient => "<-",
+        },
+        entry.method.as_deref().unwrap_or("-"),
+        id.as_deref()
+          .map(|s| s.chars().take(8).collect::<String>())
+          .unwrap_or_default(),
+        entry.status(),
+        envelope
+          .latency_ms
+          .map(|ms| format!("{}ms", ms))
+          .unwrap_or_default(),
+      );
+      result.push(serde_json::json!({
+        "index": idx + 1,
+        "timestamp": envelope.timestamp,
+        "server": envelope.server,
+        "direction": envelope.direction,
+        "method": entry.method,
+        "id": id,
+        "status": entry.status(),
+        "latencyMs": envelope.latency_ms,
+      }));
+    }
+    Ok(Value::Array(result))
   }
 
   /**
    * Process a closure with the current server process as the parameter.
    * It will print out error message to ask users to select a server;
    */
-  fn process_with_current_server<F>(&mut self, f: F)
+  fn process_with_current_server<F>(&mut self, f: F) -> Result<Value, NahError>
   where
-    F: Fn(&str, &mut Box<dyn MCPServer>),
+    F: FnOnce(&str, &mut Box<dyn MCPServer>) -> Result<Value, NahError>,
   {
     match &self.current_server {
       Some(server_name) => {
         let server_process = self.server_processes.get_mut(server_name).unwrap();
-        f(&server_name, server_process);
-      }
-      None => {
-        println!("No server is selected. Run `use` command to select a server.")
+        f(server_name, server_process)
       }
+      None => Err(output::usage_error(
+        self.output,
+        "No server is selected. Run `use` command to select a server.",
+      )),
     }
   }
 
-  fn process_chat(&mut self) {
-    if self.model_config.is_none() {
-      println!("No model is supplied! Please set model config.");
-      return;
+  fn process_chat(&mut self, command_parts: &[&str]) -> Result<Value, NahError> {
+    if command_parts.len() > 2 {
+      return Err(output::usage_error(
+        self.output,
+        "Usage: chat [model profile]",
+      ));
+    }
+    if self.models.is_empty() {
+      return Err(output::usage_error(
+        self.output,
+        "No model is supplied! Please set model config.",
+      ));
     }
-    chat::process_chat(self)
+    let name = match command_parts.get(1) {
+      Some(name) => name.to_string(),
+      None => match &self.default_model {
+        Some(name) => name.clone(),
+        None => {
+          let mut names: Vec<&String> = self.models.keys().collect();
+          names.sort();
+          textln!(self.output, "Available model profiles:");
+          for name in names {
+            textln!(self.output, "* {}", name);
+          }
+          return Err(output::usage_error(
+            self.output,
+            "Usage: chat [model profile]",
+          ));
+        }
+      },
+    };
+    if !self.models.contains_key(&name) {
+      textln!(self.output, "Model profile {} not found.", name);
+      return Err(NahError::invalid_value(
+        &format!("Model profile {} not found", name),
+        None,
+      ));
+    }
+    // Tools of all servers are offered to the model.
+    let lazy_servers: Vec<String> = self
+      .available_servers()
+      .into_iter()
+      .filter(|n| !self.server_processes.contains_key(n))
+      .collect();
+    self.start_lazy_servers(&lazy_servers);
+    chat::process_chat(self, &name);
+    Ok(Value::Null)
   }
 }
 
@@ -702,24 +1575,107 @@ fn print_help() {
     "\
 Command list of nah: \n\
 * use:               Select a MCP server to interactive with. \n\
-* list_servers:      List all available MCP servers. \n\
+* list_servers:      List all MCP servers with their status. \n\
 * restart_server:    Restart a MCP server. \n\
+* server_info:       Show protocol version, capabilities and counters of a MCP server. \n\
 * list_tools:        List all tools on the current server.\n\
 * inspect_tool:      Inspect detailed info of a tool.\n\
-* call_tool:         Call a tool on the current server.\n\
+* call_tool:         Call a tool on the current server. Pass arguments inline as JSON, `@file.json` or\n\
+                     `key=value` pairs, or add `--form` to fill them in a form.\n\
 * list_resources:    List all resources on the current server\n\
 * inspect_resources: Inspect detailed info of a resource \n\
 * read_resources:    Read resources with a URI\n\
 * list_prompts:      List all prompts on the current server.\n\
 * inspect_prompt:    Inspect detailed in of a prompt.\n\
-* get_prompt:        Get a prompt from current server.\n\
+* get_prompt:        Get a prompt from current server. Arguments are passed like `call_tool`.\n\
 * set_timeout:       Set communication timeout for the current server\n\
-* chat:              Chat with a LLM equiped with tools\n \
+* chat:              Chat with a LLM equiped with tools. Add a name to choose a model profile.\n\
+* source:            Run commands in a script file.\n\
+* history:           Inspect messages of this session: `history list [count] [--server name]\n\
+                     [--method name] [--errors]`, `history show [n]` and `history resend [n]`.\n\
+* let:               Set a variable, e.g. `let text = $last.content[0].text`.\n\
+* assert:            Check a condition, e.g. `assert $last.isError == false`.\n\
+* set:               Set an option: `set on_error [stop | continue]` or `set output [text | json]`.\n\
 * exit:              Stop all server and exit nah."
   );
 }
 
-fn load_json_arguments(filename: &str) -> Result<Value, NahError> {
+/**
+ * Let the user fill arguments of a tool call in the editor, starting from a
+ * template created from the input schema.
+ */
+fn edit_tool_arguments(tool_name: &str, input_schema: &Value) -> Result<Value, NahError> {
+  let template = json_schema::create_instance_template(input_schema)?;
+  let temp_filename = format!(".nah_req.{}.args.js", tool_name);
+  if let Err(e) = File::create(&temp_filename).and_then(|mut file| {
+    file.write_all(b"// Please fill arguments for tool call here in JSON format. \n// Lines starts with '//' will be removed\n")?;
+    file.write_all(template.as_bytes())?;
+    file.flush()
+  }) {
+    return Err(NahError::io_error(
+      "Failed to prepare file for argument template",
+      Some(Box::new(e)),
+    ));
+  }
+  launch_editor(&temp_filename)?;
+  let arguments = load_json_arguments(&temp_filename);
+  if std::fs::remove_file(&temp_filename).is_err() {
+    println!("Failed to clean up the temporary argument file.")
+  }
+  arguments
+}
+
+/**
+ * Let the user fill arguments of a prompt in the editor.
+ */
+fn edit_prompt_arguments(
+  prompt_name: &str,
+  args: &[MCPPromptArgument],
+) -> Result<HashMap<String, String>, NahError> {
+  let temp_filename = format!(".nah_req.{}.args.js", prompt_name);
+  let template_lines: Vec<String> = args
+    .iter()
+    .map(|arg| format!("    \"{}\": \"<FILL ARGUMENT HERE>\"", arg.name))
+    .collect();
+  let write_template_result = File::create(&temp_filename).and_then(|mut file| {
+    file.write_all(b"// Please fill arguments for prompt call here in JSON format \n// Lines starts with '//' will be removed\n")?;
+    file.write_all(b"{\n")?;
+    file.write_all(template_lines.join(",\n").as_bytes())?;
+    file.write_all(b"\n}\n")?;
+    Ok(())
+  });
+  if let Err(e) = write_template_result {
+    return Err(NahError::io_error(
+      &format!(
+        "Failed to prepare the argument template file for getting prompt {}",
+        prompt_name
+      ),
+      Some(Box::new(e)),
+    ));
+  }
+  launch_editor(&temp_filename)?;
+  let argument_value = load_json_arguments(&temp_filename);
+  if std::fs::remove_file(&temp_filename).is_err() {
+    println!("Failed to clean up the temporary argument file.")
+  }
+  let arguments = match argument_value?.as_object() {
+    Some(v) => v.clone(),
+    None => {
+      return Err(NahError::invalid_argument_error(
+        "Arguments should be a JSON Object!",
+        None,
+      ))
+    }
+  };
+  Ok(
+    arguments
+      .iter()
+      .map(|(k, v)| (k.to_owned(), v.as_str().unwrap_or("").to_owned()))
+      .collect(),
+  )
+}
+
+pub(crate) fn load_json_arguments(filename: &str) -> Result<Value, NahError> {
   let mut buf = String::new();
   let mut file = match File::open(&filename) {
     Ok(f) => f,
@@ -755,94 +1711,91 @@ fn load_json_arguments(filename: &str) -> Result<Value, NahError> {
   }
 }
 
-fn split_command_into_parts(command: &str) -> Vec<&str> {
-  let mut ret = Vec::new();
-  let mut command_chars = command.chars();
-  let mut last_pos = 0usize;
-  let mut current_pos = 0usize;
-  let mut in_string = false;
-  let mut in_escape = false;
-  let mut in_chunk = false;
-  while current_pos < command.len() {
-    let current_char = command_chars.next();
-    current_pos += 1;
-    match current_char {
-      None => {
-        break;
-      }
-      Some(c) => {
-        if in_string {
-          // if in string, continue to move forward until hit a non-escaping quote
-          if c == '"' && !in_escape {
-            in_string = false;
-            in_chunk = false;
-            ret.push(&command[last_pos..current_pos]);
-            last_pos = current_pos
-          } else if c == '\\' {
-            in_escape = true;
-          } else {
-            in_escape = false;
-          }
-        } else {
-          if in_chunk {
-            if c.is_ascii_whitespace() {
-              in_chunk = false;
-              ret.push(&command[last_pos..current_pos - 1]);
-              last_pos = current_pos;
-            }
-          } else {
-            if c.is_ascii_whitespace() {
-              last_pos = current_pos;
-            } else if c == '"' {
-              in_string = true;
-              in_chunk = true;
-              last_pos = current_pos - 1;
-            } else {
-              in_chunk = true;
-              last_pos = current_pos - 1;
-            }
-          }
-        }
-      }
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use history::{TrafficRecorder, Transport};
+  use mcp::FakeServer;
+
+  fn test_context(history_path: PathBuf) -> AppContext {
+    AppContext {
+      server_processes: HashMap::new(),
+      current_server: None,
+      history_path,
+      server_commands: HashMap::new(),
+      remote_server_configs: HashMap::new(),
+      models: HashMap::new(),
+      default_model: None,
+      argument_input: ArgumentInputMode::default(),
+      variables: HashMap::new(),
+      command_failed: false,
+      on_error: OnError::default(),
+      script_depth: 0,
+      output: OutputMode::Json,
     }
   }
-  if in_chunk {
-    ret.push(&command[last_pos..current_pos]);
-  }
-  return ret;
-}
 
-fn drop_quotes(raw_content: &str) -> String {
-  if !raw_content.starts_with("\"") {
-    return raw_content.to_string();
-  }
-  let mut ret = String::new();
-  let mut chars = raw_content.chars();
-  chars.next();
-  let mut in_escape = false;
-  let mut c = chars.next();
-  while c.is_some() {
-    match c {
-      Some('\\') => {
-        in_escape = true;
-      }
-      Some('"') => {
-        if in_escape {
-          ret.push('"');
-          in_escape = false;
-        } else {
-          break;
-        }
-      }
-      Some(v) => {
-        ret.push(v);
-        in_escape = false;
-      }
-      None => {
-        break;
-      }
-    }
-    c = chars.next();
+  #[test]
+  fn test_process_history() {
+    let dir = std::env::temp_dir().join(format!("nah_test_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut recorder = TrafficRecorder::open(&dir, "fake", Transport::Stdio, None).unwrap();
+    recorder.record_sent(serde_json::json!({
+      "jsonrpc": "2.0", "id": "1", "method": "tools/call", "params": {"name": "echo"}
+    }));
+    recorder.record_received(r#"{"jsonrpc": "2.0", "id": "1", "result": {"isError": true}}"#);
+    recorder.record_received(r#"{"jsonrpc": "2.0", "method": "notifications/progress"}"#);
+    recorder.flush();
+    let mut context = test_context(dir.clone());
+
+    assert!(context.process_history(&["history"]).is_err());
+    assert!(context.process_history(&["history", "list", "x"]).is_err());
+    let listed = context.process_history(&["history", "list", "1"]).unwrap();
+    assert_eq!(listed[0]["index"], 3);
+    let listed = context
+      .process_history(&["history", "list", "--errors"])
+      .unwrap();
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    let listed = context
+      .process_history(&[
+        "history",
+        "list",
+        "--method",
+        "tools/call",
+        "--server",
+        "fake",
+      ])
+      .unwrap();
+    assert_eq!(listed.as_array().unwrap().len(), 2);
+
+    assert!(context.process_history(&["history", "show", "0"]).is_err());
+    assert!(context.process_history(&["history", "show", "4"]).is_err());
+    let shown = context.process_history(&["history", "show", "1"]).unwrap();
+    assert_eq!(shown["message"]["method"], "tools/call");
+
+    // Responses and notifications can't be resent, and requests need a server
+    assert!(context
+      .process_history(&["history", "resend", "2"])
+      .is_err());
+    assert!(context
+      .process_history(&["history", "resend", "3"])
+      .is_err());
+    assert!(context
+      .process_history(&["history", "resend", "1"])
+      .is_err());
+    let server = FakeServer::new("fake", |_| Ok(serde_json::json!({"content": []})));
+    let requests = server.requests.clone();
+    context
+      .server_processes
+      .insert("fake".to_string(), Box::new(server));
+    context.current_server = Some("fake".to_string());
+    let result = context
+      .process_history(&["history", "resend", "1"])
+      .unwrap();
+    assert_eq!(result, serde_json::json!({"content": []}));
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests[0]["params"]["name"], "echo");
+    assert_ne!(requests[0]["id"], "1");
+    let _ = std::fs::remove_dir_all(&dir);
   }
-  return ret;
 }
@@ -0,0 +1,210 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * State of a connection to a MCP server: the result of `initialize` and
+ * counters of the requests sent since then.
+ */
+use crate::history::Transport;
+use crate::types::NahError;
+use nah_mcp_types::MCPResponse;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Instant;
+
+/**
+ * Counters kept by each server implementation.
+ */
+#[derive(Debug, Clone)]
+pub struct ConnectionStats {
+  /// Result of the `initialize` request.
+  pub initialize_result: Value,
+  pub started_at: Instant,
+  pub requests: u64,
+  /// Requests which failed or got an error response.
+  pub errors: u64,
+}
+
+impl ConnectionStats {
+  pub fn new() -> Self {
+    ConnectionStats {
+      initialize_result: Value::Null,
+      started_at: Instant::now(),
+      requests: 0,
+      errors: 0,
+    }
+  }
+
+  /**
+   * Count a request with its outcome.
+   */
+  pub fn record(&mut self, result: &Result<MCPResponse, NahError>) {
+    self.requests += 1;
+    if !matches!(result, Ok(response) if response.error.is_none()) {
+      self.errors += 1;
+    }
+  }
+}
+
+/**
+ * Snapshot of a connection, shown by `server_info` and `list_servers`.
+ */
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionInfo {
+  pub transport: Transport,
+  /// PID of a local server process.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub pid: Option<u32>,
+  /// URL of a remote server.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub url: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub session_id: Option<String>,
+  pub initialize_result: Value,
+  pub uptime_secs: u64,
+  pub requests: u64,
+  pub errors: u64,
+}
+
+impl ConnectionInfo {
+  pub fn new(transport: Transport, stats: &ConnectionStats) -> Self {
+    ConnectionInfo {
+      transport,
+      pid: None,
+      url: None,
+      session_id: None,
+      initialize_result: stats.initialize_result.clone(),
+      uptime_secs: stats.started_at.elapsed().as_secs(),
+      requests: stats.requests,
+      errors: stats.errors,
+    }
+  }
+
+  fn initialize_str(&self, pointer: &str) -> Option<&str> {
+    self
+      .initialize_result
+      .pointer(pointer)
+      .and_then(|v| v.as_str())
+  }
+
+  pub fn protocol_version(&self) -> Option<&str> {
+    self.initialize_str("/protocolVersion")
+  }
+
+  pub fn instructions(&self) -> Option<&str> {
+    self.initialize_str("/instructions")
+  }
+
+  /**
+   * Name and version from `serverInfo`, followed by the title when there is
+   * one, e.g. `weather 1.2.0 (Weather Service)`.
+   */
+  pub fn server_description(&self) -> String {
+    let mut description = self
+      .initialize_str("/serverInfo/name")
+      .unwrap_or("unknown")
+      .to_string();
+    if let Some(version) = self.initialize_str("/serverInfo/version") {
+      description.push(' ');
+      description.push_str(version);
+    }
+    if let Some(title) = self.initialize_str("/serverInfo/title") {
+      description.push_str(&format!(" ({})", title));
+    }
+    description
+  }
+
+  /**
+   * Capabilities with their enabled flags, e.g.
+   * `tools(listChanged), resources(subscribe, listChanged)`.
+   */
+  pub fn capabilities_summary(&self) -> String {
+    let Some(capabilities) = self
+      .initialize_result
+      .get("capabilities")
+      .and_then(|v| v.as_object())
+    else {
+      return "none".to_string();
+    };
+    let items: Vec<String> = capabilities
+      .iter()
+      .map(|(name, value)| {
+        let flags: Vec<&str> = value
+          .as_object()
+          .map(|obj| {
+            obj
+              .iter()
+              .filter(|(_, v)| v.as_bool() == Some(true))
+              .map(|(k, _)| k.as_str())
+              .collect()
+          })
+          .unwrap_or_default();
+        if flags.is_empty() {
+          name.clone()
+        } else {
+          format!("{}({})", name, flags.join(", "))
+        }
+      })
+      .collect();
+    if items.is_empty() {
+      "none".to_string()
+    } else {
+      items.join(", ")
+    }
+  }
+
+  /**
+   * `stdio, PID 1234` or `http, https://example.com/mcp`.
+   */
+  pub fn endpoint(&self) -> String {
+    match (self.transport, self.pid, &self.url) {
+      (Transport::Stdio, Some(pid), _) => format!("stdio, PID {}", pid),
+      (Transport::Http, _, Some(url)) => format!("http, {}", url),
+      (Transport::Stdio, _, _) => "stdio".to_string(),
+      (Transport::Http, _, _) => "http".to_string(),
+    }
+  }
+}
+
+/**
+ * Format a duration like `2h 5m`, `5m 3s` or `42s`.
+ */
+pub fn format_uptime(secs: u64) -> String {
+  let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+  if hours > 0 {
+    format!("{}h {}m", hours, minutes)
+  } else if minutes > 0 {
+    format!("{}m {}s", minutes, seconds)
+  } else {
+    format!("{}s", seconds)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_connection_info() {
+    let mut stats = ConnectionStats::new();
+    stats.initialize_result = serde_json::json!({
+      "protocolVersion": "2025-06-18",
+      "capabilities": {"tools": {"listChanged": true}, "logging": {}},
+      "serverInfo": {"name": "weather", "version": "1.2.0", "title": "Weather"}
+    });
+    stats.record(&Err(NahError::invalid_value("broken", None)));
+    let mut info = ConnectionInfo::new(Transport::Stdio, &stats);
+    info.pid = Some(42);
+    assert_eq!(info.protocol_version(), Some("2025-06-18"));
+    assert_eq!(info.server_description(), "weather 1.2.0 (Weather)");
+    assert_eq!(info.capabilities_summary(), "logging, tools(listChanged)");
+    assert_eq!(info.endpoint(), "stdio, PID 42");
+    assert_eq!((info.requests, info.errors), (1, 1));
+    assert_eq!(format_uptime(42), "42s");
+    assert_eq!(format_uptime(303), "5m 3s");
+    assert_eq!(format_uptime(7500), "2h 5m");
+  }
+}
@@ -0,0 +1,101 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * A server answering requests with a closure, for tests.
+ */
+use crate::history::Transport;
+use crate::mcp::{ConnectionInfo, ConnectionStats, MCPServer};
+use crate::types::NahError;
+use nah_mcp_types::request::MCPRequest;
+use nah_mcp_types::{MCPPromptDefinition, MCPResourceDefinition, MCPResponse, MCPToolDefinition};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+type Handler = Box<dyn FnMut(&MCPRequest) -> Result<Value, Value> + Send>;
+
+pub struct FakeServer {
+  name: String,
+  /// Returns the result of a request, or its error.
+  handler: Handler,
+  /// Requests received so far, shared with the test.
+  pub requests: Arc<Mutex<Vec<Value>>>,
+  stats: ConnectionStats,
+  tool_cache: HashMap<String, MCPToolDefinition>,
+  resource_cache: HashMap<String, MCPResourceDefinition>,
+  prompt_cache: HashMap<String, MCPPromptDefinition>,
+}
+
+impl FakeServer {
+  pub fn new<F>(name: &str, handler: F) -> Self
+  where
+    F: FnMut(&MCPRequest) -> Result<Value, Value> + Send + 'static,
+  {
+    FakeServer {
+      name: name.to_string(),
+      handler: Box::new(handler),
+      requests: Arc::new(Mutex::new(Vec::new())),
+      stats: ConnectionStats::new(),
+      tool_cache: HashMap::new(),
+      resource_cache: HashMap::new(),
+      prompt_cache: HashMap::new(),
+    }
+  }
+}
+
+impl MCPServer for FakeServer {
+  fn send_and_wait_for_response(&mut self, request: MCPRequest) -> Result<MCPResponse, NahError> {
+    self
+      .requests
+      .lock()
+      .unwrap()
+      .push(serde_json::to_value(&request).unwrap());
+    let result = Ok(match (self.handler)(&request) {
+      Ok(result) => MCPResponse::new(request.id.clone(), Some(result), None),
+      Err(error) => MCPResponse::new(request.id.clone(), None, Some(error)),
+    });
+    self.stats.record(&result);
+    result
+  }
+
+  fn get_server_name(&self) -> &str {
+    &self.name
+  }
+
+  fn kill(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+
+  fn connection_info(&self) -> ConnectionInfo {
+    ConnectionInfo::new(Transport::Stdio, &self.stats)
+  }
+
+  fn _get_tool_map(&self) -> &HashMap<String, MCPToolDefinition> {
+    &self.tool_cache
+  }
+
+  fn _set_tool_map(&mut self, data: HashMap<String, MCPToolDefinition>) {
+    self.tool_cache = data;
+  }
+
+  fn _get_resource_map(&self) -> &HashMap<String, MCPResourceDefinition> {
+    &self.resource_cache
+  }
+
+  fn _set_resource_map(&mut self, data: HashMap<String, MCPResourceDefinition>) {
+    self.resource_cache = data;
+  }
+
+  fn _get_prompt_map(&self) -> &HashMap<String, MCPPromptDefinition> {
+    &self.prompt_cache
+  }
+
+  fn _set_prompt_map(&mut self, data: HashMap<String, MCPPromptDefinition>) {
+    self.prompt_cache = data;
+  }
+
+  fn set_timeout(&mut self, _timeout_ms: u64) {}
+}
@@ -3,7 +3,12 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-use crate::{mcp::MCPServer, types::NahError};
+use crate::{
+  history::{TrafficRecorder, Transport},
+  mcp::{ConnectionInfo, ConnectionStats, MCPServer},
+  secret::SecretValue,
+  types::NahError,
+};
 use nah_mcp_types::{
   notification::MCPNotification, request::MCPRequest, MCPPromptDefinition, MCPResourceDefinition,
   MCPResponse, MCPToolDefinition, MCP_PROTOCOL_VERSION,
@@ -11,14 +16,21 @@ use nah_mcp_types::{
 use reqwest::Client;
 use serde::Deserialize;
 use serde_json::Value;
-use std::{collections::HashMap, time::Duration};
+use std::{collections::HashMap, path::Path, time::Duration};
 use tokio::runtime::{Builder, Runtime};
 
 #[derive(Debug, Deserialize)]
 pub struct MCPRemoteServerConfig {
   pub url: String,
-  pub headers: HashMap<String, String>,
+  #[serde(default)]
+  pub headers: HashMap<String, SecretValue>,
   pub timeout_ms: Option<u64>,
+  /// Don't connect to the server.
+  #[serde(default)]
+  pub disabled: bool,
+  /// Connect to the server when it is first used instead of when nah starts.
+  #[serde(default)]
+  pub lazy: bool,
 }
 
 pub struct MCPHTTPServerConnection {
@@ -31,14 +43,18 @@ pub struct MCPHTTPServerConnection {
   resource_cache: HashMap<String, MCPResourceDefinition>,
   prompt_cache: HashMap<String, MCPPromptDefinition>,
   session_id: Option<String>,
+  history: TrafficRecorder,
+  stats: ConnectionStats,
 }
 
-impl MCPServer for MCPHTTPServerConnection {
-  fn send_and_wait_for_response(
-    &mut self,
-    request: MCPRequest,
-  ) -> Result<nah_mcp_types::MCPResponse, crate::types::NahError> {
-    let data_str = serde_json::to_string(&request).unwrap();
+impl MCPHTTPServerConnection {
+  /**
+   * Post a request and read its response from a JSON or an event stream body.
+   */
+  fn post_request(&mut self, request: MCPRequest) -> Result<MCPResponse, NahError> {
+    let message = serde_json::to_value(&request).unwrap();
+    let data_str = message.to_string();
+    self.history.record_sent(message);
     let mut req = self.http_client.post(self.url.to_owned());
     for (k, v) in self.headers.iter() {
       req = req.header(k, v);
@@ -66,10 +82,6 @@ impl MCPServer for MCPHTTPServerConnection {
     let session_id = response.headers().get("Mcp-Session-Id");
     if session_id.is_some() && self.session_id.is_none() {
       let new_session_id = session_id.unwrap().to_str().unwrap().to_string();
-      println!(
-        "Initialized a new session with {}, id={}",
-        self.name, new_session_id
-      );
       self.session_id = Some(new_session_id);
     }
     let content_type = response.headers().get("Content-Type");
@@ -129,6 +141,7 @@ impl MCPServer for MCPHTTPServerConnection {
       }
     };
 
+    self.history.record_received(&json_content);
     match serde_json::from_str::<MCPResponse>(&json_content) {
       Ok(r) => Ok(r),
       Err(e) => Err(NahError::mcp_server_invalid_response(
@@ -137,8 +150,20 @@ impl MCPServer for MCPHTTPServerConnection {
       )),
     }
   }
+}
+
+impl MCPServer for MCPHTTPServerConnection {
+  fn send_and_wait_for_response(
+    &mut self,
+    request: MCPRequest,
+  ) -> Result<nah_mcp_types::MCPResponse, crate::types::NahError> {
+    let result = self.post_request(request);
+    self.stats.record(&result);
+    result
+  }
 
   fn kill(&mut self) -> std::io::Result<()> {
+    self.history.flush();
     match &self.session_id {
       None => Ok(()),
       Some(session_id) => {
@@ -156,6 +181,13 @@ impl MCPServer for MCPHTTPServerConnection {
     }
   }
 
+  fn connection_info(&self) -> ConnectionInfo {
+    let mut info = ConnectionInfo::new(Transport::Http, &self.stats);
+    info.url = Some(self.url.clone());
+    info.session_id = self.session_id.clone();
+    info
+  }
+
   fn set_timeout(&mut self, timeout_ms: u64) {
     self.http_client = Client::builder()
       .timeout(Duration::from_millis(timeout_ms))
@@ -194,7 +226,9 @@ impl MCPServer for MCPHTTPServerConnection {
 
 impl MCPHTTPServerConnection {
   fn send_notification(&mut self, request: MCPNotification) -> Result<(), crate::types::NahError> {
-    let data_str = serde_json::to_string(&request).unwrap();
+    let message = serde_json::to_value(&request).unwrap();
+    let data_str = message.to_string();
+    self.history.record_sent(message);
     let mut req = self.http_client.post(self.url.to_owned());
     for (k, v) in self.headers.iter() {
       req = req.header(k, v);
@@ -230,7 +264,11 @@ impl MCPHTTPServerConnection {
     }
   }
 
-  pub fn init(name: &str, config: &MCPRemoteServerConfig) -> Result<Self, NahError> {
+  pub fn init(
+    name: &str,
+    config: &MCPRemoteServerConfig,
+    history_path: &Path,
+  ) -> Result<Self, NahError> {
     let tokio_runtime = match Builder::new_current_thread()
       .enable_io()
       .enable_time()
@@ -244,16 +282,23 @@ impl MCPHTTPServerConnection {
         ));
       }
     };
+    let mut headers = HashMap::new();
+    for (k, v) in config.headers.iter() {
+      headers.insert(k.to_owned(), v.resolve()?);
+    }
+    let history = TrafficRecorder::open(history_path, name, Transport::Http, None)?;
     let mut conn = MCPHTTPServerConnection {
       name: name.to_string(),
       url: config.url.to_owned(),
-      headers: config.headers.to_owned(),
+      headers,
       tokio_runtime: tokio_runtime,
       http_client: Client::new(),
       tool_cache: HashMap::new(),
       resource_cache: HashMap::new(),
       prompt_cache: HashMap::new(),
       session_id: None,
+      history,
+      stats: ConnectionStats::new(),
     };
     if config.timeout_ms.is_some() {
       conn.set_timeout(config.timeout_ms.unwrap());
@@ -263,7 +308,8 @@ impl MCPHTTPServerConnection {
       "nah",
       "0.1",
     );
-    conn.send_and_wait_for_response(initialize_request)?;
+    let response = conn.send_and_wait_for_response(initialize_request)?;
+    conn.stats.initialize_result = response.result.unwrap_or_default();
     let initialized_notification = MCPNotification::initialized();
     conn.send_notification(initialized_notification)?;
 
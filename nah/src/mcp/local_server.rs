@@ -6,87 +6,194 @@
 use serde::Deserialize;
 use serde_json::Value;
 use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
+use std::fs::OpenOptions;
 use std::path::PathBuf;
 use std::sync::{mpsc::channel, Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{
   io::{BufRead, BufReader, Write},
-  process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+  process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio},
 };
 
-use crate::mcp::MCPServer;
+use crate::history::{TrafficRecorder, Transport};
+use crate::mcp::{ConnectionInfo, ConnectionStats, MCPServer};
 use crate::types::NahError;
 use nah_mcp_types::notification;
 use nah_mcp_types::request::MCPRequest;
 use nah_mcp_types::*;
 pub use notification::MCPNotification;
 
+/**
+ * Lines of the stderr file shown when a server exits.
+ */
+const STDERR_TAIL_LINES: usize = 10;
+
+/**
+ * Delay before the first restart, doubled for each restart in a row.
+ */
+const RESTART_BACKOFF_MS: u64 = 500;
+const MAX_RESTART_BACKOFF_MS: u64 = 8000;
+const DEFAULT_MAX_RESTARTS: u32 = 5;
+
+/**
+ * Default time to wait for a server to exit after closing its stdin, and
+ * after sending SIGTERM.
+ */
+const DEFAULT_SHUTDOWN_GRACE_MS: u64 = 1000;
+const DEFAULT_TERMINATE_GRACE_MS: u64 = 1000;
+
+/**
+ * Process groups of running servers, cleaned up when nah is interrupted or
+ * panics.
+ */
+static PROCESS_GROUPS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/**
+ * Methods that can be sent again to a restarted server. Tool calls are sent
+ * again when the tool is annotated as read-only or idempotent.
+ */
+const IDEMPOTENT_METHODS: &[&str] = &[
+  "ping",
+  "tools/list",
+  "resources/list",
+  "resources/templates/list",
+  "resources/read",
+  "prompts/list",
+  "prompts/get",
+];
+
+/**
+ * When to restart a server whose process exited.
+ */
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum RestartPolicy {
+  #[default]
+  Never,
+  /// Restart when the process exits with an error or is killed.
+  OnFailure,
+  Always,
+}
+
 /**
  * Describes how to launch a MCP server with a command.
  */
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct MCPLocalServerCommand {
   pub command: String,
+  #[serde(default)]
   pub args: Vec<String>,
   pub timeout_ms: Option<u64>,
+  /// Extra environment variables of the server process.
+  #[serde(default)]
+  pub env: HashMap<String, String>,
+  /// Working directory of the server process.
+  pub cwd: Option<PathBuf>,
+  /// Launch the server without inheriting the environment of nah, except
+  /// the variables in `env_allowlist`.
+  #[serde(rename = "clearEnv", default)]
+  pub clear_env: bool,
+  #[serde(rename = "envAllowlist", default)]
+  pub env_allowlist: Vec<String>,
+  /// Restart the server when its process exits.
+  #[serde(default)]
+  pub restart: RestartPolicy,
+  /// Restarts in a row, without a successful request between them, before
+  /// giving up.
+  #[serde(rename = "maxRestarts")]
+  pub max_restarts: Option<u32>,
+  /// Time to wait for the server to exit after closing its stdin, before
+  /// sending SIGTERM.
+  #[serde(rename = "shutdownGraceMs")]
+  pub shutdown_grace_ms: Option<u64>,
+  /// Time to wait for the server to exit after SIGTERM, before SIGKILL.
+  #[serde(rename = "terminateGraceMs")]
+  pub terminate_grace_ms: Option<u64>,
+  /// Don't start the server.
+  #[serde(default)]
+  pub disabled: bool,
+  /// Start the server when it is first used instead of when nah starts.
+  #[serde(default)]
+  pub lazy: bool,
+}
+impl MCPLocalServerCommand {
+  /**
+   * Command to launch the server with piped stdin and stdout, in its own
+   * process group so that its child processes can be stopped with it.
+   */
+  pub fn build_command(&self) -> Command {
+    let mut server_command = Command::new(&self.command);
+    #[cfg(unix)]
+    {
+      use std::os::unix::process::CommandExt;
+      server_command.process_group(0);
+    }
+    server_command.args(self.args.iter());
+    if self.clear_env {
+      server_command.env_clear();
+      for key in self.env_allowlist.iter() {
+        if let Some(value) = std::env::var_os(key) {
+          server_command.env(key, value);
+        }
+      }
+    }
+    server_command.envs(self.env.iter());
+    if let Some(cwd) = &self.cwd {
+      server_command.current_dir(cwd);
+    }
+    server_command.stdin(Stdio::piped());
+    server_command.stdout(Stdio::piped());
+    server_command
+  }
 }
+
 /**
  * Wrapper of a MCP local server process.
  */
 pub struct MCPLocalServerProcess {
   pub server_name: String,
-  history_file: File,
+  command: MCPLocalServerCommand,
+  history_path: PathBuf,
+  stderr_path: PathBuf,
+  /// Restart policy, applied once the server is initialized.
+  restart: RestartPolicy,
+  /// Restarts since the last successful request.
+  restarts_in_row: u32,
+  history: TrafficRecorder,
   process: Child,
-  stdin: ChildStdin,
+  /// Closed when the server is stopped.
+  stdin: Option<ChildStdin>,
+  /// Whether the server closed its stdout or stdin, as it does when exiting.
+  pipe_closed: bool,
   stdout: Arc<Mutex<BufReader<ChildStdout>>>,
   tool_cache: HashMap<String, MCPToolDefinition>,
   resource_cache: HashMap<String, MCPResourceDefinition>,
   prompt_cache: HashMap<String, MCPPromptDefinition>,
   timeout_ms: u64,
+  stats: ConnectionStats,
 }
 
 impl MCPServer for MCPLocalServerProcess {
   fn send_and_wait_for_response(&mut self, request: MCPRequest) -> Result<MCPResponse, NahError> {
-    let id = request.id.clone();
-    self.send_data(request)?;
-    let mut buf = String::new();
-    loop {
-      let incoming_msg = self.receive_data::<Value>(&mut buf)?;
-      let Some(incoming_data) = incoming_msg.as_object() else {
-        continue;
-      };
-      match incoming_data.get("id").and_then(|v| v.as_str()) {
-        None => {
-          // Try to unpack the message as a notification
-          match serde_json::from_value::<MCPNotification>(incoming_msg) {
-            Ok(notif) => {
-              self.process_notification(notif);
-            }
-            _ => {
-              // Unknown message. Ignore it for now.
-            }
-          }
-        }
-        Some(incoming_id) => {
-          if incoming_id == id {
-            return match serde_json::from_value::<MCPResponse>(incoming_msg) {
-              Ok(resp) => Ok(resp),
-              Err(e) => Err(NahError::mcp_server_invalid_response(
-                &self.server_name,
-                Some(Box::new(e)),
-              )),
-            };
-          }
-        }
-      }
-    }
+    let result = self.send_with_restart(request);
+    self.stats.record(&result);
+    result
   }
 
+  /**
+   * Stop the server: close its stdin, then send SIGTERM and SIGKILL to its
+   * process group when it doesn't exit within the grace periods.
+   */
   fn kill(&mut self) -> std::io::Result<()> {
-    let _ = self.history_file.flush();
-    self.process.kill()
+    self.history.flush();
+    stop_process(&mut self.process, self.stdin.take(), &self.command).map(|_| ())
+  }
+
+  fn connection_info(&self) -> ConnectionInfo {
+    let mut info = ConnectionInfo::new(Transport::Stdio, &self.stats);
+    info.pid = Some(self.process.id());
+    info
   }
 
   fn set_timeout(&mut self, timeout_ms: u64) {
@@ -152,33 +259,16 @@ impl MCPLocalServerProcess {
   ) -> Result<Self, NahError> {
     // Default 5000ms timeout
     let timeout_ms = mcp_command.timeout_ms.unwrap_or(5000);
-    let mut server_command = Command::new(&mcp_command.command);
-    for arg in mcp_command.args.iter() {
-      server_command.arg(&arg);
-    }
-    server_command.stdin(Stdio::piped());
-    server_command.stdout(Stdio::piped());
+    let mut server_command = mcp_command.build_command();
 
-    let mut history_file_path = history_path.clone();
-    history_file_path.push(format!("{}.jsonl", name));
-    let history_file = match OpenOptions::new()
-      .create(true)
-      .append(true)
-      .open(history_file_path.as_path())
-    {
-      Ok(f) => f,
-      Err(e) => {
-        return Err(NahError::io_error(
-          &format!(
-            "Failed to create history file: {}",
-            history_file_path.display(),
-          ),
-          Some(Box::new(e)),
-        ));
-      }
-    };
-    let mut stderr_file_path = history_path.clone();
-    stderr_file_path.push(format!("{}.stderr", name));
+    let stderr_file_name = format!("{}.stderr", name);
+    let history = TrafficRecorder::open(
+      history_path,
+      name,
+      Transport::Stdio,
+      Some(&stderr_file_name),
+    )?;
+    let stderr_file_path = history_path.join(&stderr_file_name);
     let stderr_file = match OpenOptions::new()
       .create(true)
       .append(true)
@@ -207,19 +297,27 @@ impl MCPLocalServerProcess {
       }
     };
 
-    let stdin = server_process.stdin.take().unwrap();
+    register_process_group(server_process.id());
+    let stdin = server_process.stdin.take();
     let stdout = server_process.stdout.take().unwrap();
     let stdout_reader = BufReader::new(stdout);
     let mut result = MCPLocalServerProcess {
       server_name: name.to_string(),
+      command: mcp_command.clone(),
+      history_path: history_path.clone(),
+      stderr_path: stderr_file_path,
+      restart: RestartPolicy::Never,
+      restarts_in_row: 0,
       process: server_process,
       stdin,
+      pipe_closed: false,
       stdout: Arc::new(Mutex::new(stdout_reader)),
       tool_cache: HashMap::new(),
       resource_cache: HashMap::new(),
       prompt_cache: HashMap::new(),
       timeout_ms,
-      history_file,
+      history,
+      stats: ConnectionStats::new(),
     };
 
     let initialize_request = MCPRequest::initialize(
@@ -227,22 +325,151 @@ impl MCPLocalServerProcess {
       "nah",
       "0.1",
     );
-    let response: MCPResponse = result.send_and_wait_for_response(initialize_request)?;
+    let response = result.send_and_wait_for_response(initialize_request)?;
+    result.stats.initialize_result = response.result.unwrap_or_default();
     let initialized_notification = MCPNotification::initialized();
     result.send_data(initialized_notification)?;
 
-    println!(
-      "Server initialized. Info: {:?}",
-      response
-        .result
-        .unwrap()
-        .as_object()
-        .unwrap()
-        .get("serverInfo")
-    );
+    result.restart = mcp_command.restart;
     Ok(result)
   }
 
+  /**
+   * Send a request, and restart the server according to its restart policy
+   * when it has exited.
+   */
+  fn send_with_restart(&mut self, request: MCPRequest) -> Result<MCPResponse, NahError> {
+    let error = match self.exchange(&request) {
+      Ok(response) => {
+        self.restarts_in_row = 0;
+        return Ok(response);
+      }
+      Err(e) => e,
+    };
+    let status = match self.process.try_wait() {
+      Ok(Some(status)) => Some(status),
+      // A process closing its pipes may not be reaped yet.
+      _ if self.pipe_closed => wait_timeout(&mut self.process, 1000),
+      _ => None,
+    };
+    let Some(status) = status else {
+      return Err(error);
+    };
+    unregister_process_group(self.process.id());
+    let error =
+      NahError::mcp_server_exited(&self.server_name, &status.to_string(), &self.stderr_tail());
+    let should_restart = match self.restart {
+      RestartPolicy::Never => false,
+      RestartPolicy::OnFailure => !status.success(),
+      RestartPolicy::Always => true,
+    };
+    let max_restarts = self.command.max_restarts.unwrap_or(DEFAULT_MAX_RESTARTS);
+    if !should_restart || self.restarts_in_row >= max_restarts {
+      return Err(error);
+    }
+    eprintln!("{}", error);
+    self.restart_process()?;
+    if self.is_idempotent(&request) {
+      eprintln!("Sending {} again to {}", request.method, self.server_name);
+      let response = self.exchange(&request)?;
+      self.restarts_in_row = 0;
+      Ok(response)
+    } else {
+      Err(error)
+    }
+  }
+
+  /**
+   * Send a request and wait for its response.
+   */
+  fn exchange(&mut self, request: &MCPRequest) -> Result<MCPResponse, NahError> {
+    let id = request.id.clone();
+    self.send_data(request)?;
+    let mut buf = String::new();
+    loop {
+      let incoming_msg = self.receive_data::<Value>(&mut buf)?;
+      let Some(incoming_data) = incoming_msg.as_object() else {
+        continue;
+      };
+      match incoming_data.get("id").and_then(|v| v.as_str()) {
+        None => {
+          // Try to unpack the message as a notification
+          match serde_json::from_value::<MCPNotification>(incoming_msg) {
+            Ok(notif) => {
+              self.process_notification(notif);
+            }
+            _ => {
+              // Unknown message. Ignore it for now.
+            }
+          }
+        }
+        Some(incoming_id) => {
+          if incoming_id == id {
+            return match serde_json::from_value::<MCPResponse>(incoming_msg) {
+              Ok(resp) => Ok(resp),
+              Err(e) => Err(NahError::mcp_server_invalid_response(
+                &self.server_name,
+                Some(Box::new(e)),
+              )),
+            };
+          }
+        }
+      }
+    }
+  }
+
+  /**
+   * Last lines of the stderr file of the server.
+   */
+  fn stderr_tail(&self) -> String {
+    let content = std::fs::read_to_string(&self.stderr_path).unwrap_or_default();
+    let lines: Vec<&str> = content.lines().collect();
+    lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n")
+  }
+
+  /**
+   * Whether a request can be sent again after a restart.
+   */
+  fn is_idempotent(&self, request: &MCPRequest) -> bool {
+    if IDEMPOTENT_METHODS.contains(&request.method.as_str()) {
+      return true;
+    }
+    if request.method != "tools/call" {
+      return false;
+    }
+    let tool = request
+      .params
+      .as_ref()
+      .and_then(|p| p.get("name"))
+      .and_then(|v| v.as_str())
+      .and_then(|name| self.tool_cache.get(name));
+    let annotations = tool.and_then(|t| t.annotations.as_ref());
+    annotations.is_some_and(|a| a.read_only_hint == Some(true) || a.idempotent_hint == Some(true))
+  }
+
+  /**
+   * Start the server again after a backoff delay. The listed tools,
+   * resources and prompts are kept, so that retries and completions still
+   * know them.
+   */
+  fn restart_process(&mut self) -> Result<(), NahError> {
+    let delay = restart_delay_ms(self.restarts_in_row);
+    eprintln!("Restarting {} in {}ms", self.server_name, delay);
+    thread::sleep(Duration::from_millis(delay));
+    self.history.flush();
+    self.restarts_in_row += 1;
+    let mut process = Self::start_and_init(&self.server_name, &self.command, &self.history_path)?;
+    process.restarts_in_row = self.restarts_in_row;
+    process.timeout_ms = self.timeout_ms;
+    process.tool_cache = std::mem::take(&mut self.tool_cache);
+    process.resource_cache = std::mem::take(&mut self.resource_cache);
+    process.prompt_cache = std::mem::take(&mut self.prompt_cache);
+    process.stats.requests += self.stats.requests;
+    process.stats.errors += self.stats.errors;
+    *self = process;
+    Ok(())
+  }
+
   /**
    * Send a piece of data to the MCP Server.
    */
@@ -250,16 +477,24 @@ impl MCPLocalServerProcess {
   where
     T: serde::Serialize,
   {
-    let mut data = serde_json::to_string(&request).unwrap();
+    let message = serde_json::to_value(&request).unwrap();
+    let mut data = message.to_string();
     data.push_str("\n");
-    let _ = self.history_file.write(data.as_bytes());
-    if self.stdin.write_all(&data.as_bytes()).is_err() {
+    self.history.record_sent(message);
+    let Some(stdin) = &mut self.stdin else {
+      return Err(NahError::mcp_server_communication_error(
+        &self.server_name,
+        None,
+      ));
+    };
+    if stdin.write_all(&data.as_bytes()).is_err() {
+      self.pipe_closed = true;
       return Err(NahError::mcp_server_communication_error(
         &self.server_name,
         None,
       ));
     }
-    if self.stdin.flush().is_err() {
+    if stdin.flush().is_err() {
       return Err(NahError::mcp_server_communication_error(
         &self.server_name,
         None,
@@ -300,6 +535,13 @@ impl MCPLocalServerProcess {
         ));
       }
       Ok(result) => match result {
+        Ok(bstr) if bstr.is_empty() => {
+          self.pipe_closed = true;
+          return Err(NahError::mcp_server_communication_error(
+            &server_name_copy,
+            None,
+          ));
+        }
         Ok(bstr) => *buf = bstr,
         Err(e) => {
           return Err(NahError::mcp_server_communication_error(
@@ -309,7 +551,7 @@ impl MCPLocalServerProcess {
         }
       },
     }
-    let _ = self.history_file.write(buf.as_bytes());
+    self.history.record_received(buf);
 
     let response_json = match buf.strip_suffix("\n") {
       Some(v) => v,
@@ -332,3 +574,257 @@ impl MCPLocalServerProcess {
     // TODO: process the notification
   }
 }
+
+/**
+ * Register the process group of a spawned server, created by `build_command`,
+ * so that it is stopped when nah is interrupted or panics.
+ */
+pub fn register_process_group(pid: u32) {
+  PROCESS_GROUPS.lock().unwrap().push(pid);
+}
+
+fn unregister_process_group(pid: u32) {
+  PROCESS_GROUPS.lock().unwrap().retain(|p| *p != pid);
+}
+
+/**
+ * Stop a server process and unregister its process group: close its stdin,
+ * then send SIGTERM and SIGKILL to the group when the server doesn't exit
+ * within the grace periods of its command.
+ */
+pub fn stop_process(
+  process: &mut Child,
+  stdin: Option<ChildStdin>,
+  command: &MCPLocalServerCommand,
+) -> std::io::Result<ExitStatus> {
+  let pid = process.id();
+  drop(stdin);
+  let shutdown_grace_ms = command
+    .shutdown_grace_ms
+    .unwrap_or(DEFAULT_SHUTDOWN_GRACE_MS);
+  let terminate_grace_ms = command
+    .terminate_grace_ms
+    .unwrap_or(DEFAULT_TERMINATE_GRACE_MS);
+  let result = if let Some(status) = wait_timeout(process, shutdown_grace_ms) {
+    // Ask child processes left behind to exit as well.
+    signal_process_group(pid, Signal::Terminate);
+    Ok(status)
+  } else {
+    signal_process_group(pid, Signal::Terminate);
+    match wait_timeout(process, terminate_grace_ms) {
+      Some(status) => Ok(status),
+      None => {
+        signal_process_group(pid, Signal::Kill);
+        process.kill().and_then(|_| process.wait())
+      }
+    }
+  };
+  unregister_process_group(pid);
+  result
+}
+
+/**
+ * Wait for a process to exit, and return its status if it exits in time.
+ */
+fn wait_timeout(process: &mut Child, timeout_ms: u64) -> Option<ExitStatus> {
+  let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+  loop {
+    match process.try_wait() {
+      Ok(Some(status)) => return Some(status),
+      Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(50)),
+      _ => return None,
+    }
+  }
+}
+
+/**
+ * Delay before a restart, after `restarts_in_row` restarts without a
+ * successful request.
+ */
+fn restart_delay_ms(restarts_in_row: u32) -> u64 {
+  (RESTART_BACKOFF_MS << restarts_in_row.min(16)).min(MAX_RESTART_BACKOFF_MS)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Signal {
+  Terminate,
+  Kill,
+}
+
+#[cfg(unix)]
+fn signal_process_group(pgid: u32, signal: Signal) {
+  let signal = match signal {
+    Signal::Terminate => libc::SIGTERM,
+    Signal::Kill => libc::SIGKILL,
+  };
+  // The process group was created with the server, whose pid is its id.
+  unsafe {
+    libc::kill(-(pgid as libc::pid_t), signal);
+  }
+}
+
+#[cfg(not(unix))]
+fn signal_process_group(_pgid: u32, _signal: Signal) {}
+
+/**
+ * Stop the process groups of all running servers, when nah exits without
+ * stopping them one by one: SIGTERM, then SIGKILL after a short delay.
+ */
+pub fn terminate_all_servers() {
+  let groups = match PROCESS_GROUPS.lock() {
+    Ok(groups) => groups.clone(),
+    Err(e) => e.into_inner().clone(),
+  };
+  if groups.is_empty() {
+    return;
+  }
+  for pgid in groups.iter() {
+    signal_process_group(*pgid, Signal::Terminate);
+  }
+  thread::sleep(Duration::from_millis(200));
+  for pgid in groups.iter() {
+    signal_process_group(*pgid, Signal::Kill);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn test_exited_server() {
+    let history_path = std::env::temp_dir().join(format!("nah_test_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&history_path).unwrap();
+    let command: MCPLocalServerCommand = serde_json::from_value(json!({
+      "command": "sh",
+      "args": ["-c", "echo boom >&2; exit 3"],
+      "restart": "onFailure"
+    }))
+    .unwrap();
+    assert_eq!(command.restart, RestartPolicy::OnFailure);
+    let result = MCPLocalServerProcess::start_and_init("crash", &command, &history_path);
+    let _ = std::fs::remove_dir_all(&history_path);
+    let error = result.err().unwrap();
+    assert_eq!(error.code, 17);
+    assert!(error.message.contains("exit status: 3"));
+    assert!(error.message.ends_with("boom"));
+  }
+
+  /**
+   * Answers `initialize` and the first `$2` other requests, then exits with
+   * the status `$1` on the next one. Answers `not json` to `resources/list`.
+   */
+  const CRASHING_SERVER_SCRIPT: &str = r#"n=0
+while read -r line; do
+  id=$(printf '%s' "$line" | sed -nE 's/.*"id":("[^"]*"|[0-9]+).*/\1/p')
+  [ -n "$id" ] || continue
+  case "$line" in
+    *'"initialize"'*) ;;
+    *'"resources/list"'*) echo 'not json'; continue;;
+    *) n=$((n + 1)); [ "$n" -gt "$2" ] && exit "$1";;
+  esac
+  printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id"
+done"#;
+
+  fn start_crashing_server(
+    history_path: &PathBuf,
+    status: u32,
+    answered: u32,
+    options: Value,
+  ) -> MCPLocalServerProcess {
+    let mut command = json!({
+      "command": "sh",
+      "args": ["-c", CRASHING_SERVER_SCRIPT, "sh", status.to_string(), answered.to_string()],
+    });
+    command
+      .as_object_mut()
+      .unwrap()
+      .extend(options.as_object().unwrap().clone());
+    let command: MCPLocalServerCommand = serde_json::from_value(command).unwrap();
+    MCPLocalServerProcess::start_and_init("crashing", &command, history_path).unwrap()
+  }
+
+  fn new_id() -> Value {
+    Value::String(uuid::Uuid::new_v4().to_string())
+  }
+
+  #[test]
+  fn test_restart() {
+    let history_path = std::env::temp_dir().join(format!("nah_test_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&history_path).unwrap();
+
+    // A list request is sent again to the restarted server.
+    let mut server = start_crashing_server(&history_path, 1, 1, json!({"restart": "onFailure"}));
+    let pid = server.process.id();
+    let resource = json!({"uri": "file:///a", "name": "a"});
+    let prompt = json!({"name": "greet", "description": null, "arguments": null});
+    server.resource_cache.insert(
+      "file:///a".to_string(),
+      serde_json::from_value(resource).unwrap(),
+    );
+    server
+      .prompt_cache
+      .insert("greet".to_string(), serde_json::from_value(prompt).unwrap());
+    assert!(server
+      .send_and_wait_for_response(MCPRequest::tools_list(&new_id()))
+      .is_ok());
+    assert!(server
+      .send_and_wait_for_response(MCPRequest::tools_list(&new_id()))
+      .is_ok());
+    assert_ne!(server.process.id(), pid);
+    assert_eq!(server.restarts_in_row, 0);
+    assert_eq!((server.stats.requests, server.stats.errors), (4, 0));
+    assert!(server.resource_cache.contains_key("file:///a"));
+    assert!(server.prompt_cache.contains_key("greet"));
+
+    // A call of a tool without annotations is not sent again.
+    let pid = server.process.id();
+    let call = MCPRequest::tools_call(&new_id(), "write", &json!({}));
+    let error = server.send_and_wait_for_response(call).err().unwrap();
+    assert_eq!(error.code, 17);
+    assert_ne!(server.process.id(), pid);
+    assert!(server
+      .send_and_wait_for_response(MCPRequest::tools_list(&new_id()))
+      .is_ok());
+
+    // An invalid response doesn't wait for the process to exit.
+    let started = Instant::now();
+    let result = server.send_and_wait_for_response(MCPRequest::resources_list(&new_id()));
+    assert!(result.is_err());
+    assert!(started.elapsed() < Duration::from_millis(500));
+    server.kill().unwrap();
+
+    // A server exiting successfully is not restarted on failure only.
+    let mut server = start_crashing_server(&history_path, 0, 0, json!({"restart": "onFailure"}));
+    let pid = server.process.id();
+    let error = server
+      .send_and_wait_for_response(MCPRequest::tools_list(&new_id()))
+      .err()
+      .unwrap();
+    assert!(error.message.contains("exit status: 0"));
+    assert_eq!(server.process.id(), pid);
+
+    // Restarts in a row stop at `maxRestarts`.
+    let options = json!({"restart": "always", "maxRestarts": 2});
+    let mut server = start_crashing_server(&history_path, 0, 0, options);
+    let mut pids = vec![server.process.id()];
+    for _ in 0..3 {
+      let result = server.send_and_wait_for_response(MCPRequest::tools_list(&new_id()));
+      assert!(result.is_err());
+      pids.push(server.process.id());
+    }
+    assert_eq!(server.restarts_in_row, 2);
+    assert!(pids[0] != pids[1] && pids[1] != pids[2]);
+    assert_eq!(pids[2], pids[3]);
+    let _ = server.kill();
+    let _ = std::fs::remove_dir_all(&history_path);
+  }
+
+  #[test]
+  fn test_restart_delay() {
+    let delays: Vec<u64> = (0..6).map(restart_delay_ms).collect();
+    assert_eq!(delays, [500, 1000, 2000, 4000, 8000, 8000]);
+    assert_eq!(restart_delay_ms(u32::MAX), MAX_RESTART_BACKOFF_MS);
+  }
+}
@@ -12,9 +12,20 @@ use nah_mcp_types::*;
 pub use request::MCPRequest;
 use serde_json::Value;
 use std::collections::HashMap;
+mod connection;
+pub use connection::format_uptime;
+pub use connection::ConnectionInfo;
+pub use connection::ConnectionStats;
 mod local_server;
+pub use local_server::register_process_group;
+pub use local_server::stop_process;
+pub use local_server::terminate_all_servers;
 pub use local_server::MCPLocalServerCommand;
 pub use local_server::MCPLocalServerProcess;
+#[cfg(test)]
+mod fake_server;
+#[cfg(test)]
+pub use fake_server::FakeServer;
 mod http_server;
 pub use http_server::MCPHTTPServerConnection;
 pub use http_server::MCPRemoteServerConfig;
@@ -39,6 +50,11 @@ pub trait MCPServer {
    */
   fn kill(&mut self) -> std::io::Result<()>;
 
+  /**
+   * Get the transport, the initialize result and the counters of the connection.
+   */
+  fn connection_info(&self) -> ConnectionInfo;
+
   /**
    * Return a reference to the tool definiton map
    */
@@ -0,0 +1,263 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * A stdio MCP server answering requests with responses recorded in a server
+ * history file, as a deterministic stand-in for the real server.
+ */
+use crate::history::{Direction, Envelope};
+use crate::types::NahError;
+use nah_mcp_types::request::MCPRequest;
+use nah_mcp_types::{MCPResourceContent, MCPResourceDefinition, MCPResponse, MCPToolDefinition};
+use nah_server::{run_mcp_server_with_stdio, AbstractMCPServer, ServerInfo};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+
+/**
+ * A recorded request and its response.
+ */
+#[derive(Debug)]
+struct Exchange {
+  method: String,
+  params: Option<Value>,
+  response: Value,
+  used: bool,
+}
+
+pub struct MockServer {
+  server_info: ServerInfo,
+  exchanges: Vec<Exchange>,
+}
+
+impl MockServer {
+  /**
+   * Load requests and their responses from a server history file.
+   */
+  pub fn load(path: &Path) -> Result<MockServer, NahError> {
+    let data = match std::fs::read_to_string(path) {
+      Ok(d) => d,
+      Err(e) => {
+        return Err(NahError::io_error(
+          &format!("Failed to read history file: {}", path.display()),
+          Some(Box::new(e)),
+        ));
+      }
+    };
+    let envelopes: Vec<Envelope> = data
+      .lines()
+      .filter_map(|line| serde_json::from_str(line).ok())
+      .collect();
+    Ok(MockServer::from_envelopes(&envelopes))
+  }
+
+  fn from_envelopes(envelopes: &[Envelope]) -> MockServer {
+    let mut responses: HashMap<String, &Value> = HashMap::new();
+    for envelope in envelopes {
+      let message = &envelope.message;
+      if envelope.direction == Direction::ServerToClient && message.get("method").is_none() {
+        if let Some(id) = message.get("id") {
+          responses.insert(id.to_string(), message);
+        }
+      }
+    }
+    let mut exchanges = Vec::new();
+    for envelope in envelopes {
+      let message = &envelope.message;
+      if envelope.direction != Direction::ClientToServer {
+        continue;
+      }
+      let (Some(method), Some(id)) = (
+        message.get("method").and_then(|m| m.as_str()),
+        message.get("id"),
+      ) else {
+        continue;
+      };
+      if let Some(response) = responses.get(&id.to_string()) {
+        exchanges.push(Exchange {
+          method: method.to_string(),
+          params: message.get("params").map(normalize_params),
+          response: (*response).clone(),
+          used: false,
+        });
+      }
+    }
+    let server_info = exchanges
+      .iter()
+      .find(|e| e.method == "initialize")
+      .and_then(|e| e.response.pointer("/result/serverInfo"))
+      .and_then(|info| {
+        Some(ServerInfo {
+          name: info.get("name")?.as_str()?.to_string(),
+          version: info.get("version")?.as_str()?.to_string(),
+        })
+      })
+      .unwrap_or(ServerInfo {
+        name: "nah-mock".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+      });
+    MockServer {
+      server_info,
+      exchanges,
+    }
+  }
+
+  /**
+   * Find the response of a request. Requests are matched by method and params,
+   * except `initialize` whose params describe the client. When a request was
+   * recorded several times, the responses are returned in the recorded order,
+   * and the last one is repeated.
+   */
+  fn respond(&mut self, request: &MCPRequest) -> Option<Value> {
+    let params = request.params.as_ref().map(normalize_params);
+    let matches = |e: &Exchange| {
+      e.method == request.method && (request.method == "initialize" || e.params == params)
+    };
+    let idx = match self.exchanges.iter().position(|e| !e.used && matches(e)) {
+      Some(idx) => idx,
+      None => self.exchanges.iter().rposition(matches)?,
+    };
+    let exchange = &mut self.exchanges[idx];
+    exchange.used = true;
+    Some(exchange.response.clone())
+  }
+}
+
+/**
+ * Remove `_meta` from params, which carries values like progress tokens that
+ * change between sessions.
+ */
+fn normalize_params(params: &Value) -> Value {
+  let mut params = params.clone();
+  if let Some(obj) = params.as_object_mut() {
+    obj.remove("_meta");
+  }
+  params
+}
+
+impl AbstractMCPServer for MockServer {
+  fn get_server_info(&self) -> ServerInfo {
+    self.server_info.clone()
+  }
+
+  // All requests are answered by `on_request` from the records.
+
+  fn get_tools_list(&self) -> Vec<MCPToolDefinition> {
+    Vec::new()
+  }
+
+  fn on_tool_call(
+    &mut self,
+    _name: &str,
+    _args: Option<&serde_json::Map<String, Value>>,
+  ) -> String {
+    String::new()
+  }
+
+  fn get_resources_list(&self) -> Vec<MCPResourceDefinition> {
+    Vec::new()
+  }
+
+  fn on_resources_read(&self, _uri: &str) -> Vec<MCPResourceContent> {
+    Vec::new()
+  }
+
+  fn on_request(&mut self, request: &MCPRequest) -> Option<MCPResponse> {
+    let Some(mut response) = self.respond(request) else {
+      if request.method == "initialize" {
+        // Nothing recorded, answer with the default capabilities.
+        return None;
+      }
+      eprintln!("No recorded response for {}", request.method);
+      return Some(MCPResponse::new(
+        request.id.clone(),
+        None,
+        Some(json!({
+          "code": -32603,
+          "message": format!("No recorded response for {} with these params", request.method),
+        })),
+      ));
+    };
+    response["id"] = request.id.clone();
+    match serde_json::from_value(response) {
+      Ok(r) => Some(r),
+      Err(e) => Some(MCPResponse::new(
+        request.id.clone(),
+        None,
+        Some(json!({
+          "code": -32603,
+          "message": format!("Invalid recorded response: {}", e),
+        })),
+      )),
+    }
+  }
+}
+
+/**
+ * Serve a recorded session over stdio. Returns the exit code.
+ */
+pub fn run_mock(history_file: &Path) -> i32 {
+  let mut server = match MockServer::load(history_file) {
+    Ok(s) => s,
+    Err(e) => {
+      eprintln!("{}", e);
+      return e.code;
+    }
+  };
+  eprintln!(
+    "Serving {} recorded request(s) from {}",
+    server.exchanges.len(),
+    history_file.display()
+  );
+  match run_mcp_server_with_stdio(&mut server) {
+    Ok(()) => 0,
+    Err(e) => {
+      eprintln!("{}", e);
+      1
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::history::Transport;
+
+  fn envelope(direction: Direction, message: Value) -> Envelope {
+    Envelope {
+      timestamp: String::new(),
+      direction,
+      transport: Transport::Stdio,
+      server: "weather".to_string(),
+      latency_ms: None,
+      message,
+    }
+  }
+
+  #[test]
+  fn test_respond() {
+    let call = |id: &str, city: &str| {
+      json!({"jsonrpc": "2.0", "id": id, "method": "tools/call",
+        "params": {"name": "forecast", "arguments": {"city": city}, "_meta": {"progressToken": id}}})
+    };
+    let result =
+      |id: &str, text: &str| json!({"jsonrpc": "2.0", "id": id, "result": {"text": text}});
+    let mut server = MockServer::from_envelopes(&[
+      envelope(Direction::ClientToServer, call("1", "Paris")),
+      envelope(Direction::ServerToClient, result("1", "sunny")),
+      envelope(Direction::ClientToServer, call("2", "Paris")),
+      envelope(Direction::ServerToClient, result("2", "rainy")),
+      envelope(Direction::ClientToServer, call("3", "Oslo")),
+    ]);
+    let request =
+      |id: &str, city: &str| serde_json::from_value::<MCPRequest>(call(id, city)).unwrap();
+    let text = |r: Option<Value>| r.unwrap()["result"]["text"].clone();
+    assert_eq!(text(server.respond(&request("a", "Paris"))), "sunny");
+    assert_eq!(text(server.respond(&request("b", "Paris"))), "rainy");
+    assert_eq!(text(server.respond(&request("c", "Paris"))), "rainy");
+    assert!(server.respond(&request("d", "Oslo")).is_none());
+    assert_eq!(server.get_server_info().name, "nah-mock");
+  }
+}
@@ -0,0 +1,192 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * Non-interactive subcommands which run a single request against a single
+ * server, for shell scripts and CI. Results are printed to stdout as JSON,
+ * errors go to stderr and the exit code is the `code` of the `NahError`.
+ */
+use crate::command;
+use crate::config::NahConfig;
+use crate::history::Manifest;
+use crate::json_schema;
+use crate::mcp::MCPServer;
+use crate::startup::{self, ServerConfigs};
+use crate::types::NahError;
+use clap::Subcommand;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Subcommand)]
+pub enum OneShotCommand {
+  /// List tools of a server.
+  ListTools { server: String },
+  /// Call a tool and print its result.
+  CallTool {
+    server: String,
+    tool: String,
+    /// Arguments as a JSON object, or `@file.json` to read them from a file.
+    #[arg(long, conflicts_with = "pairs")]
+    args: Option<String>,
+    /// Arguments as `key=value` pairs.
+    pairs: Vec<String>,
+  },
+  /// List resources and resource templates of a server.
+  ListResources { server: String },
+  /// Read a resource and print its contents.
+  ReadResource { server: String, uri: String },
+  /// List prompts of a server.
+  ListPrompts { server: String },
+  /// Get a prompt and print its messages.
+  GetPrompt {
+    server: String,
+    prompt: String,
+    /// Arguments as a JSON object, or `@file.json` to read them from a file.
+    #[arg(long, conflicts_with = "pairs")]
+    args: Option<String>,
+    /// Arguments as `key=value` pairs.
+    pairs: Vec<String>,
+  },
+}
+
+impl OneShotCommand {
+  fn server_name(&self) -> &str {
+    match self {
+      OneShotCommand::ListTools { server }
+      | OneShotCommand::CallTool { server, .. }
+      | OneShotCommand::ListResources { server }
+      | OneShotCommand::ReadResource { server, .. }
+      | OneShotCommand::ListPrompts { server }
+      | OneShotCommand::GetPrompt { server, .. } => server,
+    }
+  }
+}
+
+/**
+ * Run a one-shot command and return the exit code of the process.
+ */
+pub fn run_one_shot(
+  config: &NahConfig,
+  history_path: Option<PathBuf>,
+  command: OneShotCommand,
+) -> i32 {
+  // Records go to a temporary folder unless users ask to keep them.
+  let (history_path, is_temporary) = match history_path {
+    Some(p) => (p, false),
+    None => (
+      std::env::temp_dir().join(format!("nah_{}", uuid::Uuid::new_v4())),
+      true,
+    ),
+  };
+  if let Err(e) = std::fs::create_dir_all(&history_path) {
+    return report_error(NahError::io_error(
+      &format!(
+        "Failed to create history folder: {}",
+        history_path.display()
+      ),
+      Some(Box::new(e)),
+    ));
+  }
+
+  if let Err(e) = Manifest::create(&history_path, &config.files) {
+    return report_error(e);
+  }
+  let result = start_server(config, command.server_name(), &history_path).and_then(|mut server| {
+    let result = execute(server.as_mut(), command);
+    let _ = server.kill();
+    result
+  });
+  if is_temporary {
+    let _ = std::fs::remove_dir_all(&history_path);
+  }
+  match result {
+    Ok(()) => 0,
+    Err(e) => report_error(e),
+  }
+}
+
+pub(crate) fn start_server(
+  config: &NahConfig,
+  server_name: &str,
+  history_path: &Path,
+) -> Result<Box<dyn MCPServer>, NahError> {
+  let configs = ServerConfigs {
+    local: &config.mcp_servers,
+    remote: &config.mcp_remote_servers,
+  };
+  Ok(startup::start_server(configs, server_name, history_path)?)
+}
+
+fn execute(server: &mut dyn MCPServer, request: OneShotCommand) -> Result<(), NahError> {
+  match request {
+    OneShotCommand::ListTools { .. } => {
+      let mut tools = server.fetch_tools()?;
+      tools.sort_by(|a, b| a.name.cmp(&b.name));
+      print_json(&tools);
+    }
+    OneShotCommand::CallTool {
+      tool, args, pairs, ..
+    } => {
+      let def = server.get_tool_definition(&tool)?;
+      let arguments =
+        command::parse_inline_tool_arguments(&inline_parts(&args, &pairs), &def.input_schema)?;
+      json_schema::validate_arguments(def, &arguments)?;
+      let result = server.call_tool(&tool, &arguments)?;
+      print_json(&result);
+      if result.get("isError").and_then(|v| v.as_bool()) == Some(true) {
+        return Err(NahError::tool_call_error(&tool));
+      }
+    }
+    OneShotCommand::ListResources { .. } => {
+      let mut resources: Vec<Value> = server
+        .fetch_resources_list()?
+        .into_iter()
+        .map(|r| serde_json::to_value(r).unwrap())
+        .collect();
+      resources.sort_by_key(|r| r["uri"].to_string());
+      let templates = server.fetch_resource_templates_list()?;
+      print_json(&serde_json::json!({
+        "resources": resources,
+        "resourceTemplates": templates,
+      }));
+    }
+    OneShotCommand::ReadResource { uri, .. } => {
+      print_json(&server.read_resources(&uri)?);
+    }
+    OneShotCommand::ListPrompts { .. } => {
+      let mut prompts = server.fetch_prompts_list()?;
+      prompts.sort_by(|a, b| a.name.cmp(&b.name));
+      print_json(&prompts);
+    }
+    OneShotCommand::GetPrompt {
+      prompt,
+      args,
+      pairs,
+      ..
+    } => {
+      server.get_prompt_definition(&prompt)?;
+      let arguments = command::parse_inline_prompt_arguments(&inline_parts(&args, &pairs))?;
+      print_json(&server.get_prompt_content(&prompt, &arguments)?);
+    }
+  }
+  Ok(())
+}
+
+fn inline_parts<'a>(args: &'a Option<String>, pairs: &'a [String]) -> Vec<&'a str> {
+  match args {
+    Some(args) => vec![args.as_str()],
+    None => pairs.iter().map(|p| p.as_str()).collect(),
+  }
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) {
+  println!("{}", serde_json::to_string_pretty(value).unwrap());
+}
+
+fn report_error(e: NahError) -> i32 {
+  eprintln!("{}", e);
+  e.code
+}
@@ -0,0 +1,110 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * Output modes of the nah shell. In text mode, commands print human readable
+ * text. In JSON mode, each command prints exactly one JSON document per line
+ * to stdout, and other messages go to stderr.
+ */
+use crate::types::NahError;
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum OutputMode {
+  #[default]
+  Text,
+  Json,
+}
+
+/**
+ * Print human readable output of a command. Nothing is printed in JSON mode.
+ */
+macro_rules! textln {
+  ($output:expr, $($arg:tt)*) => {
+    if $output == $crate::output::OutputMode::Text {
+      println!($($arg)*);
+    }
+  };
+}
+
+/**
+ * Print a status message, which goes to stderr in JSON mode to keep stdout
+ * parseable.
+ */
+macro_rules! logln {
+  ($output:expr, $($arg:tt)*) => {
+    match $output {
+      $crate::output::OutputMode::Text => println!($($arg)*),
+      $crate::output::OutputMode::Json => eprintln!($($arg)*),
+    }
+  };
+}
+
+/**
+ * Build the JSON document of a command result.
+ */
+pub fn json_document(command: &str, result: &Result<Value, NahError>) -> Value {
+  match result {
+    Ok(value) => json!({
+      "command": command,
+      "ok": true,
+      "result": value,
+    }),
+    Err(e) => json!({
+      "command": command,
+      "ok": false,
+      "error": error_to_json(e),
+    }),
+  }
+}
+
+/**
+ * Convert an error to `{code, message}`. Messages of the sources are appended.
+ */
+pub fn error_to_json(e: &NahError) -> Value {
+  let mut message = e.message.clone();
+  let mut source = std::error::Error::source(e);
+  while let Some(s) = source {
+    message.push_str(&format!("\ncaused by {}", s));
+    source = s.source();
+  }
+  json!({
+    "code": e.code,
+    "message": message,
+  })
+}
+
+/**
+ * Print an error of a usage problem and return it.
+ */
+pub fn usage_error(output: OutputMode, usage: &str) -> NahError {
+  textln!(output, "{}", usage);
+  NahError::invalid_command(usage)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_json_document() {
+    assert_eq!(
+      json_document("list_tools", &Ok(json!([{"name": "foo"}]))),
+      json!({"command": "list_tools", "ok": true, "result": [{"name": "foo"}]})
+    );
+    let e = NahError::io_error(
+      "Failed to open a.json",
+      Some(Box::new(std::io::Error::other("not found"))),
+    );
+    assert_eq!(
+      json_document("source", &Err(e)),
+      json!({
+        "command": "source",
+        "ok": false,
+        "error": {"code": 1, "message": "IO Error: Failed to open a.json\ncaused by not found"}
+      })
+    );
+  }
+}
@@ -0,0 +1,281 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * A transparent stdio proxy between a MCP client and a local server. All
+ * messages are forwarded unchanged and recorded in the history format, and
+ * messages matching a filter can be shown or paused on.
+ */
+use crate::config::NahConfig;
+use crate::history::{
+  is_error_message, Direction, Manifest, MethodTracker, TrafficRecorder, Transport,
+};
+use crate::mcp;
+use crate::types::NahError;
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/**
+ * Messages to show or pause on: `errors` matches error responses, and other
+ * filters match methods. A filter ending with `*` matches methods starting
+ * with the rest of it. A response matches the method of its request.
+ */
+#[derive(Debug, Clone)]
+pub enum MessageFilter {
+  Errors,
+  Method(String),
+}
+
+impl MessageFilter {
+  pub fn parse(filter: &str) -> MessageFilter {
+    match filter {
+      "errors" => MessageFilter::Errors,
+      _ => MessageFilter::Method(filter.to_string()),
+    }
+  }
+
+  fn matches(&self, message: &Value, method: Option<&str>) -> bool {
+    match self {
+      MessageFilter::Errors => is_error_message(message),
+      MessageFilter::Method(pattern) => match (pattern.strip_suffix('*'), method) {
+        (_, None) => false,
+        (Some(prefix), Some(method)) => method.starts_with(prefix),
+        (None, Some(method)) => method == pattern,
+      },
+    }
+  }
+}
+
+/**
+ * State shared by both directions of the proxy.
+ */
+struct ProxyState {
+  recorder: TrafficRecorder,
+  methods: MethodTracker,
+  show: Vec<MessageFilter>,
+  pause: Vec<MessageFilter>,
+  /// Terminal to ask whether to forward paused messages.
+  tty: Option<BufReader<File>>,
+}
+
+impl ProxyState {
+  /**
+   * Record a message and show or pause on it when it matches a filter.
+   */
+  fn inspect(&mut self, direction: Direction, line: &str) {
+    self.recorder.record_line(direction, line);
+    let message: Value = serde_json::from_str(line.trim_end()).unwrap_or(Value::Null);
+    let method = self.methods.method(direction, &message);
+    let method = method.as_deref();
+    let pause = self.pause.iter().any(|f| f.matches(&message, method));
+    if !pause && !self.show.iter().any(|f| f.matches(&message, method)) {
+      return;
+    }
+    let arrow = match direction {
+      Direction::ClientToServer => "client -> server",
+      Direction::ServerToClient => "server -> client",
+    };
+    let pretty = serde_json::to_string_pretty(&message).unwrap_or_else(|_| line.to_string());
+    eprintln!("[{}] {}\n{}", arrow, method.unwrap_or("-"), pretty);
+    if pause {
+      if let Some(tty) = &mut self.tty {
+        eprint!("Paused, press [ENTER] to forward this message.");
+        let mut buf = String::new();
+        let _ = tty.read_line(&mut buf);
+      }
+    }
+  }
+}
+
+/**
+ * Run the proxy of a local server until the client or the server exits.
+ * Returns the exit code.
+ */
+pub fn run_proxy(
+  config: &NahConfig,
+  server_name: &str,
+  history_path: Option<PathBuf>,
+  show: &[String],
+  pause: &[String],
+) -> i32 {
+  match proxy(config, server_name, history_path, show, pause) {
+    Ok(code) => code,
+    Err(e) => {
+      eprintln!("{}", e);
+      e.code
+    }
+  }
+}
+
+fn proxy(
+  config: &NahConfig,
+  server_name: &str,
+  history_path: Option<PathBuf>,
+  show: &[String],
+  pause: &[String],
+) -> Result<i32, NahError> {
+  let Some(command) = config.mcp_servers.get(server_name) else {
+    let message = if config.mcp_remote_servers.contains_key(server_name) {
+      format!(
+        "Server {} is a remote server, only local servers can be proxied",
+        server_name
+      )
+    } else {
+      format!("Server {} not found in the config file", server_name)
+    };
+    return Err(NahError::invalid_value(&message, None));
+  };
+  let history_path = history_path.unwrap_or_else(|| {
+    let timestamp = std::time::SystemTime::now()
+      .duration_since(std::time::SystemTime::UNIX_EPOCH)
+      .unwrap()
+      .as_secs();
+    PathBuf::from(format!("nah_proxy_{}", timestamp))
+  });
+  if let Err(e) = std::fs::create_dir_all(&history_path) {
+    return Err(NahError::io_error(
+      &format!(
+        "Failed to create history folder: {}",
+        history_path.display()
+      ),
+      Some(Box::new(e)),
+    ));
+  }
+  Manifest::create(&history_path, &config.files)?;
+  let stderr_file_name = format!("{}.stderr", server_name);
+  let recorder = TrafficRecorder::open(
+    &history_path,
+    server_name,
+    Transport::Stdio,
+    Some(&stderr_file_name),
+  )?;
+  let stderr_file_path = history_path.join(&stderr_file_name);
+  let stderr_file = match OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(&stderr_file_path)
+  {
+    Ok(f) => f,
+    Err(e) => {
+      return Err(NahError::io_error(
+        &format!(
+          "Failed to create stderr file: {}",
+          stderr_file_path.display()
+        ),
+        Some(Box::new(e)),
+      ));
+    }
+  };
+  let mut server_command = command.build_command();
+  server_command.stderr(Stdio::from(stderr_file));
+  let mut server_process = match server_command.spawn() {
+    Ok(p) => p,
+    Err(e) => {
+      return Err(NahError::mcp_server_process_launch_error(
+        server_name,
+        Some(Box::new(e)),
+      ));
+    }
+  };
+  mcp::register_process_group(server_process.id());
+  eprintln!(
+    "Proxying {}, history folder: {}",
+    server_name,
+    history_path.display()
+  );
+
+  let tty = if pause.is_empty() {
+    None
+  } else {
+    match File::open("/dev/tty") {
+      Ok(f) => Some(BufReader::new(f)),
+      Err(e) => {
+        eprintln!(
+          "Failed to open the terminal, messages will not be paused: {}",
+          e
+        );
+        None
+      }
+    }
+  };
+  let state = Arc::new(Mutex::new(ProxyState {
+    recorder,
+    methods: MethodTracker::default(),
+    show: show.iter().map(|f| MessageFilter::parse(f)).collect(),
+    pause: pause.iter().map(|f| MessageFilter::parse(f)).collect(),
+    tty,
+  }));
+
+  // Client to server. When the client closes stdin, the stdin of the server is
+  // closed as well so that it can exit.
+  let mut server_stdin = server_process.stdin.take().unwrap();
+  let client_state = state.clone();
+  thread::spawn(move || {
+    let stdin = std::io::stdin();
+    let mut buf = String::new();
+    loop {
+      buf.clear();
+      match stdin.read_line(&mut buf) {
+        Ok(0) | Err(_) => break,
+        Ok(_) => {}
+      }
+      client_state
+        .lock()
+        .unwrap()
+        .inspect(Direction::ClientToServer, &buf);
+      if server_stdin.write_all(buf.as_bytes()).is_err() || server_stdin.flush().is_err() {
+        break;
+      }
+    }
+  });
+
+  // Server to client, until the server closes stdout.
+  let mut server_stdout = BufReader::new(server_process.stdout.take().unwrap());
+  let mut stdout = std::io::stdout();
+  let mut buf = String::new();
+  loop {
+    buf.clear();
+    match server_stdout.read_line(&mut buf) {
+      Ok(0) | Err(_) => break,
+      Ok(_) => {}
+    }
+    state
+      .lock()
+      .unwrap()
+      .inspect(Direction::ServerToClient, &buf);
+    if stdout.write_all(buf.as_bytes()).is_err() || stdout.flush().is_err() {
+      break;
+    }
+  }
+  state.lock().unwrap().recorder.flush();
+  // The stdin of the server is closed by the client thread.
+  let status = mcp::stop_process(&mut server_process, None, command);
+  Ok(status.ok().and_then(|s| s.code()).unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn test_message_filter() {
+    let error = json!({"id": "1", "error": {"code": -32603}});
+    assert!(MessageFilter::parse("errors").matches(&error, Some("tools/call")));
+    assert!(MessageFilter::parse("tools/*").matches(&error, Some("tools/call")));
+    assert!(MessageFilter::parse("tools/call").matches(&error, Some("tools/call")));
+    assert!(!MessageFilter::parse("tools/list").matches(&error, Some("tools/call")));
+    assert!(!MessageFilter::parse("*").matches(&error, None));
+    let tool_error = json!({"id": "2", "result": {"content": [], "isError": true}});
+    assert!(MessageFilter::parse("errors").matches(&tool_error, Some("tools/call")));
+    let ok = json!({"id": "3", "result": {"content": []}});
+    assert!(!MessageFilter::parse("errors").matches(&ok, Some("tools/call")));
+  }
+}
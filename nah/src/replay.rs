@@ -0,0 +1,574 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * Regression testing of MCP servers: requests recorded in a history directory
+ * are sent again to restarted servers, and the responses are compared with
+ * the recorded ones.
+ */
+use crate::config::{self, NahConfig};
+use crate::history::{self, Direction, HistoryEntry, Manifest};
+use crate::mcp::{MCPRequest, MCPServer};
+use crate::oneshot;
+use crate::output::{self, OutputMode};
+use crate::secret;
+use crate::types::NahError;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/**
+ * Fields masked by default: ids are generated for each session.
+ */
+pub const DEFAULT_MASKS: &[&str] = &["/id"];
+
+/**
+ * Mask ignoring strings holding a date and time wherever they are, instead of
+ * a JSON pointer.
+ */
+pub const TIMESTAMPS_MASK: &str = "timestamps";
+
+/**
+ * Longest JSON value printed in a difference.
+ */
+const MAX_VALUE_LENGTH: usize = 80;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ReplayStatus {
+  Matched,
+  Differed,
+  Failed,
+  /// The recorded request has no response to compare with.
+  Skipped,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplayedRequest {
+  pub server: String,
+  pub method: String,
+  pub id: Value,
+  pub status: ReplayStatus,
+  /// Differences between the recorded and the new response, or the error.
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub details: Vec<String>,
+}
+
+/**
+ * Replay a history directory and print a summary. Servers are started from
+ * the config files recorded in the manifest, unless a config is given.
+ * Returns the exit code: 0 when all responses match.
+ */
+pub fn run_replay(
+  config_path: Option<PathBuf>,
+  history_dir: &Path,
+  history_path: Option<PathBuf>,
+  extra_masks: &[String],
+  output: OutputMode,
+) -> i32 {
+  let report_error = |e: NahError| {
+    match output {
+      OutputMode::Text => eprintln!("{}", e),
+      OutputMode::Json => println!("{}", output::json_document("replay", &Err(e))),
+    }
+    1
+  };
+  // Traffic of the replay is recorded like a session, in a temporary folder
+  // unless users ask to keep it.
+  let (replay_path, is_temporary) = match history_path {
+    Some(p) => (p, false),
+    None => (
+      std::env::temp_dir().join(format!("nah_replay_{}", uuid::Uuid::new_v4())),
+      true,
+    ),
+  };
+  let results = replay_session(config_path, history_dir, &replay_path, extra_masks, output);
+  if is_temporary {
+    let _ = std::fs::remove_dir_all(&replay_path);
+  }
+  let results = match results {
+    Ok(r) => r,
+    Err(e) => return report_error(e),
+  };
+
+  let count = |status: ReplayStatus| results.iter().filter(|r| r.status == status).count();
+  let (matched, differed, failed, skipped) = (
+    count(ReplayStatus::Matched),
+    count(ReplayStatus::Differed),
+    count(ReplayStatus::Failed),
+    count(ReplayStatus::Skipped),
+  );
+  let passed = differed == 0 && failed == 0;
+  match output {
+    OutputMode::Text => println!(
+      "{} request(s) replayed: {} matched, {} differed, {} failed, {} skipped",
+      results.len(),
+      matched,
+      differed,
+      failed,
+      skipped
+    ),
+    OutputMode::Json => println!(
+      "{}",
+      serde_json::json!({
+        "ok": passed,
+        "matched": matched,
+        "differed": differed,
+        "failed": failed,
+        "skipped": skipped,
+        "requests": results,
+      })
+    ),
+  }
+  if passed {
+    0
+  } else {
+    1
+  }
+}
+
+/**
+ * Replay the requests of all servers in a history directory, recording the
+ * new traffic in `replay_path`.
+ */
+fn replay_session(
+  config_path: Option<PathBuf>,
+  history_dir: &Path,
+  replay_path: &Path,
+  extra_masks: &[String],
+  output: OutputMode,
+) -> Result<Vec<ReplayedRequest>, NahError> {
+  let manifest = Manifest::load(history_dir)?;
+  let config = match config_path {
+    Some(path) => config::load_config(path, false),
+    None => config::load_config_files(manifest.config_files.clone(), false),
+  };
+  let config = config?;
+  let entries = history::load_session(history_dir)?;
+  if let Err(e) = std::fs::create_dir_all(replay_path) {
+    return Err(NahError::io_error(
+      &format!("Failed to create history folder: {}", replay_path.display()),
+      Some(Box::new(e)),
+    ));
+  }
+  Manifest::create(replay_path, &config.files)?;
+
+  let masks: Vec<&str> = DEFAULT_MASKS
+    .iter()
+    .copied()
+    .chain(extra_masks.iter().map(|m| m.as_str()))
+    .collect();
+  let mut results = Vec::new();
+  for server_name in manifest.servers.keys() {
+    let server_entries: Vec<&HistoryEntry> = entries
+      .iter()
+      .filter(|e| e.envelope.server == *server_name)
+      .collect();
+    results.extend(replay_server(
+      &config,
+      server_name,
+      &server_entries,
+      replay_path,
+      &masks,
+      output,
+    ));
+  }
+  Ok(results)
+}
+
+/**
+ * Restart a server and send its recorded requests in order. The initialize
+ * handshake is done when the server starts, so it isn't sent again.
+ */
+fn replay_server(
+  config: &NahConfig,
+  server_name: &str,
+  entries: &[&HistoryEntry],
+  replay_path: &Path,
+  masks: &[&str],
+  output: OutputMode,
+) -> Vec<ReplayedRequest> {
+  let requests: Vec<&HistoryEntry> = entries
+    .iter()
+    .copied()
+    .filter(|e| {
+      e.is_request()
+        && e.envelope.direction == Direction::ClientToServer
+        && e.method.as_deref() != Some("initialize")
+    })
+    .collect();
+  let responses: HashMap<String, &Value> = entries
+    .iter()
+    .filter(|e| e.envelope.direction == Direction::ServerToClient && !e.is_request())
+    .filter_map(|e| e.id().map(|id| (id.to_string(), &e.envelope.message)))
+    .collect();
+  if requests.is_empty() {
+    return Vec::new();
+  }
+  textln!(
+    output,
+    "Replaying {} request(s) of {}",
+    requests.len(),
+    server_name
+  );
+
+  let mut server = match oneshot::start_server(config, server_name, replay_path) {
+    Ok(s) => Some(s),
+    Err(e) => {
+      textln!(output, "Failed to start {}: {}", server_name, e);
+      None
+    }
+  };
+  let mut results = Vec::new();
+  for request in requests {
+    let message = &request.envelope.message;
+    let id = message["id"].clone();
+    let mut result = ReplayedRequest {
+      server: server_name.to_string(),
+      method: request.method.clone().unwrap_or_default(),
+      id: id.clone(),
+      status: ReplayStatus::Matched,
+      details: Vec::new(),
+    };
+    let recorded = responses.get(&id.to_string());
+    match (&mut server, recorded) {
+      (None, _) => {
+        result.status = ReplayStatus::Failed;
+        result.details.push("server is not started".to_string());
+      }
+      (Some(_), None) => {
+        result.status = ReplayStatus::Skipped;
+        result.details.push("no recorded response".to_string());
+      }
+      (Some(_), Some(_)) if secret::contains_mask(message) => {
+        // Secrets are masked in history files, so the request can't be sent
+        // as it was.
+        result.status = ReplayStatus::Skipped;
+        result
+          .details
+          .push("the request holds a masked secret".to_string());
+      }
+      (Some(server), Some(recorded)) => match send(server.as_mut(), message) {
+        Ok(replayed) => {
+          result.details = compare(recorded, &replayed, masks);
+          if !result.details.is_empty() {
+            result.status = ReplayStatus::Differed;
+          }
+        }
+        Err(e) => {
+          result.status = ReplayStatus::Failed;
+          result.details.push(e.to_string());
+        }
+      },
+    }
+    textln!(
+      output,
+      "  {:<8} {}",
+      match result.status {
+        ReplayStatus::Matched => "ok",
+        ReplayStatus::Differed => "DIFF",
+        ReplayStatus::Failed => "FAIL",
+        ReplayStatus::Skipped => "skip",
+      },
+      result.method
+    );
+    for detail in result.details.iter() {
+      textln!(output, "           {}", detail);
+    }
+    results.push(result);
+  }
+  if let Some(mut server) = server {
+    let _ = server.kill();
+  }
+  results
+}
+
+fn send(server: &mut dyn MCPServer, message: &Value) -> Result<Value, NahError> {
+  let request: MCPRequest = match serde_json::from_value(message.clone()) {
+    Ok(r) => r,
+    Err(e) => {
+      return Err(NahError::invalid_value(
+        "invalid request in the history",
+        Some(Box::new(e)),
+      ))
+    }
+  };
+  let response = server.send_and_wait_for_response(request)?;
+  Ok(serde_json::to_value(response).unwrap())
+}
+
+/**
+ * Compare two responses after removing masked fields, and describe the
+ * differences.
+ */
+pub fn compare(recorded: &Value, replayed: &Value, masks: &[&str]) -> Vec<String> {
+  let mut recorded = recorded.clone();
+  let mut replayed = replayed.clone();
+  let mut ignore_timestamps = false;
+  for mask in masks {
+    if *mask == TIMESTAMPS_MASK {
+      ignore_timestamps = true;
+      continue;
+    }
+    let segments = pointer_segments(mask);
+    remove_pointer(&mut recorded, &segments);
+    remove_pointer(&mut replayed, &segments);
+  }
+  let mut differences = Vec::new();
+  diff_values(
+    "",
+    &recorded,
+    &replayed,
+    ignore_timestamps,
+    &mut differences,
+  );
+  differences
+}
+
+/**
+ * Split a JSON pointer into unescaped segments.
+ */
+fn pointer_segments(pointer: &str) -> Vec<String> {
+  pointer
+    .split('/')
+    .skip(1)
+    .map(|s| s.replace("~1", "/").replace("~0", "~"))
+    .collect()
+}
+
+/**
+ * Remove the values at a JSON pointer, where a `*` segment matches all keys
+ * or items.
+ */
+fn remove_pointer(value: &mut Value, segments: &[String]) {
+  let Some((first, rest)) = segments.split_first() else {
+    return;
+  };
+  match value {
+    Value::Object(obj) => {
+      if rest.is_empty() {
+        if first == "*" {
+          obj.clear();
+        } else {
+          obj.remove(first);
+        }
+      } else if first == "*" {
+        obj.values_mut().for_each(|v| remove_pointer(v, rest));
+      } else if let Some(v) = obj.get_mut(first) {
+        remove_pointer(v, rest);
+      }
+    }
+    Value::Array(items) => {
+      if first == "*" {
+        if rest.is_empty() {
+          items.clear();
+        } else {
+          items.iter_mut().for_each(|v| remove_pointer(v, rest));
+        }
+      } else if let Ok(idx) = first.parse::<usize>() {
+        if rest.is_empty() {
+          // Keep positions of other items
+          if let Some(v) = items.get_mut(idx) {
+            *v = Value::Null;
+          }
+        } else if let Some(v) = items.get_mut(idx) {
+          remove_pointer(v, rest);
+        }
+      }
+    }
+    _ => {}
+  }
+}
+
+fn diff_values(
+  path: &str,
+  recorded: &Value,
+  replayed: &Value,
+  ignore_timestamps: bool,
+  differences: &mut Vec<String>,
+) {
+  match (recorded, replayed) {
+    (Value::Object(a), Value::Object(b)) => {
+      for (key, value) in a.iter() {
+        let child = format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+        match b.get(key) {
+          Some(other) => diff_values(&child, value, other, ignore_timestamps, differences),
+          None => differences.push(format!("{}: removed, was {}", child, brief(value))),
+        }
+      }
+      for (key, value) in b.iter() {
+        if !a.contains_key(key) {
+          let child = format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+          differences.push(format!("{}: added {}", child, brief(value)));
+        }
+      }
+    }
+    (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+      for (idx, (x, y)) in a.iter().zip(b.iter()).enumerate() {
+        diff_values(
+          &format!("{}/{}", path, idx),
+          x,
+          y,
+          ignore_timestamps,
+          differences,
+        );
+      }
+    }
+    (Value::String(a), Value::String(b))
+      if ignore_timestamps && is_timestamp(a) && is_timestamp(b) => {}
+    _ if recorded != replayed => differences.push(format!(
+      "{}: {} -> {}",
+      if path.is_empty() { "/" } else { path },
+      brief(recorded),
+      brief(replayed)
+    )),
+    _ => {}
+  }
+}
+
+/**
+ * Whether a string is a date and time like `2025-01-01T12:34:56.789Z` or
+ * `2025-01-01 12:34`.
+ */
+fn is_timestamp(s: &str) -> bool {
+  let bytes = s.as_bytes();
+  if bytes.len() < 16 {
+    return false;
+  }
+  let pattern_matches = bytes[..16].iter().enumerate().all(|(idx, b)| match idx {
+    4 | 7 => *b == b'-',
+    10 => *b == b'T' || *b == b' ',
+    13 => *b == b':',
+    _ => b.is_ascii_digit(),
+  });
+  pattern_matches
+    && bytes[16..]
+      .iter()
+      .all(|b| b.is_ascii_digit() || b":.+-Z".contains(b))
+}
+
+fn brief(value: &Value) -> String {
+  let text = value.to_string();
+  if text.chars().count() > MAX_VALUE_LENGTH {
+    format!(
+      "{}...",
+      text.chars().take(MAX_VALUE_LENGTH).collect::<String>()
+    )
+  } else {
+    text
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn test_compare() {
+    let recorded = json!({
+      "id": "a",
+      "result": {"content": [{"text": "sunny", "time": 1}], "isError": false}
+    });
+    let replayed = json!({
+      "id": "b",
+      "result": {"content": [{"text": "rainy", "time": 2}], "extra": true}
+    });
+    assert_eq!(
+      compare(&recorded, &replayed, &["/id", "/result/content/*/time"]),
+      vec![
+        "/result/content/0/text: \"sunny\" -> \"rainy\"",
+        "/result/isError: removed, was false",
+        "/result/extra: added true",
+      ]
+    );
+    assert!(compare(&recorded, &recorded, DEFAULT_MASKS).is_empty());
+    assert_eq!(compare(&recorded, &replayed, &["/*"]), Vec::<String>::new());
+    let recorded = json!({"updatedAt": "2024-01-01T00:00:00Z", "day": "2024-01-01"});
+    let replayed = json!({"updatedAt": "2025-06-01 10:00:00.123+02:00", "day": "2025-06-01"});
+    assert_eq!(compare(&recorded, &replayed, DEFAULT_MASKS).len(), 2);
+    assert_eq!(
+      compare(&recorded, &replayed, &["/id", TIMESTAMPS_MASK]),
+      vec!["/day: \"2024-01-01\" -> \"2025-06-01\""]
+    );
+  }
+
+  /**
+   * A server answering `tools/list` with the current time, and any other
+   * request with an empty resource list.
+   */
+  const SERVER_SCRIPT: &str = r#"while read -r line; do
+  id=$(printf '%s' "$line" | sed -nE 's/.*"id":("[^"]*"|[0-9]+).*/\1/p')
+  [ -n "$id" ] || continue
+  case "$line" in
+    *'"tools/list"'*) printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[],"at":"%s"}}\n' "$id" "$(date -u +%Y-%m-%dT%H:%M:%SZ)";;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{"resources":[]}}\n' "$id";;
+  esac
+done"#;
+
+  #[test]
+  fn test_run_replay() {
+    let dir = std::env::temp_dir().join(format!("nah_test_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config_path = dir.join("config.json");
+    let config = json!({"mcpServers": {"sh": {"command": "sh", "args": ["-c", SERVER_SCRIPT]}}});
+    std::fs::write(&config_path, config.to_string()).unwrap();
+    Manifest::create(&dir, &[config_path]).unwrap();
+    let mut recorder =
+      history::TrafficRecorder::open(&dir, "sh", history::Transport::Stdio, None).unwrap();
+    let mut exchange = |id: &str, method: &str, params: Value, result: Value| {
+      recorder.record_sent(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}));
+      recorder.record_received(&json!({"jsonrpc": "2.0", "id": id, "result": result}).to_string());
+    };
+    exchange("0", "initialize", json!({}), json!({}));
+    exchange(
+      "1",
+      "tools/list",
+      json!({}),
+      json!({"tools": [], "at": "2024-01-01T00:00:00Z"}),
+    );
+    exchange(
+      "2",
+      "tools/call",
+      json!({"name": "login", "arguments": {"token": "********"}}),
+      json!({"content": []}),
+    );
+    exchange(
+      "3",
+      "resources/list",
+      json!({}),
+      json!({"resources": [{"uri": "file:///a"}]}),
+    );
+    recorder.flush();
+
+    let replay_path = dir.join("replay");
+    let masks = [TIMESTAMPS_MASK.to_string()];
+    let results = replay_session(None, &dir, &replay_path, &masks, OutputMode::Json).unwrap();
+    let statuses: Vec<(&str, ReplayStatus)> = results
+      .iter()
+      .map(|r| (r.method.as_str(), r.status))
+      .collect();
+    assert_eq!(
+      statuses,
+      vec![
+        ("tools/list", ReplayStatus::Matched),
+        ("tools/call", ReplayStatus::Skipped),
+        ("resources/list", ReplayStatus::Differed),
+      ]
+    );
+    assert_eq!(
+      results[2].details,
+      vec!["/result/resources: [{\"uri\":\"file:///a\"}] -> []"]
+    );
+    assert_eq!(run_replay(None, &dir, None, &masks, OutputMode::Json), 1);
+    let masks = [TIMESTAMPS_MASK.to_string(), "/result/resources".to_string()];
+    assert_eq!(run_replay(None, &dir, None, &masks, OutputMode::Json), 0);
+    // The time of tools/list differs without the timestamps mask.
+    let masks = ["/result/resources".to_string()];
+    assert_eq!(run_replay(None, &dir, None, &masks, OutputMode::Json), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+}
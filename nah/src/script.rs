@@ -0,0 +1,171 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * Scripts of nah shell commands: loading script files, variables and
+ * assertions.
+ */
+use crate::types::NahError;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/**
+ * What to do when a command of a script fails.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OnError {
+  #[default]
+  Stop,
+  Continue,
+}
+
+/**
+ * Read a script file into `(line number, command)` pairs. Empty lines and
+ * lines starting with `#` are skipped, and a line ending with `\` continues on
+ * the next line.
+ */
+pub fn read_script(path: &Path) -> Result<Vec<(usize, String)>, NahError> {
+  let content = match std::fs::read_to_string(path) {
+    Ok(c) => c,
+    Err(e) => {
+      return Err(NahError::io_error(
+        &format!("Failed to read script {}", path.display()),
+        Some(Box::new(e)),
+      ))
+    }
+  };
+  let mut commands = Vec::new();
+  let mut pending: Option<(usize, String)> = None;
+  for (idx, line) in content.lines().enumerate() {
+    let (line_no, mut command) = match pending.take() {
+      Some((line_no, command)) => (line_no, command + "\n" + line),
+      None => {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+          continue;
+        }
+        (idx + 1, line.to_string())
+      }
+    };
+    if command.ends_with('\\') {
+      command.pop();
+      pending = Some((line_no, command));
+    } else {
+      commands.push((line_no, command));
+    }
+  }
+  commands.extend(pending);
+  Ok(commands)
+}
+
+/**
+ * Look up an expression such as `last.content[0].text`.
+ */
+pub fn lookup_variable<'a>(
+  variables: &'a HashMap<String, Value>,
+  expr: &str,
+) -> Result<&'a Value, NahError> {
+  let undefined = || NahError::invalid_value(&format!("Undefined variable: ${}", expr), None);
+  let name_end = expr.find(['.', '[']).unwrap_or(expr.len());
+  let mut value = variables.get(&expr[..name_end]).ok_or_else(undefined)?;
+  let mut rest = &expr[name_end..];
+  while !rest.is_empty() {
+    if let Some(after_dot) = rest.strip_prefix('.') {
+      let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+      value = value.get(&after_dot[..end]).ok_or_else(undefined)?;
+      rest = &after_dot[end..];
+    } else if let Some(after_bracket) = rest.strip_prefix('[') {
+      let end = after_bracket.find(']').ok_or_else(undefined)?;
+      let idx: usize = after_bracket[..end].parse().map_err(|_| undefined())?;
+      value = value.get(idx).ok_or_else(undefined)?;
+      rest = &after_bracket[end + 1..];
+    } else {
+      return Err(undefined());
+    }
+  }
+  Ok(value)
+}
+
+/**
+ * Text of a value inserted in a command: strings as-is and other values as
+ * JSON.
+ */
+pub fn render_value(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.to_owned(),
+    v => v.to_string(),
+  }
+}
+
+/**
+ * Read a word as a JSON value, falling back to a string.
+ */
+pub fn parse_value(word: &str) -> Value {
+  serde_json::from_str(word).unwrap_or_else(|_| Value::String(word.to_string()))
+}
+
+/**
+ * Evaluate `<left> <op> <right>` where `op` is `==`, `!=` or `contains`.
+ * Return a message explaining the failure if the assertion does not hold.
+ */
+pub fn check_assertion(operands: &[&str]) -> Result<(), String> {
+  let [left, op, right] = operands else {
+    return Err("Usage: assert [value] [== | != | contains] [value]".to_string());
+  };
+  let (left, right) = (parse_value(left), parse_value(right));
+  let holds = match *op {
+    "==" => left == right,
+    "!=" => left != right,
+    "contains" => match &left {
+      Value::String(s) => s.contains(&render_value(&right)),
+      Value::Array(items) => items.contains(&right),
+      Value::Object(obj) => right.as_str().is_some_and(|key| obj.contains_key(key)),
+      _ => false,
+    },
+    _ => return Err(format!("Unknown assertion operator: {}", op)),
+  };
+  if holds {
+    Ok(())
+  } else {
+    Err(format!("Assertion failed: {} {} {}", left, op, right))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_check_assertion() {
+    assert!(check_assertion(&["42", "==", "42"]).is_ok());
+    assert!(check_assertion(&["hello world", "contains", "world"]).is_ok());
+    assert!(check_assertion(&["[1, 2]", "contains", "2"]).is_ok());
+    assert!(check_assertion(&["true", "!=", "false"]).is_ok());
+    assert!(check_assertion(&["a", "==", "b"]).is_err());
+    assert!(check_assertion(&["a", "=~", "b"]).is_err());
+    assert!(check_assertion(&["a"]).is_err());
+  }
+
+  #[test]
+  fn test_read_script() {
+    let path = std::env::temp_dir().join(format!("nah_script_{}.nah", std::process::id()));
+    std::fs::write(
+      &path,
+      "# comment\nuse weather\n\ncall_tool forecast \\\n  city=Paris\nassert $last.isError == false\n",
+    )
+    .unwrap();
+    let commands = read_script(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(
+      commands,
+      vec![
+        (2, "use weather".to_string()),
+        (4, "call_tool forecast \n  city=Paris".to_string()),
+        (6, "assert $last.isError == false".to_string()),
+      ]
+    );
+  }
+}
@@ -0,0 +1,247 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * Secrets in configs, which are plain text or references resolved when a
+ * connection is made. Resolved values are masked in history files.
+ */
+use crate::types::NahError;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Mutex;
+
+const MASK: &str = "********";
+
+/**
+ * Secrets shorter than this are not masked, as they would mask unrelated text.
+ */
+const MIN_MASKED_LENGTH: usize = 4;
+
+/**
+ * Values of all secrets resolved so far, by their references.
+ */
+static RESOLVED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+#[derive(Clone, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum SecretValue {
+  Plain(String),
+  /// `{"env": "OPENAI_API_KEY"}`
+  Env {
+    env: String,
+  },
+  /// `{"file": "~/.secrets/openai"}`
+  File {
+    file: String,
+  },
+  /// `{"command": ["pass", "show", "openai"]}`
+  Command {
+    command: Vec<String>,
+  },
+}
+
+impl fmt::Debug for SecretValue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SecretValue::Plain(_) => write!(f, "{:?}", MASK),
+      _ => write!(f, "{}", self.reference()),
+    }
+  }
+}
+
+impl SecretValue {
+  /**
+   * Description of the secret without its value.
+   */
+  fn reference(&self) -> String {
+    match self {
+      SecretValue::Plain(_) => "plain text".to_string(),
+      SecretValue::Env { env } => format!("{{env: {}}}", env),
+      SecretValue::File { file } => format!("{{file: {}}}", file),
+      SecretValue::Command { command } => format!("{{command: {}}}", command.join(" ")),
+    }
+  }
+
+  /**
+   * Get the value of the secret. Values of references are cached, so that a
+   * command is run only once, and masked in history files. Plain values are
+   * not masked: they are also used for headers like `Accept`, whose values
+   * appear in messages.
+   */
+  pub fn resolve(&self) -> Result<String, NahError> {
+    let reference = match self {
+      SecretValue::Plain(value) => return Ok(value.clone()),
+      _ => self.reference(),
+    };
+    if let Some((_, value)) = RESOLVED
+      .lock()
+      .unwrap()
+      .iter()
+      .find(|(r, _)| *r == reference)
+    {
+      return Ok(value.clone());
+    }
+    let value = match self {
+      SecretValue::Plain(_) => unreachable!(),
+      SecretValue::Env { env } => match std::env::var(env) {
+        Ok(v) => v,
+        Err(e) => {
+          return Err(NahError::secret_resolution_error(
+            &reference,
+            Some(Box::new(e)),
+          ))
+        }
+      },
+      SecretValue::File { file } => match std::fs::read_to_string(expand_home(file)) {
+        Ok(v) => v.trim_end_matches(['\r', '\n']).to_string(),
+        Err(e) => {
+          return Err(NahError::secret_resolution_error(
+            &reference,
+            Some(Box::new(e)),
+          ))
+        }
+      },
+      SecretValue::Command { command } => run_secret_command(&reference, command)?,
+    };
+    remember(&reference, &value);
+    Ok(value)
+  }
+}
+
+fn remember(reference: &str, value: &str) {
+  let mut resolved = RESOLVED.lock().unwrap();
+  if !resolved.iter().any(|(r, _)| r == reference) {
+    resolved.push((reference.to_string(), value.to_string()));
+  }
+}
+
+fn expand_home(path: &str) -> std::path::PathBuf {
+  match (path.strip_prefix("~/"), dirs::home_dir()) {
+    (Some(rest), Some(home)) => home.join(rest),
+    _ => std::path::PathBuf::from(path),
+  }
+}
+
+fn run_secret_command(reference: &str, command: &[String]) -> Result<String, NahError> {
+  let Some((program, args)) = command.split_first() else {
+    return Err(NahError::secret_resolution_error(reference, None));
+  };
+  let output = match std::process::Command::new(program)
+    .args(args)
+    .stdin(std::process::Stdio::inherit())
+    .stderr(std::process::Stdio::inherit())
+    .output()
+  {
+    Ok(o) => o,
+    Err(e) => {
+      return Err(NahError::secret_resolution_error(
+        reference,
+        Some(Box::new(e)),
+      ))
+    }
+  };
+  if !output.status.success() {
+    return Err(NahError::secret_resolution_error(
+      reference,
+      Some(Box::new(std::io::Error::other(format!(
+        "command exited with {}",
+        output.status
+      )))),
+    ));
+  }
+  let value = String::from_utf8_lossy(&output.stdout);
+  // Tools like `pass` print the secret on the first line
+  Ok(value.lines().next().unwrap_or_default().to_string())
+}
+
+/**
+ * Replace values of resolved secrets in the strings of a JSON value with a
+ * mask. Other values and object keys are kept, so the value stays the same
+ * apart from the secrets.
+ */
+pub fn mask(value: &mut Value) {
+  let resolved = RESOLVED.lock().unwrap();
+  let secrets: Vec<&str> = resolved
+    .iter()
+    .map(|(_, v)| v.as_str())
+    .filter(|v| v.len() >= MIN_MASKED_LENGTH)
+    .collect();
+  if !secrets.is_empty() {
+    mask_strings(value, &secrets);
+  }
+}
+
+/**
+ * Whether a string of a JSON value holds a masked secret.
+ */
+pub fn contains_mask(value: &Value) -> bool {
+  match value {
+    Value::String(s) => s.contains(MASK),
+    Value::Array(items) => items.iter().any(contains_mask),
+    Value::Object(obj) => obj.values().any(contains_mask),
+    _ => false,
+  }
+}
+
+fn mask_strings(value: &mut Value, secrets: &[&str]) {
+  match value {
+    Value::String(s) => {
+      for secret in secrets {
+        if s.contains(secret) {
+          *s = s.replace(secret, MASK);
+        }
+      }
+    }
+    Value::Array(items) => items.iter_mut().for_each(|v| mask_strings(v, secrets)),
+    Value::Object(obj) => obj.values_mut().for_each(|v| mask_strings(v, secrets)),
+    _ => {}
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_resolve_and_mask() {
+    let secret: SecretValue =
+      serde_json::from_str(r#"{"command": ["echo", "s3cr3t-value"]}"#).unwrap();
+    assert_eq!(format!("{:?}", secret), "{command: echo s3cr3t-value}");
+    assert_eq!(secret.resolve().unwrap(), "s3cr3t-value");
+    let mut message = serde_json::json!({"token": "Bearer s3cr3t-value", "count": 1});
+    mask(&mut message);
+    assert_eq!(
+      message,
+      serde_json::json!({"token": "Bearer ********", "count": 1})
+    );
+
+    let plain: SecretValue = serde_json::from_str(r#""Bearer abc""#).unwrap();
+    assert_eq!(format!("{:?}", plain), "\"********\"");
+    let missing = SecretValue::Env {
+      env: "NAH_SURELY_MISSING_SECRET".to_string(),
+    };
+    assert!(missing.resolve().is_err());
+  }
+
+  #[test]
+  fn test_plain_headers_are_not_masked() {
+    for value in ["application/json", "true"] {
+      let header = SecretValue::Plain(value.to_string());
+      assert_eq!(header.resolve().unwrap(), value);
+    }
+    let mut message = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": "1",
+      "result": {
+        "contents": [{"uri": "file:///a", "mimeType": "application/json", "text": "{}"}],
+        "isError": true
+      }
+    });
+    let original = message.clone();
+    mask(&mut message);
+    assert_eq!(message, original);
+  }
+}
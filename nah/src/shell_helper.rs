@@ -0,0 +1,264 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * Completion and hints for the nah shell.
+ */
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+/**
+ * Commands of the nah shell with the usage of their arguments.
+ */
+pub(crate) const COMMANDS: &[(&str, &str)] = &[
+  ("help", ""),
+  ("use", "[server_name]"),
+  ("exit", ""),
+  ("list_servers", ""),
+  ("restart_server", "[server_name]"),
+  ("server_info", "[server_name]"),
+  ("list_tools", ""),
+  ("inspect_tool", "[tool name]"),
+  (
+    "call_tool",
+    "[tool name] [JSON | @file.json | key=value ...] [--form | --editor]",
+  ),
+  ("list_resources", ""),
+  ("inspect_resources", "[Resource URI]"),
+  ("read_resources", "[Resource URI]"),
+  ("list_prompts", ""),
+  ("inspect_prompt", "[Prompt name]"),
+  (
+    "get_prompt",
+    "[Prompt name] [JSON | @file.json | key=value ...] [--form | --editor]",
+  ),
+  ("set_timeout", "[timeout in milliseconds]"),
+  ("chat", "[model profile]"),
+  ("source", "[script file]"),
+  ("let", "[name] = [value]"),
+  ("assert", "[value] [== | != | contains] [value]"),
+  ("set", "[on_error | output] [value]"),
+  ("history", "[list | show | resend] [n]"),
+];
+
+/**
+ * Names known by the shell, refreshed before reading each command.
+ */
+#[derive(Debug, Default)]
+pub struct CompletionCandidates {
+  pub servers: Vec<String>,
+  pub tools: Vec<String>,
+  pub prompts: Vec<String>,
+  pub resource_uris: Vec<String>,
+  pub models: Vec<String>,
+}
+
+pub struct NahHelper {
+  pub candidates: CompletionCandidates,
+  filename_completer: FilenameCompleter,
+}
+
+impl NahHelper {
+  pub fn new() -> Self {
+    NahHelper {
+      candidates: CompletionCandidates::default(),
+      filename_completer: FilenameCompleter::new(),
+    }
+  }
+
+  /**
+   * Candidates for the word at `word_idx` of a command.
+   */
+  fn candidates_for(&self, command: &str, word_idx: usize, words: &[&str]) -> Vec<String> {
+    match (command, word_idx) {
+      (_, 0) => COMMANDS.iter().map(|(c, _)| c.to_string()).collect(),
+      ("use" | "restart_server" | "server_info", 1) => self.candidates.servers.clone(),
+      ("inspect_tool" | "call_tool", 1) => self.candidates.tools.clone(),
+      ("inspect_prompt" | "get_prompt", 1) => self.candidates.prompts.clone(),
+      ("inspect_resources" | "read_resources", 1) => self.candidates.resource_uris.clone(),
+      ("chat", 1) => self.candidates.models.clone(),
+      ("call_tool" | "get_prompt", _) => vec!["--form".to_string(), "--editor".to_string()],
+      ("set", 1) => vec!["on_error".to_string(), "output".to_string()],
+      ("history", 1) => vec!["list".to_string(), "show".to_string(), "resend".to_string()],
+      ("history", _) if words.get(1) == Some(&"list") => match words.get(word_idx - 1) {
+        Some(&"--server") => self.candidates.servers.clone(),
+        _ => vec![
+          "--server".to_string(),
+          "--method".to_string(),
+          "--errors".to_string(),
+        ],
+      },
+      ("set", 2) if words.get(1) == Some(&"on_error") => {
+        vec!["stop".to_string(), "continue".to_string()]
+      }
+      ("set", 2) if words.get(1) == Some(&"output") => vec!["text".to_string(), "json".to_string()],
+      _ => Vec::new(),
+    }
+  }
+}
+
+/**
+ * Quote a candidate if it would be split into several words.
+ */
+fn quote_if_needed(candidate: &str) -> String {
+  if candidate.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'' || c == '\\') {
+    format!(
+      "\"{}\"",
+      candidate.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+  } else {
+    candidate.to_string()
+  }
+}
+
+impl Completer for NahHelper {
+  type Candidate = Pair;
+
+  fn complete(
+    &self,
+    line: &str,
+    pos: usize,
+    ctx: &Context<'_>,
+  ) -> rustyline::Result<(usize, Vec<Pair>)> {
+    let before = &line[..pos];
+    let start = before
+      .rfind(char::is_whitespace)
+      .map(|idx| idx + 1)
+      .unwrap_or(0);
+    let words: Vec<&str> = before[..start].split_whitespace().collect();
+    let prefix = &before[start..];
+    let command = words.first().copied().unwrap_or("");
+    if command == "source" || prefix.starts_with('@') {
+      return self.filename_completer.complete(line, pos, ctx);
+    }
+    // Quoted prefixes such as `"file:///a b` are matched without the quote.
+    let unquoted_prefix = prefix.trim_start_matches('"');
+    let pairs = self
+      .candidates_for(command, words.len(), &words)
+      .into_iter()
+      .filter(|c| c.starts_with(unquoted_prefix))
+      .map(|c| Pair {
+        replacement: quote_if_needed(&c),
+        display: c,
+      })
+      .collect();
+    Ok((start, pairs))
+  }
+}
+
+impl Hinter for NahHelper {
+  type Hint = String;
+
+  /**
+   * Show the rest of the command name and its usage while typing the first
+   * word, and the usage right after the command name.
+   */
+  fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+    if pos < line.len() || line.is_empty() {
+      return None;
+    }
+    let mut words = line.split_whitespace();
+    let first = words.next()?;
+    if words.next().is_some() {
+      return None;
+    }
+    if line.ends_with(char::is_whitespace) {
+      let (_, usage) = COMMANDS.iter().find(|(c, _)| *c == first)?;
+      return (!usage.is_empty()).then(|| usage.to_string());
+    }
+    let mut matched = COMMANDS.iter().filter(|(c, _)| c.starts_with(first));
+    let (command, usage) = matched.next()?;
+    if matched.next().is_some() {
+      return None;
+    }
+    let rest = &command[first.len()..];
+    Some(match usage.is_empty() {
+      true => rest.to_string(),
+      false => format!("{} {}", rest, usage),
+    })
+  }
+}
+
+impl Highlighter for NahHelper {
+  fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+    // Dim hints so that they are not confused with the input
+    Cow::Owned(format!("\x1b[2m{}\x1b[0m", hint))
+  }
+}
+
+impl Validator for NahHelper {}
+
+impl Helper for NahHelper {}
+
+/**
+ * Path of the file keeping the history of the shell across sessions.
+ */
+pub fn history_file_path() -> Option<PathBuf> {
+  let dir = dirs::data_dir()?.join("nah");
+  std::fs::create_dir_all(&dir).ok()?;
+  Some(dir.join("history.txt"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rustyline::history::DefaultHistory;
+
+  fn complete(helper: &NahHelper, line: &str) -> (usize, Vec<String>) {
+    let history = DefaultHistory::new();
+    let ctx = Context::new(&history);
+    let (start, pairs) = helper.complete(line, line.len(), &ctx).unwrap();
+    (start, pairs.into_iter().map(|p| p.replacement).collect())
+  }
+
+  #[test]
+  fn test_complete() {
+    let mut helper = NahHelper::new();
+    helper.candidates.servers = vec!["weather".to_string(), "docs".to_string()];
+    helper.candidates.tools = vec!["get_forecast".to_string(), "get_alerts".to_string()];
+    helper.candidates.resource_uris = vec!["file:///a b".to_string()];
+    assert_eq!(
+      complete(&helper, "list_"),
+      (
+        0,
+        vec![
+          "list_servers".to_string(),
+          "list_tools".to_string(),
+          "list_resources".to_string(),
+          "list_prompts".to_string()
+        ]
+      )
+    );
+    assert_eq!(complete(&helper, "use w"), (4, vec!["weather".to_string()]));
+    assert_eq!(complete(&helper, "call_tool get_f").1, vec!["get_forecast"]);
+    assert_eq!(
+      complete(&helper, "call_tool get_forecast --f").1,
+      vec!["--form"]
+    );
+    assert_eq!(
+      complete(&helper, "read_resources \"file").1,
+      vec!["\"file:///a b\""]
+    );
+    assert!(complete(&helper, "list_tools x").1.is_empty());
+  }
+
+  #[test]
+  fn test_hint() {
+    let helper = NahHelper::new();
+    let history = DefaultHistory::new();
+    let ctx = Context::new(&history);
+    let hint = |line: &str| helper.hint(line, line.len(), &ctx);
+    assert_eq!(hint("inspect_t"), Some("ool [tool name]".to_string()));
+    assert_eq!(hint("use "), Some("[server_name]".to_string()));
+    assert_eq!(hint("list_"), None);
+    assert_eq!(hint("use weather"), None);
+    assert_eq!(hint("exi"), Some("t".to_string()));
+  }
+}
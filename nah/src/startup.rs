@@ -0,0 +1,245 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * Start MCP servers in parallel, with a status table of their progress.
+ */
+use crate::mcp::{
+  MCPHTTPServerConnection, MCPLocalServerCommand, MCPLocalServerProcess, MCPRemoteServerConfig,
+  MCPServer,
+};
+use crate::output::OutputMode;
+use crate::types::NahError;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/**
+ * Interval between refreshes of the status table.
+ */
+const REFRESH_INTERVAL_MS: u64 = 100;
+
+/**
+ * Configs of the local and remote servers.
+ */
+#[derive(Clone, Copy)]
+pub struct ServerConfigs<'a> {
+  pub local: &'a HashMap<String, MCPLocalServerCommand>,
+  pub remote: &'a HashMap<String, MCPRemoteServerConfig>,
+}
+
+impl ServerConfigs<'_> {
+  pub fn contains(&self, server_name: &str) -> bool {
+    self.local.contains_key(server_name) || self.remote.contains_key(server_name)
+  }
+
+  pub fn is_disabled(&self, server_name: &str) -> bool {
+    self.local.get(server_name).is_some_and(|c| c.disabled)
+      || self.remote.get(server_name).is_some_and(|c| c.disabled)
+  }
+
+  pub fn is_lazy(&self, server_name: &str) -> bool {
+    self.local.get(server_name).is_some_and(|c| c.lazy)
+      || self.remote.get(server_name).is_some_and(|c| c.lazy)
+  }
+
+  /**
+   * Names of all servers, sorted.
+   */
+  pub fn names(&self) -> Vec<String> {
+    let mut names: Vec<String> = self
+      .local
+      .keys()
+      .chain(self.remote.keys())
+      .cloned()
+      .collect();
+    names.sort();
+    names.dedup();
+    names
+  }
+}
+
+/**
+ * Launch a local server or connect to a remote server.
+ */
+pub fn start_server(
+  configs: ServerConfigs,
+  server_name: &str,
+  history_path: &Path,
+) -> Result<Box<dyn MCPServer + Send>, NahError> {
+  if configs.is_disabled(server_name) {
+    return Err(NahError::invalid_value(
+      &format!("Server {} is disabled in the config file", server_name),
+      None,
+    ));
+  }
+  if let Some(command) = configs.local.get(server_name) {
+    let process =
+      MCPLocalServerProcess::start_and_init(server_name, command, &history_path.to_path_buf())?;
+    return Ok(Box::new(process));
+  }
+  if let Some(remote_config) = configs.remote.get(server_name) {
+    let conn = MCPHTTPServerConnection::init(server_name, remote_config, history_path)?;
+    return Ok(Box::new(conn));
+  }
+  Err(NahError::invalid_value(
+    &format!("Server {} not found in the config file", server_name),
+    None,
+  ))
+}
+
+#[derive(Debug, PartialEq)]
+enum StartStatus {
+  Starting,
+  Ready,
+  Failed(String),
+}
+
+struct StatusRow {
+  name: String,
+  status: StartStatus,
+  elapsed: Duration,
+}
+
+impl fmt::Display for StatusRow {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let status = match self.status {
+      StartStatus::Starting => "starting",
+      StartStatus::Ready => "ready",
+      StartStatus::Failed(_) => "failed",
+    };
+    write!(
+      f,
+      "  {:<20} {:<9} {:>5.1}s",
+      self.name,
+      status,
+      self.elapsed.as_secs_f64()
+    )
+  }
+}
+
+/**
+ * Start servers in parallel and return the started ones. On a terminal, a
+ * table of the servers is refreshed until all of them are ready or failed;
+ * otherwise a line is printed when each server is done.
+ */
+pub fn start_servers(
+  configs: ServerConfigs,
+  server_names: &[String],
+  history_path: &Path,
+  output: OutputMode,
+) -> HashMap<String, Box<dyn MCPServer>> {
+  let mut servers: HashMap<String, Box<dyn MCPServer>> = HashMap::new();
+  if server_names.is_empty() {
+    return servers;
+  }
+  let live = output == OutputMode::Text && std::io::stdout().is_terminal();
+  let started_at = Instant::now();
+  let mut rows: Vec<StatusRow> = server_names
+    .iter()
+    .map(|name| StatusRow {
+      name: name.clone(),
+      status: StartStatus::Starting,
+      elapsed: Duration::ZERO,
+    })
+    .collect();
+  let (tx, rx) = channel();
+  thread::scope(|scope| {
+    for (idx, server_name) in server_names.iter().enumerate() {
+      let tx = tx.clone();
+      scope.spawn(move || {
+        // Errors hold sources which can't be sent across threads.
+        let result = start_server(configs, server_name, history_path).map_err(|e| e.to_string());
+        let _ = tx.send((idx, result));
+      });
+    }
+    drop(tx);
+    if live {
+      draw_table(&rows, false);
+    }
+    let mut pending = rows.len();
+    while pending > 0 {
+      match rx.recv_timeout(Duration::from_millis(REFRESH_INTERVAL_MS)) {
+        Ok((idx, result)) => {
+          pending -= 1;
+          let row = &mut rows[idx];
+          row.elapsed = started_at.elapsed();
+          match result {
+            Ok(server) => {
+              row.status = StartStatus::Ready;
+              servers.insert(row.name.clone(), server);
+            }
+            Err(e) => row.status = StartStatus::Failed(e),
+          }
+          if !live {
+            logln!(output, "{}", row);
+          }
+        }
+        Err(RecvTimeoutError::Timeout) => {}
+        Err(RecvTimeoutError::Disconnected) => break,
+      }
+      if live {
+        for row in rows.iter_mut() {
+          if row.status == StartStatus::Starting {
+            row.elapsed = started_at.elapsed();
+          }
+        }
+        draw_table(&rows, true);
+      }
+    }
+  });
+  for row in rows.iter() {
+    if let StartStatus::Failed(e) = &row.status {
+      logln!(output, "Failed to start {}: {}", row.name, e);
+    }
+  }
+  servers
+}
+
+/**
+ * Print the status table, over the previous one when `redraw` is set.
+ */
+fn draw_table(rows: &[StatusRow], redraw: bool) {
+  let mut stdout = std::io::stdout().lock();
+  if redraw {
+    let _ = write!(stdout, "\x1b[{}A", rows.len());
+  }
+  for row in rows {
+    let _ = writeln!(stdout, "\x1b[2K{}", row);
+  }
+  let _ = stdout.flush();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_start_servers() {
+    let history_path = std::env::temp_dir().join(format!("nah_test_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&history_path).unwrap();
+    let local: HashMap<String, MCPLocalServerCommand> = serde_json::from_value(serde_json::json!({
+      "missing": {"command": "nah-test-command-not-found"},
+      "off": {"command": "nah-test-command-not-found", "disabled": true}
+    }))
+    .unwrap();
+    let remote = HashMap::new();
+    let configs = ServerConfigs {
+      local: &local,
+      remote: &remote,
+    };
+    assert_eq!(configs.names(), vec!["missing", "off"]);
+    assert!(configs.is_disabled("off"));
+    let servers = start_servers(configs, &configs.names(), &history_path, OutputMode::Json);
+    let _ = std::fs::remove_dir_all(&history_path);
+    assert!(servers.is_empty());
+    let error = start_server(configs, "off", &history_path).err().unwrap();
+    assert!(error.message.contains("disabled"));
+  }
+}
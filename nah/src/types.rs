@@ -140,6 +140,74 @@ impl NahError {
     }
   }
 
+  pub fn arguments_not_match_schema(tool_name: &str, details: &[String]) -> NahError {
+    NahError {
+      code: 11,
+      message: format!(
+        "Arguments of tool {} do not match its inputSchema:\n  * {}",
+        tool_name,
+        details.join("\n  * ")
+      ),
+      source: None,
+    }
+  }
+
+  pub fn tool_call_error(tool_name: &str) -> NahError {
+    NahError {
+      code: 12,
+      message: format!("Tool {} returned an error result", tool_name),
+      source: None,
+    }
+  }
+
+  pub fn script_failed(script: &str, failed_lines: &[usize]) -> NahError {
+    let lines: Vec<String> = failed_lines.iter().map(|l| l.to_string()).collect();
+    NahError {
+      code: 13,
+      message: format!("Script {} failed at line(s): {}", script, lines.join(", ")),
+      source: None,
+    }
+  }
+
+  pub fn invalid_command(message: &str) -> NahError {
+    NahError {
+      code: 14,
+      message: format!("Invalid command: {}", message),
+      source: None,
+    }
+  }
+
+  pub fn assertion_failed(message: &str) -> NahError {
+    NahError {
+      code: 15,
+      message: message.to_string(),
+      source: None,
+    }
+  }
+
+  pub fn secret_resolution_error(
+    reference: &str,
+    source: Option<Box<dyn std::error::Error>>,
+  ) -> NahError {
+    NahError {
+      code: 16,
+      message: format!("Failed to resolve secret {}", reference),
+      source,
+    }
+  }
+
+  pub fn mcp_server_exited(server_name: &str, status: &str, stderr_tail: &str) -> NahError {
+    let mut message = format!("MCP server {} exited with {}", server_name, status);
+    if !stderr_tail.is_empty() {
+      message.push_str(&format!(", last lines of its stderr:\n{}", stderr_tail));
+    }
+    NahError {
+      code: 17,
+      message,
+      source: None,
+    }
+  }
+
   pub fn model_error(
     model_name: &str,
     message: &str,
@@ -1,3 +1,11 @@
+use crate::types::NahError;
+use rustyline::completion::Completer;
+use rustyline::config::Configurer;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{ColorMode, Editor, Helper};
+use std::borrow::Cow;
 use std::io::Write;
 
 pub fn ask_for_user_confirmation(msg: &str, msg_on_cancel: &str) -> bool {
@@ -13,3 +21,65 @@ pub fn ask_for_user_confirmation(msg: &str, msg_on_cancel: &str) -> bool {
     true
   }
 }
+
+/**
+ * Replaces typed characters with `*` when reading secrets.
+ */
+struct MaskingHelper {
+  masking: bool,
+}
+
+impl Highlighter for MaskingHelper {
+  fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+    match self.masking {
+      true => Cow::Owned("*".repeat(line.chars().count())),
+      false => Cow::Borrowed(line),
+    }
+  }
+
+  fn highlight_char(&self, _line: &str, _pos: usize, kind: CmdKind) -> bool {
+    self.masking && kind != CmdKind::MoveCursor
+  }
+}
+
+impl Completer for MaskingHelper {
+  type Candidate = String;
+}
+
+impl Hinter for MaskingHelper {
+  type Hint = String;
+}
+
+impl Validator for MaskingHelper {}
+
+impl Helper for MaskingHelper {}
+
+/**
+ * Ask the user for a line of input. Typed characters are masked if `secret`.
+ */
+pub fn ask_for_input(prompt: &str, secret: bool) -> Result<String, NahError> {
+  let mut rl = match Editor::new() {
+    Ok(rl) => rl,
+    Err(e) => {
+      return Err(NahError::io_error(
+        "Failed to read input",
+        Some(Box::new(e)),
+      ))
+    }
+  };
+  rl.set_helper(Some(MaskingHelper { masking: secret }));
+  rl.set_auto_add_history(false);
+  if secret {
+    rl.set_color_mode(ColorMode::Forced);
+  }
+  match rl.readline(prompt) {
+    Ok(line) => Ok(line.trim().to_string()),
+    Err(rustyline::error::ReadlineError::Interrupted | rustyline::error::ReadlineError::Eof) => {
+      Err(NahError::user_cancel_request())
+    }
+    Err(e) => Err(NahError::io_error(
+      "Failed to read input",
+      Some(Box::new(e)),
+    )),
+  }
+}
@@ -0,0 +1,998 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * Validation of config files with diagnostics pointing to the JSON path and
+ * the line and column of each problem.
+ */
+use crate::config::{self, ConfigInput, ModelConfig};
+use crate::mcp::{MCPLocalServerCommand, MCPRemoteServerConfig};
+use crate::output::OutputMode;
+use serde::{de, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+  Error,
+  Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+  pub severity: Severity,
+  /// JSON path of the value, e.g. `$.mcpServers.weather.args[0]`.
+  pub path: String,
+  pub line: usize,
+  pub column: usize,
+  pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let severity = match self.severity {
+      Severity::Error => "error",
+      Severity::Warning => "warning",
+    };
+    write!(
+      f,
+      "{}:{}: {}: {}: {}",
+      self.line, self.column, severity, self.path, self.message
+    )
+  }
+}
+
+const TOP_LEVEL_KEYS: &[&str] = &[
+  "mcpServers",
+  "servers",
+  "inputs",
+  "model",
+  "models",
+  "defaultModel",
+  "argumentInput",
+];
+/**
+ * Keys of a config object which are not fields of its struct.
+ */
+const SERVER_EXTRA_KEYS: &[&str] = &["type"];
+const INPUT_EXTRA_KEYS: &[&str] = &["type"];
+
+/**
+ * Names of the fields of a struct as they appear in JSON, with the extra keys.
+ * The names are taken from the `Deserialize` implementation, so that the
+ * validator knows every field the config loader accepts.
+ */
+fn known_keys<'de, T: Deserialize<'de>>(extra_keys: &[&'static str]) -> Vec<&'static str> {
+  let mut fields = None;
+  let _ = T::deserialize(FieldNames(&mut fields));
+  let mut keys = extra_keys.to_vec();
+  keys.extend(fields.unwrap_or_default());
+  keys
+}
+
+/**
+ * A deserializer which records the field names of the struct it is asked for,
+ * and fails without reading anything.
+ */
+struct FieldNames<'a>(&'a mut Option<&'static [&'static str]>);
+
+impl<'de> de::Deserializer<'de> for FieldNames<'_> {
+  type Error = de::value::Error;
+
+  fn deserialize_any<V: de::Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+    Err(de::Error::custom("not a struct"))
+  }
+
+  fn deserialize_struct<V: de::Visitor<'de>>(
+    self,
+    _name: &'static str,
+    fields: &'static [&'static str],
+    _visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    *self.0 = Some(fields);
+    Err(de::Error::custom("field names are recorded"))
+  }
+
+  serde::forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+    byte_buf option unit unit_struct newtype_struct seq tuple tuple_struct map
+    enum identifier ignored_any
+  }
+}
+
+/**
+ * Names of keys which usually hold secrets.
+ */
+const SECRET_KEY_HINTS: &[&str] = &[
+  "token",
+  "secret",
+  "password",
+  "passwd",
+  "apikey",
+  "api_key",
+  "auth",
+  "credential",
+  "private",
+];
+
+/**
+ * Validate config files and print the diagnostics. Returns the exit code: 0
+ * when there is no error.
+ */
+pub fn run_validate(paths: &[PathBuf], output: OutputMode) -> i32 {
+  let mut report = Vec::new();
+  let mut error_count = 0;
+  let mut warning_count = 0;
+  for path in paths.iter() {
+    let diagnostics = validate_file(path);
+    for d in diagnostics.iter() {
+      match d.severity {
+        Severity::Error => error_count += 1,
+        Severity::Warning => warning_count += 1,
+      }
+      textln!(output, "{}:{}", path.display(), d);
+    }
+    report.push(serde_json::json!({
+      "file": path,
+      "diagnostics": diagnostics,
+    }));
+  }
+  match output {
+    OutputMode::Text => println!(
+      "{} file(s) checked: {} error(s), {} warning(s)",
+      paths.len(),
+      error_count,
+      warning_count
+    ),
+    OutputMode::Json => println!("{}", Value::Array(report)),
+  }
+  if error_count > 0 {
+    2
+  } else {
+    0
+  }
+}
+
+/**
+ * Validate a config file. Relative paths in the config are resolved against
+ * its workspace folder, like when the config is loaded.
+ */
+pub fn validate_file(path: &Path) -> Vec<Diagnostic> {
+  match std::fs::read_to_string(path) {
+    Ok(text) => validate_text(&text, &config::workspace_folder(path)),
+    Err(e) => vec![Diagnostic {
+      severity: Severity::Error,
+      path: "$".to_string(),
+      line: 0,
+      column: 0,
+      message: format!("failed to read the file: {}", e),
+    }],
+  }
+}
+
+pub fn validate_text(text: &str, base_dir: &Path) -> Vec<Diagnostic> {
+  let data: Value = match serde_json::from_str(text) {
+    Ok(v) => v,
+    Err(e) => {
+      return vec![Diagnostic {
+        severity: Severity::Error,
+        path: "$".to_string(),
+        line: e.line(),
+        column: e.column(),
+        message: format!("invalid JSON: {}", e),
+      }]
+    }
+  };
+  let mut validator = Validator {
+    positions: value_positions(text),
+    diagnostics: Vec::new(),
+    base_dir: base_dir.to_path_buf(),
+    input_ids: HashSet::new(),
+  };
+  validator.check_root(&data);
+  validator
+    .diagnostics
+    .sort_by_key(|d| (d.line, d.column, d.path.clone()));
+  validator.diagnostics
+}
+
+struct Validator {
+  positions: HashMap<String, (usize, usize)>,
+  diagnostics: Vec<Diagnostic>,
+  base_dir: PathBuf,
+  input_ids: HashSet<String>,
+}
+
+impl Validator {
+  fn report(&mut self, severity: Severity, path: &str, message: String) {
+    let (line, column) = self.position(path);
+    self.diagnostics.push(Diagnostic {
+      severity,
+      path: path.to_string(),
+      line,
+      column,
+      message,
+    });
+  }
+
+  fn error(&mut self, path: &str, message: String) {
+    self.report(Severity::Error, path, message);
+  }
+
+  fn warning(&mut self, path: &str, message: String) {
+    self.report(Severity::Warning, path, message);
+  }
+
+  /**
+   * Position of a path, or of its closest ancestor that has one.
+   */
+  fn position(&self, path: &str) -> (usize, usize) {
+    let mut path = path;
+    loop {
+      if let Some(p) = self.positions.get(path) {
+        return *p;
+      }
+      match path.rfind(['.', '[']) {
+        Some(idx) => path = &path[..idx],
+        None => return (1, 1),
+      }
+    }
+  }
+
+  fn expect_object<'a>(&mut self, path: &str, value: &'a Value) -> Option<&'a Map<String, Value>> {
+    match value.as_object() {
+      Some(obj) => Some(obj),
+      None => {
+        self.error(
+          path,
+          format!("expected an object, found {}", type_name(value)),
+        );
+        None
+      }
+    }
+  }
+
+  fn check_unknown_keys(&mut self, path: &str, obj: &Map<String, Value>, known: &[&str]) {
+    for key in obj.keys() {
+      if !known.contains(&key.as_str()) {
+        self.warning(&child(path, key), format!("unknown key `{}`", key));
+      }
+    }
+  }
+
+  fn check_string(&mut self, path: &str, value: &Value) -> Option<String> {
+    match value.as_str() {
+      Some(s) => {
+        self.check_references(path, s);
+        Some(s.to_string())
+      }
+      None => {
+        self.error(
+          path,
+          format!("expected a string, found {}", type_name(value)),
+        );
+        None
+      }
+    }
+  }
+
+  fn check_string_array(&mut self, path: &str, value: &Value) {
+    match value.as_array() {
+      Some(items) => {
+        for (idx, item) in items.iter().enumerate() {
+          self.check_string(&format!("{}[{}]", path, idx), item);
+        }
+      }
+      None => self.error(
+        path,
+        format!("expected an array of strings, found {}", type_name(value)),
+      ),
+    }
+  }
+
+  /**
+   * Check an object of string values. Values of keys that look like secrets
+   * must not be written in plain text.
+   */
+  fn check_string_map(&mut self, path: &str, value: &Value) {
+    let Some(obj) = self.expect_object(path, value) else {
+      return;
+    };
+    for (key, item) in obj.iter() {
+      let item_path = child(path, key);
+      if let Some(s) = self.check_string(&item_path, item) {
+        self.check_plaintext_secret(&item_path, key, &s);
+      }
+    }
+  }
+
+  /**
+   * Check a secret, which is a string or a reference like `{"env": "NAME"}`,
+   * `{"file": "path"}` or `{"command": ["pass", "show", "name"]}`.
+   */
+  fn check_secret(&mut self, path: &str, key: &str, value: &Value) {
+    let Some(obj) = value.as_object() else {
+      if let Some(s) = self.check_string(path, value) {
+        self.check_plaintext_secret(path, key, &s);
+      }
+      return;
+    };
+    if obj.len() != 1 {
+      self.error(
+        path,
+        "expected exactly one of `env`, `file` or `command`".to_string(),
+      );
+      return;
+    }
+    let (kind, reference) = obj.iter().next().unwrap();
+    let reference_path = child(path, kind);
+    match kind.as_str() {
+      "env" => {
+        if let Some(name) = self.check_string(&reference_path, reference) {
+          self.check_env_var(&reference_path, &name);
+        }
+      }
+      "file" => {
+        self.check_string(&reference_path, reference);
+      }
+      "command" => match reference.as_array().and_then(|a| a.first()) {
+        Some(program) => {
+          self.check_string_array(&reference_path, reference);
+          if let Some(program) = program.as_str() {
+            if !program.contains('/') && find_in_path(program).is_none() {
+              self.warning(
+                &reference_path,
+                format!("command `{}` is not found on PATH", program),
+              );
+            }
+          }
+        }
+        None => self.error(
+          &reference_path,
+          "expected a non-empty array of strings".to_string(),
+        ),
+      },
+      _ => self.error(
+        path,
+        format!(
+          "unknown secret reference `{}`, expected `env`, `file` or `command`",
+          kind
+        ),
+      ),
+    }
+  }
+
+  fn check_bool(&mut self, path: &str, value: &Value) {
+    if !value.is_boolean() {
+      self.error(
+        path,
+        format!("expected a boolean, found {}", type_name(value)),
+      );
+    }
+  }
+
+  fn check_unsigned(&mut self, path: &str, value: &Value) {
+    if !value.is_u64() {
+      self.error(
+        path,
+        format!(
+          "expected a non-negative integer, found {}",
+          type_name(value)
+        ),
+      );
+    }
+  }
+
+  fn check_number(&mut self, path: &str, value: &Value) {
+    if !value.is_number() {
+      self.error(
+        path,
+        format!("expected a number, found {}", type_name(value)),
+      );
+    }
+  }
+
+  /**
+   * Check `${...}` references: environment variables should be set and inputs
+   * should be declared.
+   */
+  fn check_references(&mut self, path: &str, value: &str) {
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+      let Some(len) = rest[start..].find('}') else {
+        self.error(path, "unclosed `${`".to_string());
+        return;
+      };
+      let reference = &rest[start + 2..start + len];
+      match reference.split_once(':') {
+        Some(("input", id)) => {
+          if !self.input_ids.contains(id) {
+            self.error(path, format!("input `{}` is not declared in `inputs`", id));
+          }
+        }
+        Some(("env", name)) => self.check_env_var(path, name),
+        Some(_) => self.error(path, format!("unknown reference `${{{}}}`", reference)),
+        None if reference == "workspaceFolder" || reference == "userHome" => {}
+        None => self.check_env_var(path, reference),
+      }
+      rest = &rest[start + len + 1..];
+    }
+  }
+
+  fn check_env_var(&mut self, path: &str, name: &str) {
+    if std::env::var_os(name).is_none() {
+      self.warning(path, format!("environment variable {} is not set", name));
+    }
+  }
+
+  fn check_plaintext_secret(&mut self, path: &str, key: &str, value: &str) {
+    if value.is_empty() || value.contains("${") {
+      return;
+    }
+    let key = key.to_lowercase();
+    let looks_secret = SECRET_KEY_HINTS.iter().any(|hint| key.contains(hint))
+      || value.starts_with("sk-")
+      || value.starts_with("ghp_")
+      || value.starts_with("github_pat_")
+      || value.starts_with("Bearer ");
+    if looks_secret {
+      self.warning(
+        path,
+        "looks like a plaintext secret, reference it like `${env:NAME}` or `{\"env\": \"NAME\"}` instead"
+          .to_string(),
+      );
+    }
+  }
+
+  fn check_url(&mut self, path: &str, value: &Value) {
+    let Some(url) = self.check_string(path, value) else {
+      return;
+    };
+    if url.contains("${") {
+      return;
+    }
+    match reqwest::Url::parse(&url) {
+      Ok(u) if u.scheme() == "http" || u.scheme() == "https" => {}
+      Ok(u) => self.error(
+        path,
+        format!(
+          "unsupported URL scheme `{}`, expected http or https",
+          u.scheme()
+        ),
+      ),
+      Err(e) => self.error(path, format!("malformed URL `{}`: {}", url, e)),
+    }
+  }
+
+  fn check_root(&mut self, data: &Value) {
+    let Some(root) = self.expect_object("$", data) else {
+      return;
+    };
+    self.check_unknown_keys("$", root, TOP_LEVEL_KEYS);
+    if let Some(inputs) = root.get("inputs") {
+      self.check_inputs("$.inputs", inputs);
+    }
+    match (root.get("mcpServers"), root.get("servers")) {
+      (None, None) => self.error("$", "missing field `mcpServers`".to_string()),
+      (Some(servers), other) => {
+        if other.is_some() {
+          self.warning(
+            "$.servers",
+            "`servers` is ignored because `mcpServers` is present".to_string(),
+          );
+        }
+        self.check_servers("$.mcpServers", servers);
+      }
+      (None, Some(servers)) => self.check_servers("$.servers", servers),
+    }
+    let mut profiles = HashSet::new();
+    if let Some(model) = root.get("model") {
+      self.check_model("$.model", model);
+      profiles.insert(crate::config::DEFAULT_MODEL_PROFILE.to_string());
+    }
+    if let Some(models) = root.get("models") {
+      if let Some(models) = self.expect_object("$.models", models) {
+        for (name, model) in models.iter() {
+          self.check_model(&child("$.models", name), model);
+          profiles.insert(name.to_string());
+        }
+      }
+    }
+    if let Some(default_model) = root.get("defaultModel") {
+      if let Some(name) = self.check_string("$.defaultModel", default_model) {
+        if !profiles.contains(&name) {
+          self.error(
+            "$.defaultModel",
+            format!("model profile `{}` is not defined", name),
+          );
+        }
+      }
+    }
+    if let Some(mode) = root.get("argumentInput") {
+      if mode != "editor" && mode != "form" {
+        self.error(
+          "$.argumentInput",
+          "expected \"editor\" or \"form\"".to_string(),
+        );
+      }
+    }
+  }
+
+  fn check_inputs(&mut self, path: &str, value: &Value) {
+    let Some(inputs) = value.as_array() else {
+      self.error(
+        path,
+        format!("expected an array, found {}", type_name(value)),
+      );
+      return;
+    };
+    for (idx, input) in inputs.iter().enumerate() {
+      let input_path = format!("{}[{}]", path, idx);
+      let Some(obj) = self.expect_object(&input_path, input) else {
+        continue;
+      };
+      self.check_unknown_keys(
+        &input_path,
+        obj,
+        &known_keys::<ConfigInput>(INPUT_EXTRA_KEYS),
+      );
+      match obj.get("id").and_then(|v| v.as_str()) {
+        Some(id) => {
+          self.input_ids.insert(id.to_string());
+        }
+        None => self.error(&input_path, "missing field `id`".to_string()),
+      }
+    }
+  }
+
+  fn check_servers(&mut self, path: &str, value: &Value) {
+    let Some(servers) = self.expect_object(path, value) else {
+      return;
+    };
+    for (name, server) in servers.iter() {
+      let server_path = child(path, name);
+      let Some(obj) = self.expect_object(&server_path, server) else {
+        continue;
+      };
+      let declared_type = obj.get("type").and_then(|v| v.as_str());
+      let has_command = obj.contains_key("command");
+      let has_url = obj.contains_key("url");
+      if has_command && has_url {
+        self.error(
+          &server_path,
+          "conflicting server types: both `command` and `url` are set".to_string(),
+        );
+        continue;
+      }
+      match (declared_type, has_command, has_url) {
+        (Some("stdio") | None, true, _) => self.check_local_server(&server_path, obj),
+        (Some("http" | "sse") | None, _, true) => self.check_remote_server(&server_path, obj),
+        (Some(t @ "stdio"), false, true) | (Some(t @ ("http" | "sse")), true, false) => self.error(
+          &child(&server_path, "type"),
+          format!("conflicting server types: `type` is `{}`", t),
+        ),
+        (Some("stdio"), false, _) => {
+          self.error(&server_path, "missing field `command`".to_string())
+        }
+        (Some("http" | "sse"), _, false) => {
+          self.error(&server_path, "missing field `url`".to_string())
+        }
+        (Some(t), _, _) => self.error(
+          &child(&server_path, "type"),
+          format!("unknown server type `{}`, expected stdio, http or sse", t),
+        ),
+        (None, false, false) => {
+          self.error(&server_path, "missing field `command` or `url`".to_string())
+        }
+      }
+    }
+  }
+
+  /**
+   * Check `disabled` and `lazy` of a server.
+   */
+  fn check_flags(&mut self, path: &str, obj: &Map<String, Value>) {
+    for key in ["disabled", "lazy"] {
+      if let Some(value) = obj.get(key) {
+        self.check_bool(&child(path, key), value);
+      }
+    }
+  }
+
+  fn check_local_server(&mut self, path: &str, obj: &Map<String, Value>) {
+    self.check_unknown_keys(
+      path,
+      obj,
+      &known_keys::<MCPLocalServerCommand>(SERVER_EXTRA_KEYS),
+    );
+    self.check_flags(path, obj);
+    if let Some(args) = obj.get("args") {
+      self.check_string_array(&child(path, "args"), args);
+    }
+    if let Some(env) = obj.get("env") {
+      self.check_string_map(&child(path, "env"), env);
+    }
+    if let Some(allowlist) = obj.get("envAllowlist") {
+      self.check_string_array(&child(path, "envAllowlist"), allowlist);
+    }
+    if let Some(clear_env) = obj.get("clearEnv") {
+      self.check_bool(&child(path, "clearEnv"), clear_env);
+    }
+    if let Some(timeout) = obj.get("timeout_ms") {
+      self.check_unsigned(&child(path, "timeout_ms"), timeout);
+    }
+    if let Some(restart) = obj.get("restart") {
+      if restart != "never" && restart != "onFailure" && restart != "always" {
+        self.error(
+          &child(path, "restart"),
+          "expected \"never\", \"onFailure\" or \"always\"".to_string(),
+        );
+      }
+    }
+    for key in ["maxRestarts", "shutdownGraceMs", "terminateGraceMs"] {
+      if let Some(value) = obj.get(key) {
+        self.check_unsigned(&child(path, key), value);
+      }
+    }
+    let mut cwd = self.base_dir.clone();
+    if let Some(value) = obj.get("cwd") {
+      let cwd_path = child(path, "cwd");
+      if let Some(dir) = self.check_string(&cwd_path, value) {
+        if !dir.contains("${") {
+          cwd = self.base_dir.join(dir);
+          if !cwd.is_dir() {
+            self.error(
+              &cwd_path,
+              format!("directory {} does not exist", cwd.display()),
+            );
+          }
+        }
+      }
+    }
+    let command_path = child(path, "command");
+    let Some(command) = self.check_string(&command_path, &obj["command"]) else {
+      return;
+    };
+    if command.contains("${") {
+      return;
+    }
+    if command.contains(std::path::MAIN_SEPARATOR) || command.contains('/') {
+      if !cwd.join(&command).is_file() {
+        self.error(&command_path, format!("command {} does not exist", command));
+      }
+    } else if find_in_path(&command).is_none() {
+      self.error(
+        &command_path,
+        format!("command `{}` is not found on PATH", command),
+      );
+    }
+  }
+
+  fn check_remote_server(&mut self, path: &str, obj: &Map<String, Value>) {
+    self.check_unknown_keys(
+      path,
+      obj,
+      &known_keys::<MCPRemoteServerConfig>(SERVER_EXTRA_KEYS),
+    );
+    self.check_flags(path, obj);
+    self.check_url(&child(path, "url"), &obj["url"]);
+    if let Some(headers) = obj.get("headers") {
+      let headers_path = child(path, "headers");
+      if let Some(headers) = self.expect_object(&headers_path, headers) {
+        for (key, item) in headers.iter() {
+          self.check_secret(&child(&headers_path, key), key, item);
+        }
+      }
+    }
+    if let Some(timeout) = obj.get("timeout_ms") {
+      self.check_unsigned(&child(path, "timeout_ms"), timeout);
+    }
+  }
+
+  fn check_model(&mut self, path: &str, value: &Value) {
+    let Some(obj) = self.expect_object(path, value) else {
+      return;
+    };
+    self.check_unknown_keys(path, obj, &known_keys::<ModelConfig>(&[]));
+    for key in ["baseUrl", "model"] {
+      if !obj.contains_key(key) {
+        self.error(path, format!("missing field `{}`", key));
+      }
+    }
+    if let Some(url) = obj.get("baseUrl") {
+      self.check_url(&child(path, "baseUrl"), url);
+    }
+    for key in ["model", "systemPrompt"] {
+      if let Some(v) = obj.get(key) {
+        self.check_string(&child(path, key), v);
+      }
+    }
+    if let Some(token) = obj.get("authToken") {
+      self.check_secret(&child(path, "authToken"), "authToken", token);
+    }
+    if let Some(api) = obj.get("api") {
+      if api != "chatCompletions" && api != "responses" {
+        self.error(
+          &child(path, "api"),
+          "expected \"chatCompletions\" or \"responses\"".to_string(),
+        );
+      }
+    }
+    for key in ["maxTokens", "contextWindow"] {
+      if let Some(v) = obj.get(key) {
+        self.check_unsigned(&child(path, key), v);
+      }
+    }
+    for key in ["temperature", "topP", "frequencyPenalty"] {
+      if let Some(v) = obj.get(key) {
+        self.check_number(&child(path, key), v);
+      }
+    }
+    if let Some(extra) = obj.get("extraParams") {
+      self.expect_object(&child(path, "extraParams"), extra);
+    }
+  }
+}
+
+fn child(path: &str, key: &str) -> String {
+  format!("{}.{}", path, key)
+}
+
+fn type_name(value: &Value) -> &'static str {
+  match value {
+    Value::Null => "null",
+    Value::Bool(_) => "a boolean",
+    Value::Number(_) => "a number",
+    Value::String(_) => "a string",
+    Value::Array(_) => "an array",
+    Value::Object(_) => "an object",
+  }
+}
+
+fn find_in_path(command: &str) -> Option<PathBuf> {
+  let paths = std::env::var_os("PATH")?;
+  std::env::split_paths(&paths)
+    .map(|dir| dir.join(command))
+    .find(|p| p.is_file())
+}
+
+/**
+ * Lines and columns of values in a JSON text by their paths. For members of
+ * objects, the position of the key is recorded. The text must be valid JSON.
+ */
+fn value_positions(text: &str) -> HashMap<String, (usize, usize)> {
+  let mut scanner = PositionScanner {
+    chars: text.chars().peekable(),
+    line: 1,
+    column: 1,
+    positions: HashMap::new(),
+  };
+  scanner.skip_whitespace();
+  let position = (scanner.line, scanner.column);
+  scanner.positions.insert("$".to_string(), position);
+  scanner.scan_value("$");
+  scanner.positions
+}
+
+struct PositionScanner<'a> {
+  chars: std::iter::Peekable<std::str::Chars<'a>>,
+  line: usize,
+  column: usize,
+  positions: HashMap<String, (usize, usize)>,
+}
+
+impl PositionScanner<'_> {
+  fn next(&mut self) -> Option<char> {
+    let c = self.chars.next()?;
+    if c == '\n' {
+      self.line += 1;
+      self.column = 1;
+    } else {
+      self.column += 1;
+    }
+    Some(c)
+  }
+
+  fn skip_whitespace(&mut self) {
+    while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+      self.next();
+    }
+  }
+
+  fn scan_value(&mut self, path: &str) {
+    self.skip_whitespace();
+    match self.chars.peek() {
+      Some('{') => {
+        self.next();
+        loop {
+          self.skip_whitespace();
+          match self.chars.peek() {
+            Some('"') => {}
+            Some(_) => {
+              // `,` or `}`
+              if self.next() == Some('}') {
+                return;
+              }
+              continue;
+            }
+            None => return,
+          }
+          let position = (self.line, self.column);
+          let key = self.scan_string();
+          let member_path = child(path, &key);
+          self.positions.insert(member_path.clone(), position);
+          self.skip_whitespace();
+          // `:`
+          self.next();
+          self.scan_value(&member_path);
+        }
+      }
+      Some('[') => {
+        self.next();
+        let mut idx = 0;
+        loop {
+          self.skip_whitespace();
+          match self.chars.peek() {
+            Some(']') => {
+              self.next();
+              return;
+            }
+            Some(',') => {
+              self.next();
+              continue;
+            }
+            Some(_) => {}
+            None => return,
+          }
+          let item_path = format!("{}[{}]", path, idx);
+          self
+            .positions
+            .insert(item_path.clone(), (self.line, self.column));
+          self.scan_value(&item_path);
+          idx += 1;
+        }
+      }
+      Some('"') => {
+        self.scan_string();
+      }
+      Some(_) => {
+        while self
+          .chars
+          .peek()
+          .is_some_and(|c| !matches!(c, ',' | '}' | ']') && !c.is_whitespace())
+        {
+          self.next();
+        }
+      }
+      None => {}
+    }
+  }
+
+  /**
+   * Scan a string and return its unescaped content.
+   */
+  fn scan_string(&mut self) -> String {
+    let mut raw = String::new();
+    raw.push(self.next().unwrap_or('"'));
+    while let Some(c) = self.next() {
+      raw.push(c);
+      match c {
+        '\\' => {
+          if let Some(escaped) = self.next() {
+            raw.push(escaped);
+          }
+        }
+        '"' => break,
+        _ => {}
+      }
+    }
+    serde_json::from_str(&raw).unwrap_or_default()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_value_positions() {
+    let text = "{\n  \"a\": [1, {\"b\": \"x\\\"y\"}],\n  \"c\": true\n}";
+    let positions = value_positions(text);
+    assert_eq!(positions["$"], (1, 1));
+    assert_eq!(positions["$.a"], (2, 3));
+    assert_eq!(positions["$.a[0]"], (2, 9));
+    assert_eq!(positions["$.a[1].b"], (2, 13));
+    assert_eq!(positions["$.c"], (3, 3));
+  }
+
+  #[test]
+  fn test_validate_vscode_file() {
+    let root = std::env::temp_dir().join(format!("nah_validate_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(root.join(".vscode")).unwrap();
+    std::fs::create_dir_all(root.join("tools")).unwrap();
+    let path = root.join(".vscode").join("mcp.json");
+    std::fs::write(
+      &path,
+      r#"{"servers": {"local": {"command": "sh", "cwd": "tools"}}}"#,
+    )
+    .unwrap();
+    let diagnostics = validate_file(&path);
+    std::fs::remove_dir_all(&root).unwrap();
+    assert_eq!(diagnostics, Vec::new());
+  }
+
+  #[test]
+  fn test_every_field_is_known() {
+    assert!(known_keys::<MCPLocalServerCommand>(SERVER_EXTRA_KEYS).contains(&"maxRestarts"));
+    let text = r#"{
+  "inputs": [{"type": "promptString", "id": "key", "description": "Key", "password": true, "default": "", "options": ["a"]}],
+  "mcpServers": {
+    "local": {
+      "type": "stdio", "command": "sh", "args": ["-c", "true"], "timeout_ms": 1000,
+      "env": {"MODE": "${input:key}"}, "cwd": ".", "clearEnv": true, "envAllowlist": ["PATH"],
+      "restart": "onFailure", "maxRestarts": 3, "shutdownGraceMs": 100, "terminateGraceMs": 100,
+      "disabled": false, "lazy": true
+    },
+    "remote": {
+      "type": "http", "url": "https://example.com/mcp", "headers": {"Accept": "application/json"},
+      "timeout_ms": 1000, "disabled": true, "lazy": false
+    }
+  },
+  "model": {
+    "baseUrl": "https://example.com/v1", "model": "m", "authToken": {"env": "HOME"},
+    "extraParams": {}, "systemPrompt": "Be brief.", "api": "responses", "maxTokens": 100,
+    "temperature": 0.5, "topP": 0.9, "frequencyPenalty": 0.1, "contextWindow": 1000
+  },
+  "models": {},
+  "defaultModel": "default",
+  "argumentInput": "form"
+}"#;
+    assert_eq!(validate_text(text, Path::new(".")), vec![]);
+    // The config loader accepts the same servers and model
+    let data: Value = serde_json::from_str(text).unwrap();
+    serde_json::from_value::<MCPLocalServerCommand>(data["mcpServers"]["local"].clone()).unwrap();
+    serde_json::from_value::<MCPRemoteServerConfig>(data["mcpServers"]["remote"].clone()).unwrap();
+    serde_json::from_value::<ModelConfig>(data["model"].clone()).unwrap();
+  }
+
+  #[test]
+  fn test_validate_text() {
+    let text = r#"{
+  "mcpServers": {
+    "local": {"command": "surely-not-a-command-of-nah", "args": [1], "env": {"API_TOKEN": "abc"}},
+    "both": {"command": "sh", "url": "https://example.com"},
+    "remote": {"type": "http", "url": "not a url", "header": {}},
+    "hf": {"url": "https://example.com", "headers": {"A": {"command": []}, "B": {"file": "~/k"}}}
+  },
+  "model": {"baseUrl": "https://example.com/v1", "authToken": "sk-123"},
+  "defaultModel": "gpt"
+}"#;
+    let diagnostics = validate_text(text, Path::new("."));
+    let summary: Vec<(Severity, &str, usize)> = diagnostics
+      .iter()
+      .map(|d| (d.severity, d.path.as_str(), d.line))
+      .collect();
+    assert_eq!(
+      summary,
+      vec![
+        (Severity::Error, "$.mcpServers.local.command", 3),
+        (Severity::Error, "$.mcpServers.local.args[0]", 3),
+        (Severity::Warning, "$.mcpServers.local.env.API_TOKEN", 3),
+        (Severity::Error, "$.mcpServers.both", 4),
+        (Severity::Error, "$.mcpServers.remote.url", 5),
+        (Severity::Warning, "$.mcpServers.remote.header", 5),
+        (Severity::Error, "$.mcpServers.hf.headers.A.command", 6),
+        (Severity::Error, "$.model", 8),
+        (Severity::Warning, "$.model.authToken", 8),
+        (Severity::Error, "$.defaultModel", 9),
+      ]
+    );
+    let invalid = validate_text("{\n  \"mcpServers\": {,}\n}", Path::new("."));
+    assert_eq!((invalid[0].line, invalid[0].column), (2, 18));
+  }
+}
@@ -0,0 +1,542 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+/**
+ * A lightweight JSON Schema validator shared by MCP clients and servers.
+ *
+ * Supported keywords: `type`, `enum`, `const`, `properties`, `required`,
+ * `additionalProperties`, `patternProperties`, `items`, `prefixItems`,
+ * `minItems`, `maxItems`, `minimum`, `maximum`, `exclusiveMinimum`,
+ * `exclusiveMaximum`, `minLength`, `maxLength`, `pattern`, `allOf`, `anyOf`,
+ * `oneOf`, `not` and local `$ref` such as `#/$defs/name`.
+ */
+use regex::Regex;
+use serde_json::Value;
+
+/**
+ * Max depth of nested `$ref`, which stops recursive schemas from looping.
+ */
+const MAX_REF_DEPTH: usize = 64;
+
+/**
+ * One violation found while validating an instance against a schema.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+  /// JSON pointer to the invalid part of the instance, e.g. `/items/0/name`.
+  pub path: String,
+  pub message: String,
+}
+
+impl std::fmt::Display for ValidationError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.path.is_empty() {
+      write!(f, "/: {}", self.message)
+    } else {
+      write!(f, "{}: {}", self.path, self.message)
+    }
+  }
+}
+
+/**
+ * Validate a JSON instance against a JSON Schema. Return all violations found,
+ * or an empty Vec if the instance is valid.
+ */
+pub fn validate(schema: &Value, instance: &Value) -> Vec<ValidationError> {
+  let mut validator = Validator {
+    root: schema,
+    errors: Vec::new(),
+  };
+  validator.validate(schema, instance, "", 0);
+  validator.errors
+}
+
+struct Validator<'a> {
+  root: &'a Value,
+  errors: Vec<ValidationError>,
+}
+
+impl<'a> Validator<'a> {
+  fn validate(&mut self, schema: &'a Value, instance: &Value, path: &str, depth: usize) {
+    let schema_obj = match schema {
+      Value::Object(obj) => obj,
+      Value::Bool(false) => {
+        self.push_error(path, "no value is allowed here".to_string());
+        return;
+      }
+      _ => return,
+    };
+
+    if let Some(reference) = schema_obj.get("$ref").and_then(|v| v.as_str()) {
+      match self.resolve_ref(reference) {
+        None => {
+          self.push_error(path, format!("cannot resolve $ref {}", reference));
+        }
+        Some(_) if depth >= MAX_REF_DEPTH => {
+          self.push_error(path, format!("$ref {} is nested too deeply", reference));
+        }
+        Some(target) => self.validate(target, instance, path, depth + 1),
+      }
+    }
+
+    if let Some(type_value) = schema_obj.get("type") {
+      let allowed_types: Vec<&str> = match type_value {
+        Value::String(s) => vec![s.as_str()],
+        Value::Array(arr) => arr.iter().filter_map(|v| v.as_str()).collect(),
+        _ => Vec::new(),
+      };
+      if !allowed_types.is_empty() && !allowed_types.iter().any(|t| is_type_of(t, instance)) {
+        self.push_error(
+          path,
+          format!(
+            "expected {}, found {}",
+            allowed_types.join(" or "),
+            get_instance_type_name(instance)
+          ),
+        );
+        // Other keywords would only report noise on a value of the wrong type.
+        return;
+      }
+    }
+
+    if let Some(candidates) = schema_obj.get("enum").and_then(|v| v.as_array()) {
+      if !candidates.contains(instance) {
+        let candidate_strs: Vec<String> = candidates.iter().map(|v| v.to_string()).collect();
+        self.push_error(
+          path,
+          format!("expected one of {}", candidate_strs.join(", ")),
+        );
+      }
+    }
+    if let Some(expected) = schema_obj.get("const") {
+      if expected != instance {
+        self.push_error(path, format!("expected {}", expected));
+      }
+    }
+
+    if let Some(sub_schemas) = schema_obj.get("allOf").and_then(|v| v.as_array()) {
+      for sub_schema in sub_schemas.iter() {
+        self.validate(sub_schema, instance, path, depth);
+      }
+    }
+    if let Some(sub_schemas) = schema_obj.get("anyOf").and_then(|v| v.as_array()) {
+      let branch_errors = self.validate_branches(sub_schemas, instance, path, depth);
+      if !branch_errors.iter().any(|errors| errors.is_empty()) {
+        self.push_error(path, "does not match any schema in anyOf".to_string());
+        self.append_closest_branch(branch_errors);
+      }
+    }
+    if let Some(sub_schemas) = schema_obj.get("oneOf").and_then(|v| v.as_array()) {
+      let branch_errors = self.validate_branches(sub_schemas, instance, path, depth);
+      let matched = branch_errors.iter().filter(|e| e.is_empty()).count();
+      if matched == 0 {
+        self.push_error(path, "does not match any schema in oneOf".to_string());
+        self.append_closest_branch(branch_errors);
+      } else if matched > 1 {
+        self.push_error(
+          path,
+          format!(
+            "matches {} schemas in oneOf, but exactly one is expected",
+            matched
+          ),
+        );
+      }
+    }
+    if let Some(sub_schema) = schema_obj.get("not") {
+      let branch_errors =
+        self.validate_branches(std::slice::from_ref(sub_schema), instance, path, depth);
+      if branch_errors[0].is_empty() {
+        self.push_error(path, "must not match the schema in not".to_string());
+      }
+    }
+
+    match instance {
+      Value::Object(obj) => self.validate_object(schema_obj, obj, path, depth),
+      Value::Array(items) => self.validate_array(schema_obj, items, path, depth),
+      Value::String(s) => self.validate_string(schema_obj, s, path),
+      Value::Number(n) => {
+        if let Some(n) = n.as_f64() {
+          self.validate_number(schema_obj, n, path);
+        }
+      }
+      _ => {}
+    }
+  }
+
+  fn validate_object(
+    &mut self,
+    schema_obj: &'a serde_json::Map<String, Value>,
+    obj: &serde_json::Map<String, Value>,
+    path: &str,
+    depth: usize,
+  ) {
+    if let Some(required) = schema_obj.get("required").and_then(|v| v.as_array()) {
+      for name in required.iter().filter_map(|v| v.as_str()) {
+        if !obj.contains_key(name) {
+          self.push_error(
+            &join_path(path, name),
+            "required property is missing".to_string(),
+          );
+        }
+      }
+    }
+    let properties = schema_obj.get("properties").and_then(|v| v.as_object());
+    let pattern_properties: Vec<(Option<Regex>, &'a Value)> = schema_obj
+      .get("patternProperties")
+      .and_then(|v| v.as_object())
+      .map(|p| p.iter().map(|(k, v)| (Regex::new(k).ok(), v)).collect())
+      .unwrap_or_default();
+    for (key, value) in obj.iter() {
+      let field_path = join_path(path, key);
+      let mut matched = false;
+      if let Some(field_schema) = properties.and_then(|p| p.get(key)) {
+        self.validate(field_schema, value, &field_path, depth);
+        matched = true;
+      }
+      for (pattern, field_schema) in pattern_properties.iter() {
+        if pattern.as_ref().is_some_and(|p| p.is_match(key)) {
+          self.validate(field_schema, value, &field_path, depth);
+          matched = true;
+        }
+      }
+      if matched {
+        continue;
+      }
+      match schema_obj.get("additionalProperties") {
+        Some(Value::Bool(false)) => {
+          self.push_error(&field_path, "unexpected property".to_string());
+        }
+        Some(additional_schema) => {
+          self.validate(additional_schema, value, &field_path, depth);
+        }
+        None => {}
+      }
+    }
+  }
+
+  fn validate_array(
+    &mut self,
+    schema_obj: &'a serde_json::Map<String, Value>,
+    items: &[Value],
+    path: &str,
+    depth: usize,
+  ) {
+    if let Some(min_items) = schema_obj.get("minItems").and_then(|v| v.as_u64()) {
+      if (items.len() as u64) < min_items {
+        self.push_error(
+          path,
+          format!(
+            "expected at least {} items, found {}",
+            min_items,
+            items.len()
+          ),
+        );
+      }
+    }
+    if let Some(max_items) = schema_obj.get("maxItems").and_then(|v| v.as_u64()) {
+      if (items.len() as u64) > max_items {
+        self.push_error(
+          path,
+          format!(
+            "expected at most {} items, found {}",
+            max_items,
+            items.len()
+          ),
+        );
+      }
+    }
+    // Positional schemas come from `prefixItems` (2020-12) or an array-valued
+    // `items` (earlier drafts). The rest of items follow the `items` schema.
+    let (prefix_schemas, rest_schema) =
+      match (schema_obj.get("prefixItems"), schema_obj.get("items")) {
+        (Some(Value::Array(prefix)), items_schema) => (prefix.as_slice(), items_schema),
+        (_, Some(Value::Array(prefix))) => (prefix.as_slice(), schema_obj.get("additionalItems")),
+        (_, items_schema) => (&[][..], items_schema),
+      };
+    for (idx, item) in items.iter().enumerate() {
+      let item_schema = match prefix_schemas.get(idx) {
+        Some(s) => s,
+        None => match rest_schema {
+          Some(s) => s,
+          None => continue,
+        },
+      };
+      self.validate(item_schema, item, &join_path(path, &idx.to_string()), depth);
+    }
+  }
+
+  fn validate_string(&mut self, schema_obj: &serde_json::Map<String, Value>, s: &str, path: &str) {
+    let length = s.chars().count() as u64;
+    if let Some(min_length) = schema_obj.get("minLength").and_then(|v| v.as_u64()) {
+      if length < min_length {
+        self.push_error(
+          path,
+          format!(
+            "expected at least {} characters, found {}",
+            min_length, length
+          ),
+        );
+      }
+    }
+    if let Some(max_length) = schema_obj.get("maxLength").and_then(|v| v.as_u64()) {
+      if length > max_length {
+        self.push_error(
+          path,
+          format!(
+            "expected at most {} characters, found {}",
+            max_length, length
+          ),
+        );
+      }
+    }
+    if let Some(pattern) = schema_obj.get("pattern").and_then(|v| v.as_str()) {
+      match Regex::new(pattern) {
+        Ok(re) => {
+          if !re.is_match(s) {
+            self.push_error(path, format!("does not match pattern {}", pattern));
+          }
+        }
+        Err(_) => {
+          self.push_error(path, format!("schema has an invalid pattern {}", pattern));
+        }
+      }
+    }
+  }
+
+  fn validate_number(&mut self, schema_obj: &serde_json::Map<String, Value>, n: f64, path: &str) {
+    let get_bound = |key: &str| schema_obj.get(key).and_then(|v| v.as_f64());
+    if let Some(minimum) = get_bound("minimum") {
+      if n < minimum {
+        self.push_error(
+          path,
+          format!("expected a value >= {}, found {}", minimum, n),
+        );
+      }
+    }
+    if let Some(maximum) = get_bound("maximum") {
+      if n > maximum {
+        self.push_error(
+          path,
+          format!("expected a value <= {}, found {}", maximum, n),
+        );
+      }
+    }
+    if let Some(minimum) = get_bound("exclusiveMinimum") {
+      if n <= minimum {
+        self.push_error(path, format!("expected a value > {}, found {}", minimum, n));
+      }
+    }
+    if let Some(maximum) = get_bound("exclusiveMaximum") {
+      if n >= maximum {
+        self.push_error(path, format!("expected a value < {}, found {}", maximum, n));
+      }
+    }
+  }
+
+  /**
+   * Validate the instance against each sub-schema separately and return the
+   * errors of every branch.
+   */
+  fn validate_branches(
+    &mut self,
+    sub_schemas: &'a [Value],
+    instance: &Value,
+    path: &str,
+    depth: usize,
+  ) -> Vec<Vec<ValidationError>> {
+    sub_schemas
+      .iter()
+      .map(|sub_schema| {
+        let mut branch = Validator {
+          root: self.root,
+          errors: Vec::new(),
+        };
+        branch.validate(sub_schema, instance, path, depth);
+        branch.errors
+      })
+      .collect()
+  }
+
+  /**
+   * Report the errors of the branch that is closest to match, which is usually
+   * the one the user intended.
+   */
+  fn append_closest_branch(&mut self, branch_errors: Vec<Vec<ValidationError>>) {
+    if let Some(closest) = branch_errors.into_iter().min_by_key(|e| e.len()) {
+      self.errors.extend(closest);
+    }
+  }
+
+  /**
+   * Resolve a local `$ref` such as `#/$defs/address` in the root schema.
+   */
+  fn resolve_ref(&self, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    if pointer.is_empty() {
+      Some(self.root)
+    } else {
+      self.root.pointer(pointer)
+    }
+  }
+
+  fn push_error(&mut self, path: &str, message: String) {
+    self.errors.push(ValidationError {
+      path: path.to_string(),
+      message,
+    });
+  }
+}
+
+/**
+ * Append a reference token to a JSON pointer, following RFC 6901 escaping.
+ */
+fn join_path(path: &str, token: &str) -> String {
+  format!("{}/{}", path, token.replace('~', "~0").replace('/', "~1"))
+}
+
+fn is_type_of(type_name: &str, instance: &Value) -> bool {
+  match type_name {
+    "string" => instance.is_string(),
+    "number" => instance.is_number(),
+    "integer" => {
+      instance.is_i64() || instance.is_u64() || instance.as_f64().is_some_and(|v| v.fract() == 0.0)
+    }
+    "boolean" => instance.is_boolean(),
+    "object" => instance.is_object(),
+    "array" => instance.is_array(),
+    "null" => instance.is_null(),
+    // Unknown type names are not enforced.
+    _ => true,
+  }
+}
+
+fn get_instance_type_name(instance: &Value) -> &'static str {
+  match instance {
+    Value::Null => "null",
+    Value::Bool(_) => "boolean",
+    Value::Number(n) => {
+      if n.is_f64() {
+        "number"
+      } else {
+        "integer"
+      }
+    }
+    Value::String(_) => "string",
+    Value::Array(_) => "array",
+    Value::Object(_) => "object",
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn test_validate_object() {
+    let schema = json!({
+      "type": "object",
+      "properties": {
+        "city": {"type": "string"},
+        "days": {"type": "integer"},
+        "unit": {"enum": ["C", "F"]},
+        "tags": {"type": "array", "items": {"type": "string"}}
+      },
+      "required": ["city"],
+      "additionalProperties": false
+    });
+    assert!(validate(&schema, &json!({"city": "Paris", "days": 3})).is_empty());
+
+    let errors = validate(
+      &schema,
+      &json!({"days": 1.5, "unit": "K", "tags": ["a", 1], "extra/key": true}),
+    );
+    let paths: Vec<&str> = errors.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(
+      paths,
+      vec!["/city", "/days", "/extra~1key", "/tags/1", "/unit"]
+    );
+    assert_eq!(
+      errors[1].to_string(),
+      "/days: expected integer, found number"
+    );
+  }
+
+  #[test]
+  fn test_validate_type_mismatch_on_root() {
+    let errors = validate(&json!({"type": ["object", "null"]}), &json!([]));
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+      errors[0].to_string(),
+      "/: expected object or null, found array"
+    );
+  }
+
+  #[test]
+  fn test_validate_bounds_and_pattern() {
+    let schema = json!({
+      "type": "object",
+      "properties": {
+        "age": {"type": "integer", "minimum": 0, "exclusiveMaximum": 150},
+        "code": {"type": "string", "pattern": "^[A-Z]{3}$", "maxLength": 3},
+        "ids": {"type": "array", "minItems": 1, "items": {"type": "integer"}}
+      }
+    });
+    assert!(validate(&schema, &json!({"age": 30, "code": "CDG", "ids": [1]})).is_empty());
+    let errors: Vec<String> = validate(&schema, &json!({"age": 150, "code": "cdg!", "ids": []}))
+      .iter()
+      .map(|e| e.to_string())
+      .collect();
+    assert_eq!(
+      errors,
+      vec![
+        "/age: expected a value < 150, found 150",
+        "/code: expected at most 3 characters, found 4",
+        "/code: does not match pattern ^[A-Z]{3}$",
+        "/ids: expected at least 1 items, found 0",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_validate_ref_and_combinators() {
+    let schema = json!({
+      "$defs": {
+        "point": {
+          "type": "object",
+          "properties": {"x": {"type": "number"}, "y": {"type": "number"}},
+          "required": ["x", "y"]
+        }
+      },
+      "type": "object",
+      "properties": {
+        "location": {
+          "anyOf": [{"$ref": "#/$defs/point"}, {"type": "string"}]
+        },
+        "id": {"oneOf": [{"type": "integer"}, {"type": "number"}]}
+      }
+    });
+    assert!(validate(&schema, &json!({"location": {"x": 1, "y": 2}, "id": 1.5})).is_empty());
+    assert!(validate(&schema, &json!({"location": "Paris"})).is_empty());
+
+    let errors: Vec<String> = validate(&schema, &json!({"location": {"x": 1}, "id": 1}))
+      .iter()
+      .map(|e| e.to_string())
+      .collect();
+    assert_eq!(
+      errors,
+      vec![
+        "/id: matches 2 schemas in oneOf, but exactly one is expected",
+        "/location: does not match any schema in anyOf",
+        "/location/y: required property is missing",
+      ]
+    );
+    let errors = validate(&json!({"$ref": "#/$defs/missing"}), &json!(1));
+    assert_eq!(
+      errors[0].to_string(),
+      "/: cannot resolve $ref #/$defs/missing"
+    );
+  }
+}
@@ -3,6 +3,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
+pub mod json_schema;
 pub mod notification;
 pub mod request;
 use serde::{Deserialize, Serialize};
@@ -73,6 +74,8 @@ pub struct MCPToolDefinition {
   pub description: Option<String>,
   #[serde(rename = "inputSchema")]
   pub input_schema: Value,
+  #[serde(rename = "outputSchema", skip_serializing_if = "Option::is_none")]
+  pub output_schema: Option<Value>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub annotations: Option<MCPToolAnnotations>,
 }
@@ -146,6 +149,22 @@ impl MCPResourceDefinition {
       uri_template: None,
     }
   }
+
+  pub fn resource_template(
+    uri_template: String,
+    name: String,
+    description: Option<String>,
+    mime_type: Option<String>,
+  ) -> MCPResourceDefinition {
+    MCPResourceDefinition {
+      uri: None,
+      name,
+      description,
+      mime_type,
+      size: None,
+      uri_template: Some(uri_template),
+    }
+  }
 }
 
 /**
@@ -154,15 +173,18 @@ impl MCPResourceDefinition {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct MCPResourceContent {
   pub uri: String,
+  #[serde(rename = "mimeType", skip_serializing_if = "Option::is_none")]
   pub mime: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub text: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub blob: Option<String>,
 }
 
 /**
  * Describe a MCP prompt.
  */
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct MCPPromptDefinition {
   pub name: String,
   pub description: Option<String>,
@@ -172,7 +194,7 @@ pub struct MCPPromptDefinition {
 /**
  * Describe an argument that a prompt can accept.
  */
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct MCPPromptArgument {
   pub name: String,
   pub description: Option<String>,
@@ -25,4 +25,12 @@ impl MCPNotification {
       params: None,
     }
   }
+
+  pub fn resources_list_changed() -> Self {
+    MCPNotification {
+      jsonrpc: "2.0".to_owned(),
+      method: "notifications/resources/list_changed".to_owned(),
+      params: None,
+    }
+  }
 }
@@ -0,0 +1,365 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+use crate::{AbstractMCPServer, ServerInfo};
+use base64::Engine;
+use nah_mcp_types::notification::MCPNotification;
+use nah_mcp_types::{MCPResourceContent, MCPResourceDefinition, MCPToolDefinition};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/**
+ * A ready-made MCP server exposing all files under a root directory as
+ * `file://` resources.
+ *
+ * ```no_run
+ * use nah_server::{run_mcp_server_with_stdio, FileSystemServer};
+ * use std::time::Duration;
+ *
+ * let mut server = FileSystemServer::new("./docs").unwrap();
+ * server.watch(Duration::from_secs(2));
+ * run_mcp_server_with_stdio(&mut server).unwrap();
+ * ```
+ */
+pub struct FileSystemServer {
+    root: PathBuf,
+    watching: bool,
+    notification_receiver: Option<Receiver<MCPNotification>>,
+}
+
+impl FileSystemServer {
+    /**
+     * Create a server for the given root directory.
+     */
+    pub fn new<P: AsRef<Path>>(root: P) -> std::io::Result<Self> {
+        let root = root.as_ref().canonicalize()?;
+        if !root.is_dir() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is not a directory", root.display()),
+            ));
+        }
+        Ok(FileSystemServer {
+            root,
+            watching: false,
+            notification_receiver: None,
+        })
+    }
+
+    /**
+     * Poll the root directory every `interval` and send
+     * `notifications/resources/list_changed` when files are added, removed
+     * or resized.
+     */
+    pub fn watch(&mut self, interval: Duration) {
+        let (tx, rx) = channel();
+        let root = self.root.clone();
+        thread::spawn(move || {
+            let mut snapshot = snapshot_files(&root);
+            loop {
+                thread::sleep(interval);
+                let new_snapshot = snapshot_files(&root);
+                if new_snapshot != snapshot {
+                    snapshot = new_snapshot;
+                    if tx.send(MCPNotification::resources_list_changed()).is_err() {
+                        // The receiver is gone, so nobody listens anymore.
+                        break;
+                    }
+                }
+            }
+        });
+        self.watching = true;
+        self.notification_receiver = Some(rx);
+    }
+
+    /**
+     * Map a `file://` URI to a path under the root. Return `None` if the URI
+     * is malformed or points outside of the root.
+     */
+    fn resolve_uri(&self, uri: &str) -> Option<PathBuf> {
+        let path = uri_to_path(uri)?;
+        let path = path.canonicalize().ok()?;
+        if path.starts_with(&self.root) && path.is_file() {
+            Some(path)
+        } else {
+            None
+        }
+    }
+}
+
+impl AbstractMCPServer for FileSystemServer {
+    fn get_server_info(&self) -> ServerInfo {
+        ServerInfo {
+            name: "nah-filesystem".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    fn get_tools_list(&self) -> Vec<MCPToolDefinition> {
+        Vec::new()
+    }
+
+    fn on_tool_call(
+        &mut self,
+        name: &str,
+        _args: Option<&serde_json::Map<String, Value>>,
+    ) -> String {
+        format!("Unknown tool: {}", name)
+    }
+
+    fn get_resources_list(&self) -> Vec<MCPResourceDefinition> {
+        let mut resources: Vec<MCPResourceDefinition> = snapshot_files(&self.root)
+            .into_iter()
+            .map(|(path, size)| {
+                let name = path
+                    .strip_prefix(&self.root)
+                    .unwrap_or(&path)
+                    .display()
+                    .to_string();
+                MCPResourceDefinition::direct_resource(
+                    path_to_uri(&path),
+                    name,
+                    None,
+                    Some(guess_mime_type(&path).to_string()),
+                    Some(size as usize),
+                )
+            })
+            .collect();
+        resources.push(MCPResourceDefinition::resource_template(
+            "file:///{path}".to_string(),
+            "file".to_string(),
+            Some(format!("Any file under {}", self.root.display())),
+            None,
+        ));
+        resources
+    }
+
+    fn on_resources_read(&self, uri: &str) -> Vec<MCPResourceContent> {
+        self.find_resource(uri).unwrap_or_default()
+    }
+
+    fn find_resource(&self, uri: &str) -> Option<Vec<MCPResourceContent>> {
+        let path = self.resolve_uri(uri)?;
+        let data = std::fs::read(&path).ok()?;
+        let mime_type = guess_mime_type(&path);
+        let (text, blob) = match is_text_mime_type(mime_type) {
+            true => match String::from_utf8(data) {
+                Ok(s) => (Some(s), None),
+                Err(e) => (
+                    None,
+                    Some(base64::engine::general_purpose::STANDARD.encode(e.into_bytes())),
+                ),
+            },
+            false => (
+                None,
+                Some(base64::engine::general_purpose::STANDARD.encode(data)),
+            ),
+        };
+        Some(vec![MCPResourceContent {
+            uri: uri.to_string(),
+            mime: Some(mime_type.to_string()),
+            text,
+            blob,
+        }])
+    }
+
+    fn resources_list_changed(&self) -> bool {
+        self.watching
+    }
+
+    fn take_notification_receiver(&mut self) -> Option<Receiver<MCPNotification>> {
+        self.notification_receiver.take()
+    }
+}
+
+/**
+ * Collect all regular files under `root` with their sizes, sorted by path.
+ * Symbolic links are skipped so that the walk never leaves the root.
+ */
+fn snapshot_files(root: &Path) -> Vec<(PathBuf, u64)> {
+    let mut result = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                result.push((entry.path(), size));
+            }
+        }
+    }
+    result.sort();
+    result
+}
+
+/**
+ * Convert an absolute path into a `file://` URI with percent-encoding.
+ */
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/**
+ * Convert a `file://` URI back into a path. Return `None` for other schemes or
+ * invalid percent-encoding.
+ */
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let high = (iter.next()? as char).to_digit(16)?;
+            let low = (iter.next()? as char).to_digit(16)?;
+            bytes.push((high * 16 + low) as u8);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/**
+ * Guess the MIME type of a file from its extension.
+ */
+fn guess_mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "txt" | "log" | "cfg" | "ini" | "conf" => "text/plain",
+        "md" | "markdown" => "text/markdown",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "rs" => "text/x-rust",
+        "py" => "text/x-python",
+        "c" | "h" => "text/x-c",
+        "cpp" | "cc" | "hpp" => "text/x-c++",
+        "java" => "text/x-java",
+        "go" => "text/x-go",
+        "sh" => "text/x-shellscript",
+        "js" | "mjs" => "text/javascript",
+        "ts" => "text/x-typescript",
+        "json" | "jsonl" => "application/json",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        "toml" => "application/toml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "wav" => "audio/wav",
+        "mp3" => "audio/mpeg",
+        _ => "application/octet-stream",
+    }
+}
+
+/**
+ * Whether contents of the MIME type should be returned as `text`.
+ */
+fn is_text_mime_type(mime_type: &str) -> bool {
+    mime_type.starts_with("text/")
+        || matches!(
+            mime_type,
+            "application/json"
+                | "application/xml"
+                | "application/yaml"
+                | "application/toml"
+                | "image/svg+xml"
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nah_mcp_types::request::MCPRequest;
+
+    fn create_test_root(name: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("nah_fs_server_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sub dir")).unwrap();
+        std::fs::write(root.join("a.txt"), "hello").unwrap();
+        std::fs::write(root.join("sub dir").join("b.png"), [0u8, 159, 146, 150]).unwrap();
+        root
+    }
+
+    #[test]
+    fn test_uri_round_trip() {
+        let path = PathBuf::from("/tmp/sub dir/%b.txt");
+        let uri = path_to_uri(&path);
+        assert_eq!(uri, "file:///tmp/sub%20dir/%25b.txt");
+        assert_eq!(uri_to_path(&uri), Some(path));
+        assert_eq!(uri_to_path("http://example.com/a"), None);
+    }
+
+    #[test]
+    fn test_list_and_read_resources() {
+        let root = create_test_root("list");
+        let server = FileSystemServer::new(&root).unwrap();
+        let resources = server.get_resources_list();
+        let direct: Vec<&MCPResourceDefinition> =
+            resources.iter().filter(|r| r.uri.is_some()).collect();
+        assert_eq!(direct.len(), 2);
+        assert_eq!(direct[0].name, "a.txt");
+        assert_eq!(direct[0].size, Some(5));
+        assert_eq!(direct[0].mime_type.as_deref(), Some("text/plain"));
+        assert!(resources
+            .iter()
+            .any(|r| r.uri_template.as_deref() == Some("file:///{path}")));
+
+        let text = server.on_resources_read(direct[0].uri.as_ref().unwrap());
+        assert_eq!(text[0].text.as_deref(), Some("hello"));
+        let blob = server.on_resources_read(direct[1].uri.as_ref().unwrap());
+        assert_eq!(blob[0].text, None);
+        assert_eq!(blob[0].blob.as_deref(), Some("AJ+Slg=="));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_block_path_traversal() {
+        let root = create_test_root("traversal");
+        let mut server = FileSystemServer::new(root.join("sub dir")).unwrap();
+        let escaped = format!("{}/../a.txt", path_to_uri(&server.root));
+        assert!(server.find_resource(&escaped).is_none());
+        assert!(server.on_resources_read("file:///etc/passwd").is_empty());
+        let request: MCPRequest = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "resources/read",
+            "params": {"uri": escaped}
+        }))
+        .unwrap();
+        let response = crate::process_routine::process_request(&mut server, request);
+        assert_eq!(response.error.unwrap()["code"], -32002);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,341 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::process_routine::process_request;
+use crate::AbstractMCPServer;
+use nah_mcp_types::request::MCPRequest;
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+/**
+ * Path of the MCP endpoint.
+ */
+pub const MCP_ENDPOINT: &str = "/mcp";
+
+/**
+ * Largest request body accepted, in bytes.
+ */
+const MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+/**
+ * Largest request line or header line accepted, in bytes.
+ */
+const MAX_LINE_SIZE: usize = 8 * 1024;
+
+/**
+ * Largest size of the request line and all headers together, in bytes.
+ */
+const MAX_HEADERS_SIZE: usize = 64 * 1024;
+
+/**
+ * Time to wait for the next bytes of a request before dropping the connection.
+ */
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/**
+ * Time given to a client to send a whole request, so that a slow client can't
+ * hold the connection, and the connections waiting behind it, forever.
+ */
+const REQUEST_DEADLINE: Duration = Duration::from_secs(60);
+
+/**
+ * A HTTP request received on the endpoint.
+ */
+#[derive(Debug)]
+struct HttpRequest {
+    method: String,
+    path: String,
+    /// Value of the `Origin` header sent by browsers.
+    origin: Option<String>,
+    body: Vec<u8>,
+}
+
+/**
+ * Run the given MCP Server with the Streamable HTTP transport at `address`,
+ * like `127.0.0.1:8080`. Each POST to `/mcp` carries one message and requests
+ * are answered with a JSON body. Connections are served one after another,
+ * each with a bounded header size and a deadline to send its request, and
+ * server notifications are not sent since no SSE stream is opened.
+ * Requests from a browser page with an `Origin` other than the local host or
+ * the bound host are rejected, to protect against DNS rebinding.
+ */
+pub fn run_mcp_server_with_http<T>(server: &mut T, address: &str) -> std::io::Result<()>
+where
+    T: AbstractMCPServer,
+{
+    let listener = TcpListener::bind(address)?;
+    let bound_host = address.rsplit_once(':').map_or(address, |(host, _)| host);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
+        if let Err(e) = serve_connection(server, stream, bound_host) {
+            eprintln!(
+                "[nah-server: {}] HTTP connection error: {}",
+                server.get_server_info().name,
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+fn serve_connection<T>(server: &mut T, stream: TcpStream, bound_host: &str) -> std::io::Result<()>
+where
+    T: AbstractMCPServer,
+{
+    stream.set_write_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(DeadlineStream {
+        stream: stream.try_clone()?,
+        deadline: Instant::now() + REQUEST_DEADLINE,
+    });
+    let mut stream = stream;
+    let request = match read_request(&mut reader)? {
+        Ok(request) => request,
+        Err(status) => return write_response(&mut stream, status, None),
+    };
+    if let Some(origin) = &request.origin {
+        if !is_allowed_origin(origin, bound_host) {
+            return write_response(&mut stream, "403 Forbidden", None);
+        }
+    }
+    if request.path.split('?').next() != Some(MCP_ENDPOINT) {
+        return write_response(&mut stream, "404 Not Found", None);
+    }
+    if request.method != "POST" {
+        return write_response(&mut stream, "405 Method Not Allowed", None);
+    }
+    let message: Value = match serde_json::from_slice(&request.body) {
+        Ok(v) => v,
+        Err(_) => return write_response(&mut stream, "400 Bad Request", None),
+    };
+    let is_request = message.get("method").is_some() && message.get("id").is_some();
+    if !is_request {
+        // Notifications and responses are accepted without a body.
+        return write_response(&mut stream, "202 Accepted", None);
+    }
+    let request: MCPRequest = match serde_json::from_value(message) {
+        Ok(r) => r,
+        Err(_) => return write_response(&mut stream, "400 Bad Request", None),
+    };
+    let response = process_request(server, request);
+    let body = serde_json::to_string(&response).unwrap();
+    write_response(&mut stream, "200 OK", Some(&body))
+}
+
+/**
+ * A stream whose reads fail once the deadline has passed, each read waiting
+ * at most until the deadline.
+ */
+struct DeadlineStream {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "request deadline exceeded",
+            ));
+        }
+        self.stream
+            .set_read_timeout(Some(remaining.min(READ_TIMEOUT)))?;
+        self.stream.read(buf)
+    }
+}
+
+/**
+ * Read a line of at most `MAX_LINE_SIZE` bytes into `line`, counting it in
+ * `total`. Return the status to answer with when the line or the headers are
+ * too large.
+ */
+fn read_limited_line<R: BufRead>(
+    reader: &mut R,
+    line: &mut String,
+    total: &mut usize,
+) -> std::io::Result<Result<usize, &'static str>> {
+    line.clear();
+    let size = reader
+        .by_ref()
+        .take(MAX_LINE_SIZE as u64 + 1)
+        .read_line(line)?;
+    *total += size;
+    if size > MAX_LINE_SIZE || *total > MAX_HEADERS_SIZE {
+        return Ok(Err("431 Request Header Fields Too Large"));
+    }
+    Ok(Ok(size))
+}
+
+/**
+ * Read a request line, headers and a body of `Content-Length` bytes. Return
+ * the status to answer with when the request is malformed or too large.
+ */
+fn read_request<R: BufRead>(reader: &mut R) -> std::io::Result<Result<HttpRequest, &'static str>> {
+    let mut line = String::new();
+    let mut total = 0;
+    if let Err(status) = read_limited_line(reader, &mut line, &mut total)? {
+        return Ok(Err(status));
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Ok(Err("400 Bad Request"));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+    let mut origin = None;
+    let mut content_length = 0;
+    loop {
+        match read_limited_line(reader, &mut line, &mut total)? {
+            Ok(0) => return Ok(Err("400 Bad Request")),
+            Ok(_) => {}
+            Err(status) => return Ok(Err(status)),
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                match value.trim().parse() {
+                    Ok(n) => content_length = n,
+                    Err(_) => return Ok(Err("400 Bad Request")),
+                }
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.trim().to_string());
+            }
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        return Ok(Err("413 Payload Too Large"));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Ok(HttpRequest {
+        method,
+        path,
+        origin,
+        body,
+    }))
+}
+
+/**
+ * Whether an `Origin` like `http://localhost:3000` names the local host or
+ * the host the server is bound to.
+ */
+fn is_allowed_origin(origin: &str, bound_host: &str) -> bool {
+    let Some((_, authority)) = origin.split_once("://") else {
+        return false;
+    };
+    let authority = authority.split('/').next().unwrap_or_default();
+    let host = if authority.starts_with('[') {
+        authority.split_inclusive(']').next().unwrap_or_default()
+    } else {
+        authority.split(':').next().unwrap_or_default()
+    };
+    ["localhost", "127.0.0.1", "[::1]", bound_host]
+        .iter()
+        .any(|allowed| host.eq_ignore_ascii_case(allowed))
+}
+
+fn write_response(stream: &mut TcpStream, status: &str, body: Option<&str>) -> std::io::Result<()> {
+    let mut response = format!("HTTP/1.1 {}\r\nConnection: close\r\n", status);
+    match body {
+        Some(body) => response.push_str(&format!(
+            "Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )),
+        None => response.push_str("Content-Length: 0\r\n\r\n"),
+    }
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_request() {
+        let data =
+            "POST /mcp HTTP/1.1\r\nHost: localhost\r\ncontent-length: 7\r\n\r\n{\"a\":1}trailing";
+        let request = read_request(&mut data.as_bytes()).unwrap().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/mcp");
+        assert_eq!(request.origin, None);
+        assert_eq!(request.body, b"{\"a\":1}");
+        assert_eq!(
+            read_request(&mut "GARBAGE\r\n".as_bytes()).unwrap().err(),
+            Some("400 Bad Request")
+        );
+        let data = "POST /mcp HTTP/1.1\r\nContent-Length: 999999999999\r\n\r\n";
+        assert_eq!(
+            read_request(&mut data.as_bytes()).unwrap().err(),
+            Some("413 Payload Too Large")
+        );
+        let data = "POST /mcp HTTP/1.1\r\nOrigin: http://localhost:3000\r\n\r\n";
+        let request = read_request(&mut data.as_bytes()).unwrap().unwrap();
+        assert_eq!(request.origin.as_deref(), Some("http://localhost:3000"));
+    }
+
+    #[test]
+    fn test_read_request_header_limits() {
+        let long_line = format!(
+            "POST /mcp HTTP/1.1\r\nX-Long: {}\r\n\r\n",
+            "a".repeat(MAX_LINE_SIZE)
+        );
+        assert_eq!(
+            read_request(&mut long_line.as_bytes()).unwrap().err(),
+            Some("431 Request Header Fields Too Large")
+        );
+        let header = format!("X-Header: {}\r\n", "a".repeat(1000));
+        let many_headers = format!("POST /mcp HTTP/1.1\r\n{}\r\n", header.repeat(100));
+        assert_eq!(
+            read_request(&mut many_headers.as_bytes()).unwrap().err(),
+            Some("431 Request Header Fields Too Large")
+        );
+        let data = format!("POST /mcp HTTP/1.1\r\n{}\r\n", header.repeat(10));
+        assert!(read_request(&mut data.as_bytes()).unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_request_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(DeadlineStream {
+            stream,
+            deadline: Instant::now() + Duration::from_millis(100),
+        });
+        let start = Instant::now();
+        let error = read_request(&mut reader).unwrap_err();
+        assert!(start.elapsed() < READ_TIMEOUT);
+        assert!(matches!(
+            error.kind(),
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+        ));
+    }
+
+    #[test]
+    fn test_is_allowed_origin() {
+        assert!(is_allowed_origin("http://localhost:3000", "127.0.0.1"));
+        assert!(is_allowed_origin("http://127.0.0.1", "127.0.0.1"));
+        assert!(is_allowed_origin("http://[::1]:8080", "127.0.0.1"));
+        assert!(is_allowed_origin(
+            "https://mcp.example.com/",
+            "mcp.example.com"
+        ));
+        assert!(!is_allowed_origin("https://evil.example.com", "127.0.0.1"));
+        assert!(!is_allowed_origin("http://localhost.evil.com", "127.0.0.1"));
+        assert!(!is_allowed_origin("null", "127.0.0.1"));
+    }
+}
@@ -3,12 +3,19 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-use nah_mcp_types::{MCPResourceContent, MCPResourceDefinition, MCPToolDefinition};
+use nah_mcp_types::notification::MCPNotification;
+use nah_mcp_types::request::MCPRequest;
+use nah_mcp_types::{MCPResourceContent, MCPResourceDefinition, MCPResponse, MCPToolDefinition};
 use serde::Serialize;
 use serde_json::Value;
+use std::sync::mpsc::Receiver;
 
+mod filesystem_server;
+mod http_server;
 pub(crate) mod process_routine;
 mod stdio_server;
+pub use crate::filesystem_server::FileSystemServer;
+pub use crate::http_server::{run_mcp_server_with_http, MCP_ENDPOINT};
 pub use crate::stdio_server::run_mcp_server_with_stdio;
 
 /**
@@ -20,6 +27,15 @@ pub struct ServerInfo {
     pub version: String,
 }
 
+/**
+ * Output of a tool call with optional structured content.
+ */
+#[derive(Debug, Clone)]
+pub struct ToolCallOutput {
+    pub text: String,
+    pub structured_content: Option<Value>,
+}
+
 /**
  * The trait for MCP Server instances. All routines in this package will
  * operate on the instance of this trait.
@@ -45,6 +61,27 @@ pub trait AbstractMCPServer {
     fn on_tool_call(&mut self, name: &str, args: Option<&serde_json::Map<String, Value>>)
         -> String;
 
+    /**
+     * Respond to the tool calls with structured content. Tools declaring an
+     * `output_schema` should override this method, and the structured content
+     * will be validated against the schema before responding. By default it
+     * returns the result of `on_tool_call` without structured content.
+     *
+     * Args:
+     * * `name`: the name of the function to be caled.
+     * * `args`: the arguments of the function call in JSON Value
+     */
+    fn on_structured_tool_call(
+        &mut self,
+        name: &str,
+        args: Option<&serde_json::Map<String, Value>>,
+    ) -> ToolCallOutput {
+        ToolCallOutput {
+            text: self.on_tool_call(name, args),
+            structured_content: None,
+        }
+    }
+
     /**
      * Return a Vec of all resource definitions.
      */
@@ -57,4 +94,42 @@ pub trait AbstractMCPServer {
      * * `uri`: the request URI
      */
     fn on_resources_read(&self, uri: &str) -> Vec<MCPResourceContent>;
+
+    /**
+     * Respond to the resource read requests, or return `None` when the
+     * resource doesn't exist so that a "Resource not found" error is sent.
+     * By default it returns the result of `on_resources_read`.
+     *
+     * Args:
+     * * `uri`: the request URI
+     */
+    fn find_resource(&self, uri: &str) -> Option<Vec<MCPResourceContent>> {
+        Some(self.on_resources_read(uri))
+    }
+
+    /**
+     * Whether the server may send `notifications/resources/list_changed`.
+     * It is reported to the client as the `resources.listChanged` capability.
+     */
+    fn resources_list_changed(&self) -> bool {
+        false
+    }
+
+    /**
+     * Take the receiver of notifications that the server pushes to the client.
+     * It is called once before serving requests. Return `None` if the server
+     * never sends notifications.
+     */
+    fn take_notification_receiver(&mut self) -> Option<Receiver<MCPNotification>> {
+        None
+    }
+
+    /**
+     * Respond to a request before the built-in routines. Servers answering
+     * methods that are not supported by the routines should override this
+     * method. Return `None` to let the routines process the request.
+     */
+    fn on_request(&mut self, _request: &MCPRequest) -> Option<MCPResponse> {
+        None
+    }
 }
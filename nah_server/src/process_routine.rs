@@ -4,10 +4,33 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 use crate::AbstractMCPServer;
+use nah_mcp_types::json_schema;
 use nah_mcp_types::request::MCPRequest;
 use nah_mcp_types::MCPResponse;
 use serde_json::{json, Value};
 
+/**
+ * Respond to a request with the `on_request` hook of the server, or the
+ * built-in routine of its method.
+ */
+pub fn process_request<T>(server: &mut T, request: MCPRequest) -> MCPResponse
+where
+    T: AbstractMCPServer,
+{
+    if let Some(response) = server.on_request(&request) {
+        return response;
+    }
+    match request.method.as_str() {
+        "initialize" => process_initialize(server, request),
+        "tools/list" => process_tools_list(server, request),
+        "tools/call" => process_tools_call(server, request),
+        "resources/list" => process_resources_list(server, request),
+        "resources/templates/list" => process_resources_templates_list(server, request),
+        "resources/read" => process_resources_read(server, request),
+        _ => invalid_request(&request.id, format!("Unknown method: {}", request.method)),
+    }
+}
+
 /**
  * Process the initialize request
  */
@@ -23,7 +46,7 @@ where
                 "listChanged": false
             },
             "resources": {
-                "listChanged": false
+                "listChanged": server.resources_list_changed()
             }
         }
     });
@@ -87,11 +110,56 @@ where
         );
     };
     let args = params.get("arguments").and_then(|v| v.as_object());
-    let response_content = server.on_tool_call(name, args);
+    let output_schema = server
+        .get_tools_list()
+        .into_iter()
+        .find(|t| t.name == name)
+        .and_then(|t| t.output_schema);
+    let output = server.on_structured_tool_call(name, args);
+    let Some(structured_content) = output.structured_content else {
+        if output_schema.is_some() {
+            return internal_error_response(
+                id,
+                format!(
+                    "Tool {} declares an outputSchema but returned no structured content",
+                    name
+                ),
+            );
+        }
+        return MCPResponse::new(
+            id.clone(),
+            Some(json!({
+                "content": [{"type": "text", "text": output.text}]
+            })),
+            None,
+        );
+    };
+    if let Some(schema) = output_schema {
+        let errors = json_schema::validate(&schema, &structured_content);
+        if !errors.is_empty() {
+            let details: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            return internal_error_response(
+                id,
+                format!(
+                    "Structured content of tool {} does not match its outputSchema: {}",
+                    name,
+                    details.join("; ")
+                ),
+            );
+        }
+    }
+    // Keep a serialized copy in the text content for clients without
+    // structured output support.
+    let text = if output.text.is_empty() {
+        structured_content.to_string()
+    } else {
+        output.text
+    };
     MCPResponse::new(
         id.clone(),
         Some(json!({
-            "content": [{"type": "text", "text": response_content}]
+            "content": [{"type": "text", "text": text}],
+            "structuredContent": structured_content
         })),
         None,
     )
@@ -166,10 +234,24 @@ where
         }
     };
 
-    let contents = server.on_resources_read(uri);
+    let Some(contents) = server.find_resource(uri) else {
+        return resource_not_found_error_response(id, uri);
+    };
     MCPResponse::new(id.clone(), Some(json!({"contents": contents})), None)
 }
 
+fn resource_not_found_error_response(id: &Value, uri: &str) -> MCPResponse {
+    MCPResponse::new(
+        id.clone(),
+        None,
+        Some(json!({
+            "code": -32002,
+            "message": "Resource not found",
+            "data": {"uri": uri}
+        })),
+    )
+}
+
 fn invalid_params_error_response(id: &Value, message: String) -> MCPResponse {
     MCPResponse::new(
         id.clone(),
@@ -181,6 +263,17 @@ fn invalid_params_error_response(id: &Value, message: String) -> MCPResponse {
     )
 }
 
+fn internal_error_response(id: &Value, message: String) -> MCPResponse {
+    MCPResponse::new(
+        id.clone(),
+        None,
+        Some(json!({
+            "code": -32603,
+            "message": message
+        })),
+    )
+}
+
 pub fn invalid_request(id: &Value, message: String) -> MCPResponse {
     MCPResponse::new(
         id.clone(),
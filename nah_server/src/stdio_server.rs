@@ -4,16 +4,14 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use crate::process_routine::{
-    invalid_request, process_initialize, process_resources_list, process_resources_read,
-    process_resources_templates_list, process_tools_call, process_tools_list,
-};
+use crate::process_routine::process_request;
 use crate::AbstractMCPServer;
 use nah_mcp_types::request::MCPRequest;
-use nah_mcp_types::MCPResponse;
+use serde::Serialize;
 use serde_json::Value;
 use std::error::Error;
 use std::io::{stderr, stdin, stdout, Write};
+use std::thread;
 
 /**
  * Run the given MCP Server in STDIO.
@@ -22,11 +20,23 @@ pub fn run_mcp_server_with_stdio<T>(server: &mut T) -> std::io::Result<()>
 where
     T: AbstractMCPServer,
 {
+    if let Some(receiver) = server.take_notification_receiver() {
+        thread::spawn(move || {
+            for notification in receiver {
+                if send_message(&notification).is_err() {
+                    break;
+                }
+            }
+        });
+    }
     let stdin = stdin();
     let mut buf = String::new();
     loop {
         buf.clear();
-        stdin.read_line(&mut buf)?;
+        if stdin.read_line(&mut buf)? == 0 {
+            // The client closed stdin, which asks the server to exit.
+            return Ok(());
+        }
         let request: MCPRequest = match serde_json::from_str::<Value>(&buf.trim()) {
             Ok(r) => {
                 if r.as_object().is_some_and(|r| r.contains_key("id")) {
@@ -48,16 +58,8 @@ where
             }
         };
 
-        let response = match request.method.as_str() {
-            "initialize" => process_initialize(server, request),
-            "tools/list" => process_tools_list(server, request),
-            "tools/call" => process_tools_call(server, request),
-            "resources/list" => process_resources_list(server, request),
-            "resources/templates/list" => process_resources_templates_list(server, request),
-            "resources/read" => process_resources_read(server, request),
-            _ => invalid_request(&request.id, format!("Unknown method: {}", request.method)),
-        };
-        send_response(response)?;
+        let response = process_request(server, request);
+        send_message(&response)?;
     }
 }
 
@@ -84,13 +86,14 @@ where
 }
 
 /**
- * Send a response to stdout.
+ * Send a response or notification to stdout. The lock on stdout keeps
+ * messages from different threads on separated lines.
  */
-fn send_response(resp: MCPResponse) -> std::io::Result<()> {
-    let resp_str = serde_json::to_string(&resp).unwrap();
-    let mut stdout = stdout();
-    stdout.write(resp_str.as_bytes())?;
-    stdout.write(b"\n")?;
+fn send_message<T: Serialize>(message: &T) -> std::io::Result<()> {
+    let message_str = serde_json::to_string(message).unwrap();
+    let mut stdout = stdout().lock();
+    stdout.write_all(message_str.as_bytes())?;
+    stdout.write_all(b"\n")?;
     stdout.flush()?;
     Ok(())
 }